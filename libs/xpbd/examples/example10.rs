@@ -0,0 +1,145 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+};
+use xpbd::{character::*, colliders::*, components::*, XpbdPlugin};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(Example10Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example10Plugin;
+
+impl Plugin for Example10Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example10Plugin::startup)
+            .add_system(Example10Plugin::control);
+    }
+}
+
+/// Vertical speed of the player, the controller itself has no velocity
+#[derive(Component, Default)]
+struct Player {
+    fall_speed: f32,
+}
+
+const RADIUS: f32 = 12.;
+const HALF_HEIGHT: f32 = 12.;
+const WALK_SPEED: f32 = 200.;
+const JUMP_SPEED: f32 = 400.;
+const GRAVITY: f32 = 1000.;
+
+impl Example10Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+        let blue = materials.add(ColorMaterial::from(Color::rgb(0.3, 0.5, 1.)));
+
+        // ground, low steps the player walks onto, a wall to jump over and a ceiling
+        for (pos, size) in [
+            (Vec2::new(0., -250.), Vec2::new(800., 20.)),
+            (Vec2::new(-150., -234.), Vec2::new(80., 12.)),
+            (Vec2::new(-130., -222.), Vec2::new(40., 12.)),
+            (Vec2::new(100., -190.), Vec2::new(20., 100.)),
+            (Vec2::new(-300., -100.), Vec2::new(200., 20.)),
+            (Vec2::new(-400., 0.), Vec2::new(20., 500.)),
+            (Vec2::new(400., 0.), Vec2::new(20., 500.)),
+        ] {
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: quad.clone().into(),
+                    material: white.clone(),
+                    transform: Transform {
+                        scale: size.extend(1.),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StaticBoxBundle {
+                    pos: Pos(pos),
+                    collider: BoxCollider { size },
+                    ..default()
+                });
+        }
+
+        commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(
+                        shape::Capsule {
+                            radius: RADIUS,
+                            depth: HALF_HEIGHT * 2.,
+                            ..default()
+                        }
+                        .into(),
+                    )
+                    .into(),
+                material: blue,
+                ..default()
+            })
+            .insert(CharacterControllerBundle {
+                controller: CharacterController {
+                    step_height: 14.,
+                    ..default()
+                },
+                ..CharacterControllerBundle::new(
+                    Vec2::new(0., -200.),
+                    CharacterShape::Capsule {
+                        radius: RADIUS,
+                        half_height: HALF_HEIGHT,
+                    },
+                )
+            })
+            .insert(Player::default());
+    }
+
+    // arrows to walk, space to jump. Movement accumulates until the next physics step consumes it
+    fn control(
+        keyboard: Res<Input<KeyCode>>,
+        time: Res<Time>,
+        mut query: Query<(&mut CharacterController, &CharacterContacts, &mut Player)>,
+    ) {
+        let dt = time.delta_seconds();
+
+        for (mut controller, contacts, mut player) in query.iter_mut() {
+            let mut walk = 0.;
+
+            if keyboard.pressed(KeyCode::Left) {
+                walk -= WALK_SPEED;
+            }
+            if keyboard.pressed(KeyCode::Right) {
+                walk += WALK_SPEED;
+            }
+
+            if contacts.is_grounded() {
+                player.fall_speed = if keyboard.just_pressed(KeyCode::Space) {
+                    JUMP_SPEED
+                } else {
+                    0.
+                };
+            } else if contacts.ceiling.is_some() {
+                player.fall_speed = player.fall_speed.min(0.);
+            }
+
+            player.fall_speed -= GRAVITY * dt;
+            controller.displacement += Vec2::new(walk, player.fall_speed) * dt;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+};
+use xpbd::{
+    colliders::*,
+    components::*,
+    resources::{Gravity, SolverMode, SolverSettings},
+    XpbdPlugin,
+};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(Example11Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example11Plugin;
+
+impl Plugin for Example11Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Gravity(Vec2::new(0., -500.)))
+            .add_startup_system(Example11Plugin::startup)
+            .add_system(Example11Plugin::control);
+    }
+}
+
+#[derive(Resource)]
+struct Materials {
+    sphere: Handle<Mesh>,
+    blue: Handle<ColorMaterial>,
+}
+
+#[derive(Component)]
+struct Ball;
+
+const RADIUS: f32 = 10.;
+const ROWS: usize = 12;
+
+impl Example11Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let sphere = meshes.add(shape::Circle::new(1.).into());
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+        let blue = materials.add(ColorMaterial::from(Color::MIDNIGHT_BLUE));
+
+        // a pit just wide enough for the bottom row, so a leaning pile shows against the walls
+        let half_width = ROWS as f32 * RADIUS + 1.;
+
+        for (pos, size) in [
+            (Vec2::new(0., -210.), Vec2::new(600., 20.)),
+            (Vec2::new(-half_width - 10., -100.), Vec2::new(20., 200.)),
+            (Vec2::new(half_width + 10., -100.), Vec2::new(20., 200.)),
+        ] {
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: quad.clone().into(),
+                    material: white.clone(),
+                    transform: Transform {
+                        scale: size.extend(1.),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StaticBoxBundle {
+                    pos: Pos(pos),
+                    collider: BoxCollider { size },
+                    ..default()
+                });
+        }
+
+        let materials = Materials { sphere, blue };
+
+        Self::spawn_pile(&mut commands, &materials);
+        commands.insert_resource(materials);
+    }
+
+    // a pyramid of balls standing on the ground of the pit
+    fn spawn_pile(commands: &mut Commands, materials: &Materials) {
+        for row in 0..ROWS {
+            let count = ROWS - row;
+
+            for column in 0..count {
+                let pos = Vec2::new(
+                    (column as f32 - (count - 1) as f32 / 2.) * 2. * RADIUS,
+                    -200. + RADIUS + row as f32 * 1.8 * RADIUS,
+                );
+
+                commands
+                    .spawn(MaterialMesh2dBundle {
+                        mesh: materials.sphere.clone().into(),
+                        material: materials.blue.clone(),
+                        transform: Transform {
+                            scale: Vec3::splat(RADIUS),
+                            translation: pos.extend(0.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(ParticleBundle {
+                        collider: CircleCollider { radius: RADIUS },
+                        ..ParticleBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                    })
+                    .insert(Ball);
+            }
+        }
+    }
+
+    // M switches between gauss-seidel and jacobi contact solving, R drops a new pile
+    fn control(
+        mut commands: Commands,
+        keyboard: Res<Input<KeyCode>>,
+        mut settings: ResMut<SolverSettings>,
+        materials: Res<Materials>,
+        balls: Query<Entity, With<Ball>>,
+    ) {
+        if keyboard.just_pressed(KeyCode::M) {
+            settings.mode = match settings.mode {
+                SolverMode::GaussSeidel => SolverMode::Jacobi,
+                SolverMode::Jacobi => SolverMode::GaussSeidel,
+            };
+            info!("solver mode: {:?}", settings.mode);
+        }
+
+        if keyboard.just_pressed(KeyCode::R) {
+            for ball in balls.iter() {
+                commands.entity(ball).despawn();
+            }
+
+            Self::spawn_pile(&mut commands, &materials);
+        }
+    }
+}
@@ -5,15 +5,26 @@ use bevy::{
     time::FixedTimestep,
 };
 use rand::random;
-use xpbd::{colliders::*, components::*, XpbdPlugin};
+use xpbd::{
+    colliders::*, components::*, diagnostics::XpbdDiagnosticsPlugin,
+    recording::PhysicsRecorderPlugin, XpbdPlugin,
+};
 
 fn main() {
-    App::new()
-        .add_plugin(LogDiagnosticsPlugin::default())
+    let mut app = App::new();
+
+    app.add_plugin(LogDiagnosticsPlugin::default())
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_plugins(DefaultPlugins)
         .add_plugin(XpbdPlugin)
-        .add_plugin(Example4Plugin)
+        .add_plugin(XpbdDiagnosticsPlugin);
+
+    // the recording can be watched with the replay example
+    if let Ok(path) = std::env::var("XPBD_RECORD") {
+        app.add_plugin(PhysicsRecorderPlugin::new(path));
+    }
+
+    app.add_plugin(Example4Plugin)
         .add_startup_system(app_startup)
         .run();
 }
@@ -75,7 +86,7 @@ impl Example4Plugin {
     }
 
     fn spawn_boxes(mut commands: Commands, materials: Res<Materials>, meshes: Res<Meshes>) {
-        let size = Vec2::splat(20.);
+        let size = Vec2::splat(10. + random::<f32>() * 20.);
         let pos = Vec2::new(
             (random::<f32>() - 0.5) * 500.,
             (random::<f32>() - 0.5) * 50.,
@@ -99,7 +110,8 @@ impl Example4Plugin {
             .insert(DynamicBoxBundle {
                 collider: BoxCollider { size },
                 ..DynamicBoxBundle::new_with_pos_and_vel(pos, vel)
-            });
+            })
+            .insert(Density(1.));
     }
 
     fn despawn_boxes(mut commands: Commands, query: Query<(Entity, &Pos)>) {
@@ -0,0 +1,87 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+};
+use xpbd::{colliders::*, components::*, resources::Gravity, XpbdPlugin};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(Example5Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example5Plugin;
+
+impl Plugin for Example5Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example5Plugin::startup)
+            .insert_resource(Gravity(Vec2::new(0., -500.)));
+    }
+}
+
+impl Example5Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let blue = materials.add(ColorMaterial::from(Color::MIDNIGHT_BLUE));
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+
+        let static_size = Vec2::new(1000., 20.);
+
+        commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: quad.clone().into(),
+                material: blue,
+                transform: Transform::from_scale(static_size.extend(1.)),
+                ..default()
+            })
+            .insert(StaticBoxBundle {
+                pos: Pos(Vec2::new(0., -200.)),
+                collider: BoxCollider { size: static_size },
+                friction: Friction(0.5),
+                ..default()
+            });
+
+        let size = Vec2::splat(30.);
+
+        for column in 0..5 {
+            for row in 0..10 {
+                let pos = Vec2::new(
+                    (column as f32 - 2.) * size.x * 3. + (row % 2) as f32 * 2.,
+                    -200. + (static_size.y + size.y) / 2. + row as f32 * size.y,
+                );
+
+                commands
+                    .spawn(MaterialMesh2dBundle {
+                        mesh: quad.clone().into(),
+                        material: white.clone(),
+                        transform: Transform {
+                            scale: size.extend(1.),
+                            translation: pos.extend(0.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(DynamicBoxBundle {
+                        collider: BoxCollider { size },
+                        friction: Friction(0.5),
+                        ..DynamicBoxBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                    })
+                    .insert(Density(1.));
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    time::FixedTimestep,
+};
+use rand::random;
+use xpbd::{colliders::*, components::*, resources::Gravity, terrain::*, XpbdPlugin};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(Example6Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example6Plugin;
+
+impl Plugin for Example6Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example6Plugin::startup)
+            .insert_resource(Gravity(Vec2::new(0., -300.)))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(1. / 5.))
+                    .with_system(Example6Plugin::spawn_bodies),
+            )
+            .add_system(Example6Plugin::despawn_bodies);
+    }
+}
+
+#[derive(Resource)]
+struct Materials {
+    blue: Handle<ColorMaterial>,
+    white: Handle<ColorMaterial>,
+}
+
+#[derive(Resource)]
+struct Meshes {
+    quad: Handle<Mesh>,
+    sphere: Handle<Mesh>,
+}
+
+const TILE_SIZE: f32 = 20.;
+
+// bottom row first
+const TILES: [&str; 6] = [
+    "##############################",
+    "##############################",
+    "#####....................#####",
+    "####......................####",
+    "###........................###",
+    "##..........................##",
+];
+
+impl Example6Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let sphere = meshes.add(shape::Circle::new(1.).into());
+        let blue = materials.add(ColorMaterial::from(Color::MIDNIGHT_BLUE));
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+
+        let width = TILES[0].len() as u32;
+        let solid: Vec<bool> = TILES
+            .iter()
+            .flat_map(|row| row.chars().map(|tile| tile == '#'))
+            .collect();
+        let tile_map = TileMapCollider::new(Vec2::splat(TILE_SIZE), width, &solid);
+        let tile_map_pos = Vec2::new(-(width as f32) * TILE_SIZE / 2., -300.);
+
+        for rect in tile_map.rects() {
+            let (center, size) = tile_map.rect_bounds(rect);
+
+            commands.spawn(MaterialMesh2dBundle {
+                mesh: quad.clone().into(),
+                material: blue.clone(),
+                transform: Transform {
+                    scale: size.extend(1.),
+                    translation: (tile_map_pos + center).extend(0.),
+                    ..default()
+                },
+                ..default()
+            });
+        }
+
+        commands.spawn(StaticTerrainBundle::new(tile_map_pos, tile_map));
+
+        let chain = ChainCollider {
+            points: vec![
+                Vec2::new(-250., 50.),
+                Vec2::new(-150., 0.),
+                Vec2::new(-50., -20.),
+                Vec2::new(0., -20.),
+            ],
+            one_sided: false,
+        };
+
+        for segment in chain.points.windows(2) {
+            Self::spawn_segment(&mut commands, &quad, &blue, segment[0], segment[1]);
+        }
+
+        commands.spawn(StaticTerrainBundle::new(Vec2::ZERO, chain));
+
+        // one-sided platform, bodies only land on it from above
+        let platform = SegmentCollider {
+            a: Vec2::new(50., -100.),
+            b: Vec2::new(250., -100.),
+            one_sided: true,
+        };
+
+        Self::spawn_segment(&mut commands, &quad, &white, platform.a, platform.b);
+        commands.spawn(StaticTerrainBundle::new(Vec2::ZERO, platform));
+
+        commands.insert_resource(Meshes { quad, sphere });
+        commands.insert_resource(Materials { blue, white });
+    }
+
+    fn spawn_segment(
+        commands: &mut Commands,
+        quad: &Handle<Mesh>,
+        material: &Handle<ColorMaterial>,
+        a: Vec2,
+        b: Vec2,
+    ) {
+        let ab = b - a;
+
+        commands.spawn(MaterialMesh2dBundle {
+            mesh: quad.clone().into(),
+            material: material.clone(),
+            transform: Transform {
+                scale: Vec3::new(ab.length(), 2., 1.),
+                rotation: Quat::from_rotation_z(ab.y.atan2(ab.x)),
+                translation: ((a + b) / 2.).extend(0.),
+            },
+            ..default()
+        });
+    }
+
+    fn spawn_bodies(mut commands: Commands, materials: Res<Materials>, meshes: Res<Meshes>) {
+        let pos = Vec2::new((random::<f32>() - 0.5) * 500., 250.);
+        let size = 5. + random::<f32>() * 10.;
+
+        if random::<bool>() {
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: meshes.sphere.clone().into(),
+                    material: materials.white.clone(),
+                    transform: Transform {
+                        scale: Vec3::splat(size),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(ParticleBundle {
+                    collider: CircleCollider { radius: size },
+                    ..ParticleBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                });
+        } else {
+            let size = Vec2::splat(size * 2.);
+
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: meshes.quad.clone().into(),
+                    material: materials.white.clone(),
+                    transform: Transform {
+                        scale: size.extend(1.),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(DynamicBoxBundle {
+                    collider: BoxCollider { size },
+                    ..DynamicBoxBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                });
+        }
+    }
+
+    fn despawn_bodies(mut commands: Commands, query: Query<(Entity, &Pos, &RigidBody)>) {
+        for (entity, pos, rigid_body) in query.iter() {
+            if !rigid_body.is_static() && (pos.0.y < -800. || pos.0.x.abs() > 1000.) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::Mesh2dHandle,
+};
+use xpbd::{
+    colliders::*, mouse_drag::MouseDragPlugin, resources::Gravity, scene::PhysicsScene, terrain::*,
+    XpbdPlugin,
+};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        // edit assets/scenes/example7.physics.ron while the example runs to reload the scene
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        }))
+        .add_plugin(XpbdPlugin)
+        // drag bodies around with the left mouse button
+        .add_plugin(MouseDragPlugin)
+        .add_plugin(Example7Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example7Plugin;
+
+impl Plugin for Example7Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example7Plugin::startup)
+            .insert_resource(Gravity(Vec2::new(0., -300.)))
+            .add_system(Example7Plugin::add_body_meshes)
+            .add_system(Example7Plugin::add_segment_meshes)
+            .add_system(Example7Plugin::add_chain_meshes)
+            .add_system(Example7Plugin::add_tile_map_meshes);
+    }
+}
+
+#[derive(Resource)]
+struct Materials {
+    blue: Handle<ColorMaterial>,
+    white: Handle<ColorMaterial>,
+}
+
+#[derive(Resource)]
+struct Meshes {
+    quad: Handle<Mesh>,
+    sphere: Handle<Mesh>,
+}
+
+impl Example7Plugin {
+    fn startup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let scene: Handle<PhysicsScene> = asset_server.load("scenes/example7.physics.ron");
+
+        commands.spawn(scene);
+
+        commands.insert_resource(Meshes {
+            quad: meshes.add(shape::Quad::new(Vec2::ONE).into()),
+            sphere: meshes.add(shape::Circle::new(1.).into()),
+        });
+        commands.insert_resource(Materials {
+            blue: materials.add(ColorMaterial::from(Color::MIDNIGHT_BLUE)),
+            white: materials.add(ColorMaterial::from(Color::WHITE)),
+        });
+    }
+
+    // scene bodies come without visuals, so they are added as the bodies appear
+    #[allow(clippy::type_complexity)]
+    fn add_body_meshes(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        meshes: Res<Meshes>,
+        mut query: Query<
+            (
+                Entity,
+                &mut Transform,
+                Option<&CircleCollider>,
+                Option<&BoxCollider>,
+            ),
+            Or<(Added<CircleCollider>, Added<BoxCollider>)>,
+        >,
+    ) {
+        for (entity, mut transform, circle, box_) in query.iter_mut() {
+            let (mesh, scale) = match (circle, box_) {
+                (Some(circle), _) => (meshes.sphere.clone(), Vec2::splat(circle.radius)),
+                (_, Some(box_)) => (meshes.quad.clone(), box_.size),
+                _ => continue,
+            };
+
+            transform.scale = scale.extend(1.);
+            commands.entity(entity).insert((
+                Mesh2dHandle(mesh),
+                materials.white.clone(),
+                VisibilityBundle::default(),
+            ));
+        }
+    }
+
+    fn add_segment_meshes(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        meshes: Res<Meshes>,
+        query: Query<(Entity, &SegmentCollider), Added<SegmentCollider>>,
+    ) {
+        for (entity, segment) in query.iter() {
+            Self::add_segments(
+                &mut commands,
+                entity,
+                &meshes,
+                &materials,
+                &[segment.a, segment.b],
+            );
+        }
+    }
+
+    fn add_chain_meshes(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        meshes: Res<Meshes>,
+        query: Query<(Entity, &ChainCollider), Added<ChainCollider>>,
+    ) {
+        for (entity, chain) in query.iter() {
+            Self::add_segments(&mut commands, entity, &meshes, &materials, &chain.points);
+        }
+    }
+
+    fn add_segments(
+        commands: &mut Commands,
+        entity: Entity,
+        meshes: &Meshes,
+        materials: &Materials,
+        points: &[Vec2],
+    ) {
+        commands
+            .entity(entity)
+            .insert(VisibilityBundle::default())
+            .with_children(|parent| {
+                for segment in points.windows(2) {
+                    let (a, b) = (segment[0], segment[1]);
+                    let ab = b - a;
+
+                    parent.spawn(ColorMesh2dBundle {
+                        mesh: meshes.quad.clone().into(),
+                        material: materials.blue.clone(),
+                        transform: Transform {
+                            scale: Vec3::new(ab.length(), 2., 1.),
+                            rotation: Quat::from_rotation_z(ab.y.atan2(ab.x)),
+                            translation: ((a + b) / 2.).extend(0.),
+                        },
+                        ..default()
+                    });
+                }
+            });
+    }
+
+    fn add_tile_map_meshes(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        meshes: Res<Meshes>,
+        query: Query<(Entity, &TileMapCollider), Added<TileMapCollider>>,
+    ) {
+        for (entity, tile_map) in query.iter() {
+            commands
+                .entity(entity)
+                .insert(VisibilityBundle::default())
+                .with_children(|parent| {
+                    for rect in tile_map.rects() {
+                        let (center, size) = tile_map.rect_bounds(rect);
+
+                        parent.spawn(ColorMesh2dBundle {
+                            mesh: meshes.quad.clone().into(),
+                            material: materials.blue.clone(),
+                            transform: Transform {
+                                scale: size.extend(1.),
+                                translation: center.extend(0.),
+                                ..default()
+                            },
+                            ..default()
+                        });
+                    }
+                });
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    time::FixedTimestep,
+};
+use xpbd::{colliders::*, components::*, fluids::*, resources::Gravity, XpbdPlugin};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(Example8Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example8Plugin;
+
+impl Plugin for Example8Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example8Plugin::startup)
+            .insert_resource(Gravity(Vec2::new(0., -300.)))
+            .insert_resource(FluidSettings::from_spacing(SPACING))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(1. / 20.))
+                    .with_system(Example8Plugin::pour),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct Materials {
+    water: Handle<ColorMaterial>,
+}
+
+#[derive(Resource)]
+struct Meshes {
+    sphere: Handle<Mesh>,
+}
+
+const SPACING: f32 = 8.;
+const MAX_PARTICLES: usize = 1200;
+
+impl Example8Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let sphere = meshes.add(shape::Circle::new(1.).into());
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+        let water = materials.add(ColorMaterial::from(Color::rgb(0.2, 0.5, 1.)));
+
+        // open box container
+        for (pos, size) in [
+            (Vec2::new(0., -250.), Vec2::new(420., 20.)),
+            (Vec2::new(-200., -150.), Vec2::new(20., 180.)),
+            (Vec2::new(200., -150.), Vec2::new(20., 180.)),
+        ] {
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: quad.clone().into(),
+                    material: white.clone(),
+                    transform: Transform {
+                        scale: size.extend(1.),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StaticBoxBundle {
+                    pos: Pos(pos),
+                    collider: BoxCollider { size },
+                    ..default()
+                });
+        }
+
+        commands.insert_resource(Meshes { sphere });
+        commands.insert_resource(Materials { water });
+    }
+
+    // a stream of water from a spout above the left side of the box, fast enough for the rows not to overlap
+    fn pour(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        meshes: Res<Meshes>,
+        particles: Query<(), With<FluidParticle>>,
+    ) {
+        if particles.iter().count() >= MAX_PARTICLES {
+            return;
+        }
+
+        for i in 0..4 {
+            let pos = Vec2::new(-150., 150. + i as f32 * SPACING);
+            let radius = SPACING / 2.;
+
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: meshes.sphere.clone().into(),
+                    material: materials.water.clone(),
+                    transform: Transform {
+                        scale: Vec3::splat(radius),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(ParticleBundle {
+                    collider: CircleCollider { radius },
+                    restitution: Restitution(0.),
+                    ..ParticleBundle::new_with_pos_and_vel(pos, Vec2::new(160., 0.))
+                })
+                .insert(FluidParticle);
+        }
+    }
+}
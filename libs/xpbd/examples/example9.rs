@@ -0,0 +1,112 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    time::FixedTimestep,
+};
+use rand::random;
+use xpbd::{colliders::*, components::*, resources::Gravity, soft_bodies::*, XpbdPlugin};
+
+fn main() {
+    App::new()
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(XpbdPlugin)
+        .add_plugin(SoftBodyMeshPlugin)
+        .add_plugin(Example9Plugin)
+        .add_startup_system(app_startup)
+        .run();
+}
+
+fn app_startup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub struct Example9Plugin;
+
+impl Plugin for Example9Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Example9Plugin::startup)
+            .insert_resource(Gravity(Vec2::new(0., -300.)))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(1.))
+                    .with_system(Example9Plugin::spawn_soft_bodies),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct Materials {
+    slime: Handle<ColorMaterial>,
+    jelly: Handle<ColorMaterial>,
+}
+
+const MAX_SOFT_BODIES: usize = 12;
+
+impl Example9Plugin {
+    fn startup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+        let white = materials.add(ColorMaterial::from(Color::WHITE));
+
+        for (pos, size) in [
+            (Vec2::new(0., -300.), Vec2::new(800., 20.)),
+            (Vec2::new(-400., 0.), Vec2::new(20., 600.)),
+            (Vec2::new(400., 0.), Vec2::new(20., 600.)),
+            (Vec2::new(-150., -100.), Vec2::new(200., 20.)),
+        ] {
+            commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: quad.clone().into(),
+                    material: white.clone(),
+                    transform: Transform {
+                        scale: size.extend(1.),
+                        translation: pos.extend(0.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StaticBoxBundle {
+                    pos: Pos(pos),
+                    collider: BoxCollider { size },
+                    ..default()
+                });
+        }
+
+        commands.insert_resource(Materials {
+            slime: materials.add(ColorMaterial::from(Color::rgb(0.3, 0.9, 0.3))),
+            jelly: materials.add(ColorMaterial::from(Color::rgb(0.9, 0.3, 0.5))),
+        });
+    }
+
+    // alternates wobbly slimes and stiffer jelly crates
+    fn spawn_soft_bodies(
+        mut commands: Commands,
+        materials: Res<Materials>,
+        soft_bodies: Query<(), With<SoftBody>>,
+    ) {
+        if soft_bodies.iter().count() >= MAX_SOFT_BODIES {
+            return;
+        }
+
+        let pos = Vec2::new((random::<f32>() - 0.5) * 600., 250.);
+        let (body, material) = if random::<bool>() {
+            (
+                SoftBody::spawn_circle(&mut commands, pos, 30., 4., 0.3),
+                materials.slime.clone(),
+            )
+        } else {
+            (
+                SoftBody::spawn_box(&mut commands, pos, Vec2::new(60., 40.), 4., 0.8),
+                materials.jelly.clone(),
+            )
+        };
+
+        commands.entity(body).insert(material);
+    }
+}
@@ -0,0 +1,271 @@
+//! Replays a physics recording without running any game logic.
+//!
+//! Record one by adding `PhysicsRecorderPlugin::new("recording.xpbd")` to an app,
+//! e.g. `XPBD_RECORD=recording.xpbd cargo run --example example4`,
+//! then `cargo run --example replay -- recording.xpbd`.
+//!
+//! Space plays and pauses, left and right step through the recording (ten steps at a time
+//! with shift), home and end jump to its ends and dragging the mouse across the window scrubs.
+
+use std::{env, fs::File, io::BufReader};
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle, utils::HashMap};
+use xpbd::{
+    consts::DELTA_TIME,
+    recording::{RecordedBody, Recording},
+    terrain::BodyShape,
+};
+
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "recording.xpbd".to_string());
+    let recording = File::open(&path)
+        .and_then(|file| Recording::read(&mut BufReader::new(file)))
+        .unwrap_or_else(|error| panic!("can't read the recording {path}: {error}"));
+
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .insert_resource(Replay {
+            recording,
+            step: 0,
+            playing: true,
+            elapsed: 0.,
+        })
+        .init_resource::<BodyVisuals>()
+        .add_startup_system(startup)
+        .add_system(controls)
+        .add_system(show_step.after(controls))
+        .run();
+}
+
+#[derive(Resource)]
+struct Replay {
+    recording: Recording,
+    step: usize,
+    playing: bool,
+    // time since the last step was shown while playing
+    elapsed: f32,
+}
+
+impl Replay {
+    fn last_step(&self) -> usize {
+        self.recording.steps.len().saturating_sub(1)
+    }
+}
+
+#[derive(Component)]
+struct ReplayBody;
+
+#[derive(Resource, Default)]
+struct BodyVisuals {
+    // shown bodies with the shape they are shown with
+    entities: HashMap<u64, (Entity, RecordedBody)>,
+    circle: Handle<Mesh>,
+    quad: Handle<Mesh>,
+    dynamic: Handle<ColorMaterial>,
+    fixed: Handle<ColorMaterial>,
+    line_width: f32,
+}
+
+fn startup(
+    mut commands: Commands,
+    replay: Res<Replay>,
+    mut visuals: ResMut<BodyVisuals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // frames everything the recorded bodies ever cover
+    let (min, max) = (0..replay.recording.steps.len())
+        .flat_map(|step| replay.recording.bodies_at(step))
+        .flat_map(|(body, state)| {
+            let parts = body.parts.iter().map(|(offset, shape)| {
+                let half_extents = shape.half_extents();
+
+                (*offset - half_extents, *offset + half_extents)
+            });
+            let lines = body.lines.iter().map(|(a, b)| (a.min(*b), a.max(*b)));
+
+            parts
+                .chain(lines)
+                .map(|(min, max)| (state.pos + min, state.pos + max))
+                .collect::<Vec<_>>()
+        })
+        .fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), (part_min, part_max)| (min.min(part_min), max.max(part_max)),
+        );
+    let (center, size) = if min.cmple(max).all() {
+        ((min + max) / 2., max - min)
+    } else {
+        (Vec2::ZERO, Vec2::ONE)
+    };
+    let mut camera = Camera2dBundle::default();
+
+    camera.transform.translation = center.extend(camera.transform.translation.z);
+    camera.projection.scale = (size / Vec2::new(1280., 720.)).max_element() * 1.1;
+    commands.spawn(camera);
+
+    visuals.circle = meshes.add(shape::Circle::new(1.).into());
+    visuals.quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+    visuals.dynamic = materials.add(ColorMaterial::from(Color::MIDNIGHT_BLUE));
+    visuals.fixed = materials.add(ColorMaterial::from(Color::GRAY));
+    visuals.line_width = size.max_element() / 200.;
+}
+
+fn controls(
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    mut replay: ResMut<Replay>,
+) {
+    let last_step = replay.last_step();
+    let stride = if keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift) {
+        10
+    } else {
+        1
+    };
+
+    if keys.just_pressed(KeyCode::Space) {
+        replay.playing = !replay.playing;
+    }
+
+    if keys.just_pressed(KeyCode::Left) {
+        replay.playing = false;
+        replay.step = replay.step.saturating_sub(stride);
+    }
+
+    if keys.just_pressed(KeyCode::Right) {
+        replay.playing = false;
+        replay.step = (replay.step + stride).min(last_step);
+    }
+
+    if keys.just_pressed(KeyCode::Home) {
+        replay.step = 0;
+    }
+
+    if keys.just_pressed(KeyCode::End) {
+        replay.step = last_step;
+    }
+
+    if mouse_buttons.pressed(MouseButton::Left) {
+        let window = windows.get_primary();
+
+        if let Some((cursor, window)) =
+            window.and_then(|window| Some((window.cursor_position()?, window)))
+        {
+            let fraction = (cursor.x / window.width()).clamp(0., 1.);
+
+            replay.playing = false;
+            replay.step = (fraction * last_step as f32).round() as usize;
+        }
+    }
+
+    if replay.playing {
+        replay.elapsed += time.delta_seconds();
+
+        while replay.elapsed >= DELTA_TIME && replay.step < last_step {
+            replay.elapsed -= DELTA_TIME;
+            replay.step += 1;
+        }
+
+        if replay.step == last_step {
+            replay.playing = false;
+            replay.elapsed = 0.;
+        }
+    }
+}
+
+fn show_step(
+    mut commands: Commands,
+    replay: Res<Replay>,
+    mut visuals: ResMut<BodyVisuals>,
+    mut bodies: Query<(&mut Transform, &mut Visibility), With<ReplayBody>>,
+    mut windows: ResMut<Windows>,
+) {
+    if !replay.is_changed() {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!(
+            "xpbd replay - step {} / {}",
+            replay.step,
+            replay.last_step()
+        ));
+    }
+
+    for (_, mut visibility) in bodies.iter_mut() {
+        visibility.is_visible = false;
+    }
+
+    for (body, state) in replay.recording.bodies_at(replay.step) {
+        let transform = Transform::from_translation(state.pos.extend(0.));
+
+        match visuals.entities.get(&body.id) {
+            Some((entity, shown)) if shown == body => {
+                if let Ok((mut body_transform, mut visibility)) = bodies.get_mut(*entity) {
+                    *body_transform = transform;
+                    visibility.is_visible = true;
+                }
+            }
+            shown => {
+                // the body changed shape or rigid body since it was shown
+                if let Some((entity, _)) = shown {
+                    commands.entity(*entity).despawn_recursive();
+                }
+
+                let entity = spawn_body(&mut commands, &visuals, body, transform);
+
+                visuals.entities.insert(body.id, (entity, body.clone()));
+            }
+        }
+    }
+}
+
+fn spawn_body(
+    commands: &mut Commands,
+    visuals: &BodyVisuals,
+    body: &RecordedBody,
+    transform: Transform,
+) -> Entity {
+    let material = if body.is_static {
+        visuals.fixed.clone()
+    } else {
+        visuals.dynamic.clone()
+    };
+
+    commands
+        .spawn((SpatialBundle::from_transform(transform), ReplayBody))
+        .with_children(|parent| {
+            for (offset, shape) in body.parts.iter() {
+                let (mesh, scale) = match *shape {
+                    BodyShape::Circle { radius } => (visuals.circle.clone(), Vec2::splat(radius)),
+                    BodyShape::Box { size } => (visuals.quad.clone(), size),
+                };
+
+                parent.spawn(MaterialMesh2dBundle {
+                    mesh: mesh.into(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(offset.extend(0.))
+                        .with_scale(scale.extend(1.)),
+                    ..default()
+                });
+            }
+
+            for (a, b) in body.lines.iter() {
+                let delta = *b - *a;
+
+                parent.spawn(MaterialMesh2dBundle {
+                    mesh: visuals.quad.clone().into(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(((*a + *b) / 2.).extend(0.))
+                        .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x)))
+                        .with_scale(Vec3::new(delta.length(), visuals.line_width, 1.)),
+                    ..default()
+                });
+            }
+        })
+        .id()
+}
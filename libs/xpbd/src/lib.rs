@@ -1,6 +1,22 @@
 mod xpbd;
 
+pub use xpbd::bvh;
+pub use xpbd::character;
 pub use xpbd::colliders;
 pub use xpbd::components;
-pub use xpbd::plugin::XpbdPlugin;
+pub use xpbd::constraints;
+pub use xpbd::consts;
+pub use xpbd::contact;
+pub use xpbd::diagnostics;
+pub use xpbd::explosions;
+pub use xpbd::fluids;
+pub use xpbd::forces;
+pub use xpbd::materials;
+pub use xpbd::mouse_drag;
+pub use xpbd::plugin::{FixedUpdateStage, PhysicsSet, XpbdAppExt, XpbdPlugin};
+pub use xpbd::queries;
+pub use xpbd::recording;
 pub use xpbd::resources;
+pub use xpbd::scene;
+pub use xpbd::soft_bodies;
+pub use xpbd::terrain;
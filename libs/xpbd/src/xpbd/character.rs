@@ -0,0 +1,422 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+
+use super::{
+    components::{CollisionLayers, Pos},
+    queries::{ShapeHit, SpatialQuery},
+    terrain::BodyShape,
+};
+
+// surfaces hit in a single move before the character gives up on the rest of it
+const MAX_SLIDES: usize = 4;
+const MIN_MOVE: f32 = 1e-4;
+
+/// Shape of a character, always kept upright
+#[derive(Reflect, FromReflect, Debug, Clone, Copy)]
+pub enum CharacterShape {
+    Circle {
+        radius: f32,
+    },
+    /// Vertical capsule, `half_height` being half the length of its straight part
+    Capsule {
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+impl Default for CharacterShape {
+    fn default() -> Self {
+        CharacterShape::Capsule {
+            radius: 10.,
+            half_height: 10.,
+        }
+    }
+}
+
+impl CharacterShape {
+    /// Shapes making up the character, with their offset from its position
+    pub fn parts(&self) -> Vec<(Vec2, BodyShape)> {
+        match *self {
+            CharacterShape::Circle { radius } => vec![(Vec2::ZERO, BodyShape::Circle { radius })],
+            CharacterShape::Capsule {
+                radius,
+                half_height,
+            } => {
+                let mut parts = vec![
+                    (Vec2::Y * half_height, BodyShape::Circle { radius }),
+                    (-Vec2::Y * half_height, BodyShape::Circle { radius }),
+                ];
+
+                if half_height > 0. {
+                    parts.push((
+                        Vec2::ZERO,
+                        BodyShape::Box {
+                            size: Vec2::new(radius, half_height) * 2.,
+                        },
+                    ));
+                }
+
+                parts
+            }
+        }
+    }
+}
+
+/// Kinematic body moved by setting `displacement` rather than by forces.
+/// It slides along walls, walks up slopes and small steps, and is stopped by every other collider,
+/// but doesn't push dynamic bodies out of its way, see `CharacterControllerBundle`
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct CharacterController {
+    pub shape: CharacterShape,
+    /// Movement applied during the next physics step, reset to zero afterwards
+    pub displacement: Vec2,
+    /// Steepest slope the character can stand on, in radians
+    pub max_slope: f32,
+    /// Highest ledge the character walks onto without jumping
+    pub step_height: f32,
+    /// Gap kept between the character and the surfaces it touches, so it doesn't get stuck in them
+    pub skin: f32,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            shape: default(),
+            displacement: Vec2::ZERO,
+            max_slope: FRAC_PI_4,
+            step_height: 5.,
+            skin: 0.05,
+        }
+    }
+}
+
+/// Surfaces touched by a character during its last move, with normals pointing from the character towards them
+#[derive(Component, Debug, Default, Clone)]
+pub struct CharacterContacts {
+    pub ground: Option<(Entity, Vec2)>,
+    pub walls: Vec<(Entity, Vec2)>,
+    pub ceiling: Option<(Entity, Vec2)>,
+}
+
+impl CharacterContacts {
+    pub fn is_grounded(&self) -> bool {
+        self.ground.is_some()
+    }
+
+    fn clear(&mut self) {
+        self.ground = None;
+        self.walls.clear();
+        self.ceiling = None;
+    }
+}
+
+/// Characters have no `RigidBody` or `Aabb`, so they are left out of the broad phase.
+/// A moving character is stopped by the bodies in its way, but dynamic bodies moving into it pass through
+#[derive(Bundle, Default)]
+pub struct CharacterControllerBundle {
+    pub controller: CharacterController,
+    pub contacts: CharacterContacts,
+    pub pos: Pos,
+    pub collision_layers: CollisionLayers,
+}
+
+impl CharacterControllerBundle {
+    pub fn new(pos: Vec2, shape: CharacterShape) -> Self {
+        Self {
+            controller: CharacterController { shape, ..default() },
+            pos: Pos(pos),
+            ..default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Surface {
+    Ground,
+    Wall,
+    Ceiling,
+}
+
+struct Mover<'a, 'w, 's> {
+    spatial_query: &'a SpatialQuery<'w, 's>,
+    controller: &'a CharacterController,
+    layers: CollisionLayers,
+}
+
+impl<'a, 'w, 's> Mover<'a, 'w, 's> {
+    fn cast(&self, pos: Vec2, delta: Vec2) -> Option<ShapeHit> {
+        self.controller
+            .shape
+            .parts()
+            .into_iter()
+            .filter_map(|(offset, shape)| {
+                self.spatial_query
+                    .shape_cast(shape, pos + offset, delta, self.layers)
+            })
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    // how far along `delta` the character can go before the hit, keeping its skin away from it
+    fn advance(&self, delta: Vec2, fraction: f32) -> Vec2 {
+        let distance = delta.length();
+
+        delta / distance * (distance * fraction - self.controller.skin).max(0.)
+    }
+
+    fn surface(&self, normal: Vec2) -> Surface {
+        let cos_max_slope = self.controller.max_slope.cos();
+
+        // `normal` points into the surface, so the ground is below
+        if -normal.y >= cos_max_slope {
+            Surface::Ground
+        } else if normal.y >= cos_max_slope {
+            Surface::Ceiling
+        } else {
+            Surface::Wall
+        }
+    }
+
+    // climbs a ledge of at most `step_height` by moving up, then `horizontal`, then down onto it
+    fn step(&self, pos: Vec2, horizontal: Vec2) -> Option<(Vec2, ShapeHit)> {
+        let up = Vec2::Y * self.controller.step_height;
+        let raised = pos
+            + self
+                .cast(pos, up)
+                .map_or(up, |hit| self.advance(up, hit.fraction));
+        let forward = self
+            .cast(raised, horizontal)
+            .map_or(horizontal, |hit| self.advance(horizontal, hit.fraction));
+
+        if forward.length() <= self.controller.skin {
+            return None;
+        }
+
+        let advanced = raised + forward;
+        let down = Vec2::new(0., pos.y - raised.y - self.controller.skin * 2.);
+        let hit = self.cast(advanced, down)?;
+
+        (self.surface(hit.normal) == Surface::Ground)
+            .then(|| (advanced + self.advance(down, hit.fraction), hit))
+    }
+
+    fn move_and_slide(
+        &self,
+        pos: Vec2,
+        was_grounded: bool,
+        contacts: &mut CharacterContacts,
+    ) -> Vec2 {
+        let mut pos = pos;
+        let mut remaining = self.controller.displacement;
+        let mut planes: Vec<Vec2> = Vec::new();
+
+        contacts.clear();
+
+        for _ in 0..MAX_SLIDES {
+            if remaining.length() <= MIN_MOVE {
+                break;
+            }
+
+            let Some(hit) = self.cast(pos, remaining) else {
+                pos += remaining;
+                break;
+            };
+
+            pos += self.advance(remaining, hit.fraction);
+            remaining *= 1. - hit.fraction;
+
+            let surface = self.surface(hit.normal);
+
+            match surface {
+                Surface::Ground => contacts.ground = Some((hit.entity, hit.normal)),
+                Surface::Ceiling => contacts.ceiling = Some((hit.entity, hit.normal)),
+                Surface::Wall => {
+                    let horizontal = Vec2::new(remaining.x, 0.);
+
+                    if (was_grounded || contacts.is_grounded()) && self.controller.step_height > 0.
+                    {
+                        if let Some((stepped, ground)) = self.step(pos, horizontal) {
+                            pos = stepped;
+                            remaining.x = 0.;
+                            contacts.ground = Some((ground.entity, ground.normal));
+                            continue;
+                        }
+                    }
+
+                    contacts.walls.push((hit.entity, hit.normal));
+                }
+            }
+
+            // standing on the ground absorbs the downward part of the move instead of sliding down slopes
+            if surface == Surface::Ground {
+                remaining.y = remaining.y.max(0.);
+            }
+
+            let normal = -hit.normal;
+
+            remaining -= normal * remaining.dot(normal).min(0.);
+
+            // two surfaces meeting at a corner leave no direction to slide in
+            if planes.iter().any(|plane| remaining.dot(*plane) < -MIN_MOVE) {
+                break;
+            }
+
+            planes.push(normal);
+        }
+
+        // keeps walking characters on the ground when going down slopes and steps
+        let probe = if was_grounded && self.controller.displacement.y <= 0. {
+            self.controller.step_height + self.controller.skin * 2.
+        } else {
+            self.controller.skin * 2.
+        };
+        let down = -Vec2::Y * probe;
+
+        if let Some(hit) = self.cast(pos, down) {
+            if self.surface(hit.normal) == Surface::Ground {
+                pos += self.advance(down, hit.fraction);
+                contacts.ground = Some((hit.entity, hit.normal));
+            }
+        }
+
+        pos
+    }
+}
+
+/// Moves a character by its `displacement`, updating its contacts, and returns its new position
+pub(crate) fn move_character(
+    spatial_query: &SpatialQuery,
+    controller: &CharacterController,
+    layers: CollisionLayers,
+    pos: Vec2,
+    contacts: &mut CharacterContacts,
+) -> Vec2 {
+    let was_grounded = contacts.is_grounded();
+    let mover = Mover {
+        spatial_query,
+        controller,
+        layers,
+    };
+
+    mover.move_and_slide(pos, was_grounded, contacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::BoxCollider,
+        components::*,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    fn walk(app: &mut App, character: Entity, displacement: Vec2, steps: u32) {
+        for _ in 0..steps {
+            app.world
+                .get_mut::<CharacterController>(character)
+                .unwrap()
+                .displacement = displacement;
+            run_steps(app, 1);
+        }
+    }
+
+    #[test]
+    fn character_slides_along_ground_into_wall() {
+        let mut app = test_app();
+
+        let ground = app
+            .world
+            .spawn(StaticBoxBundle {
+                pos: Pos(Vec2::new(0., -5.)),
+                collider: BoxCollider {
+                    size: Vec2::new(200., 10.),
+                },
+                ..default()
+            })
+            .id();
+        let wall = app
+            .world
+            .spawn(StaticBoxBundle {
+                pos: Pos(Vec2::new(50., 20.)),
+                collider: BoxCollider {
+                    size: Vec2::new(10., 40.),
+                },
+                ..default()
+            })
+            .id();
+        let character = app
+            .world
+            .spawn(CharacterControllerBundle::new(
+                Vec2::new(0., 20.),
+                CharacterShape::Circle { radius: 5. },
+            ))
+            .id();
+
+        walk(&mut app, character, Vec2::new(0., -2.), 5);
+
+        assert!(!app
+            .world
+            .get::<CharacterContacts>(character)
+            .unwrap()
+            .is_grounded());
+
+        walk(&mut app, character, Vec2::new(3., -2.), 60);
+
+        let pos = app.world.get::<Pos>(character).unwrap().0;
+        let contacts = app.world.get::<CharacterContacts>(character).unwrap();
+
+        assert!(pos.abs_diff_eq(Vec2::new(40., 5.), 0.2), "{pos:?}");
+        assert_eq!(contacts.ground.map(|(entity, _)| entity), Some(ground));
+        assert!(contacts
+            .walls
+            .iter()
+            .any(|(entity, normal)| *entity == wall && normal.abs_diff_eq(Vec2::X, 0.01)));
+    }
+
+    #[test]
+    fn character_steps_onto_ledge_but_not_over_wall() {
+        let mut app = test_app();
+
+        for (pos, size) in [
+            (Vec2::new(0., -5.), Vec2::new(300., 10.)),
+            // ledge lower than the step height
+            (Vec2::new(40., 1.5), Vec2::new(40., 3.)),
+            (Vec2::new(85., 15.), Vec2::new(10., 30.)),
+        ] {
+            app.world.spawn(StaticBoxBundle {
+                pos: Pos(pos),
+                collider: BoxCollider { size },
+                ..default()
+            });
+        }
+
+        let character = app
+            .world
+            .spawn(CharacterControllerBundle::new(
+                Vec2::new(0., 8.1),
+                CharacterShape::Capsule {
+                    radius: 4.,
+                    half_height: 4.,
+                },
+            ))
+            .id();
+
+        walk(&mut app, character, Vec2::new(2., -1.), 20);
+
+        // on top of the ledge
+        let pos = app.world.get::<Pos>(character).unwrap().0;
+
+        assert!((pos.y - 11.).abs() < 0.2, "{pos:?}");
+
+        walk(&mut app, character, Vec2::new(2., -1.), 40);
+
+        let pos = app.world.get::<Pos>(character).unwrap().0;
+        let contacts = app.world.get::<CharacterContacts>(character).unwrap();
+
+        assert!(pos.abs_diff_eq(Vec2::new(76., 8.), 0.2), "{pos:?}");
+        assert!(contacts.is_grounded());
+        assert!(!contacts.walls.is_empty());
+    }
+}
@@ -1,23 +1,190 @@
 use bevy::prelude::*;
+pub use xpbd_core::{colliders::*, compound::*};
 
-#[derive(Component, Debug)]
-pub struct CircleCollider {
-    pub radius: f32,
+use super::terrain::BodyShape;
+
+/// Part of the `CompoundCollider` of its parent body, at `offset` from the parent position.
+/// Needs a `CircleCollider` or `BoxCollider` next to it, and no rigid body components of its own.
+/// The `Transform` of the child doesn't move the part, only `offset` does
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct ChildCollider {
+    pub offset: Vec2,
 }
 
-impl Default for CircleCollider {
-    fn default() -> Self {
-        Self { radius: 20. }
+/// Calls `f` with the shapes of a body and their offsets from its position,
+/// unless the body has neither a compound, a circle nor a box collider
+pub(crate) fn with_body_parts<R>(
+    circle: Option<&CircleCollider>,
+    box_: Option<&BoxCollider>,
+    compound: Option<&CompoundCollider>,
+    f: impl FnOnce(&[(Vec2, BodyShape)]) -> R,
+) -> Option<R> {
+    if let Some(compound) = compound {
+        return Some(f(&compound.parts));
     }
-}
 
-#[derive(Component, Debug)]
-pub struct BoxCollider {
-    pub size: Vec2,
+    let shape = match (circle, box_) {
+        (Some(circle), _) => BodyShape::Circle {
+            radius: circle.radius,
+        },
+        (_, Some(box_)) => BodyShape::Box { size: box_.size },
+        _ => return None,
+    };
+
+    Some(f(&[(Vec2::ZERO, shape)]))
 }
 
-impl Default for BoxCollider {
-    fn default() -> Self {
-        Self { size: Vec2::ONE }
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        components::*,
+        terrain::{ChainCollider, TileMapCollider},
+        testing::{run_steps, spawn_ball, spawn_box, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn l_shaped_crate_rests_on_its_child_part() {
+        let mut app = test_app();
+
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -10.)),
+            collider: BoxCollider {
+                size: Vec2::new(100., 10.),
+            },
+            ..default()
+        });
+        let body = app
+            .world
+            .spawn(DynamicBoxBundle {
+                collider: BoxCollider { size: Vec2::ONE },
+                ..DynamicBoxBundle::new_with_pos_and_vel(Vec2::ZERO, Vec2::ZERO)
+            })
+            .insert(Density(1.))
+            .with_children(|parent| {
+                parent.spawn((
+                    ChildCollider {
+                        offset: Vec2::new(0.5, -1.),
+                    },
+                    BoxCollider {
+                        size: Vec2::new(2., 1.),
+                    },
+                ));
+            })
+            .id();
+
+        run_steps(&mut app, 120);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+        let aabb = app.world.get::<Aabb>(body).unwrap();
+
+        assert_eq!(app.world.get::<Mass>(body).unwrap().0, 3.);
+        assert!((pos.y - -3.5).abs() < 0.1, "{pos:?}");
+        assert!((aabb.max.x - aabb.min.x - 2.).abs() < 0.1, "{aabb:?}");
+    }
+
+    #[test]
+    fn compound_follows_the_child_parts_of_bodies() {
+        let mut app = test_app();
+
+        let part = (
+            ChildCollider {
+                offset: Vec2::new(0., -1.),
+            },
+            BoxCollider { size: Vec2::ONE },
+        );
+        // a plain group of colliders, not a body
+        let group = app
+            .world
+            .spawn(TransformBundle::default())
+            .with_children(|parent| {
+                parent.spawn(part);
+            })
+            .id();
+        let mut child = None;
+        let body = app
+            .world
+            .spawn(DynamicBoxBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::ZERO,
+            ))
+            .with_children(|parent| {
+                child = Some(parent.spawn(part).id());
+            })
+            .id();
+        let child = child.unwrap();
+
+        app.update();
+
+        let parts = |app: &App| {
+            app.world
+                .get::<CompoundCollider>(body)
+                .map(|compound| compound.parts.clone())
+        };
+
+        assert!(app.world.get::<CompoundCollider>(group).is_none());
+        assert_eq!(parts(&app).map(|parts| parts.len()), Some(2));
+
+        app.world.get_mut::<BoxCollider>(child).unwrap().size = Vec2::new(3., 1.);
+        app.update();
+
+        assert_eq!(
+            parts(&app).unwrap()[1],
+            (
+                Vec2::new(0., -1.),
+                BodyShape::Box {
+                    size: Vec2::new(3., 1.)
+                }
+            )
+        );
+
+        app.world.entity_mut(child).despawn_recursive();
+        app.update();
+
+        assert_eq!(parts(&app), None);
+    }
+
+    #[test]
+    fn box_rests_on_tile_map() {
+        let mut app = test_app();
+
+        app.world.spawn(StaticTerrainBundle::new(
+            Vec2::new(-50., -10.),
+            TileMapCollider::new(Vec2::splat(10.), 10, &[true; 10]),
+        ));
+        let body = spawn_box(&mut app, Vec2::new(-1., 5.), Vec2::ZERO, Vec2::splat(4.)).id();
+
+        run_steps(&mut app, 200);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+
+        assert!(pos.abs_diff_eq(Vec2::new(-1., 2.), 0.1), "{pos:?}");
+    }
+
+    #[test]
+    fn ball_jumps_through_one_sided_chain() {
+        let mut app = test_app();
+
+        app.world.spawn(StaticTerrainBundle::new(
+            Vec2::ZERO,
+            ChainCollider {
+                points: vec![Vec2::new(-50., 0.), Vec2::new(0., 0.), Vec2::new(50., 0.)],
+                one_sided: true,
+            },
+        ));
+        let ball = spawn_ball(&mut app, Vec2::new(0., -3.), Vec2::new(0., 15.), 2.).id();
+
+        run_steps(&mut app, 30);
+
+        assert!(app.world.get::<Pos>(ball).unwrap().0.y > 2.5);
+
+        run_steps(&mut app, 270);
+
+        // passed through from below and landed on top
+        let pos = app.world.get::<Pos>(ball).unwrap().0;
+
+        assert!((pos.y - 2.).abs() < 0.1, "{pos:?}");
     }
 }
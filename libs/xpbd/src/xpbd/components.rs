@@ -1,23 +1,32 @@
 use bevy::prelude::*;
+pub use xpbd_core::body::{
+    Aabb, CollisionLayers, CombineRule, ContactSoftness, LockedAxes, MaxLinearSpeed, RigidBody,
+};
 
 use super::{
     colliders::{BoxCollider, CircleCollider},
     consts::SUB_DT,
+    terrain::TerrainCollider,
 };
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
 pub struct Pos(pub Vec2);
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
 pub struct PrevPos(pub Vec2);
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
 pub struct Vel(pub Vec2);
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
 pub struct PreSolveVel(pub Vec2);
 
-#[derive(Component, Debug)]
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
 pub struct Mass(pub f32);
 
 impl Default for Mass {
@@ -26,7 +35,53 @@ impl Default for Mass {
     }
 }
 
-#[derive(Component, Debug)]
+/// Inverse of `Mass`, zero for bodies that are not moved by the solver
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct InvMass(pub f32);
+
+impl Default for InvMass {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Moment of inertia around the body center
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Inertia(pub f32);
+
+impl Default for Inertia {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Mass per unit of collider area. Bodies with density get their `Mass` and `Inertia` computed from the collider shape
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Density(pub f32);
+
+impl Default for Density {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Keeps the `Mass` of a body as it was set, instead of computing it from the density of its `PhysicsMaterial`
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct ExplicitMass;
+
+/// Translation the physics last wrote to the `Transform` of a body, part of the body bundles.
+/// Transforms edited to anything else teleport the body. Bodies spawned without it are only
+/// teleported by transforms away from their position
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SyncedTranslation(pub Vec3);
+
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
 pub struct Restitution(pub f32);
 
 impl Default for Restitution {
@@ -35,33 +90,84 @@ impl Default for Restitution {
     }
 }
 
-#[derive(Component, Debug, Default)]
-pub struct Aabb {
-    // bottom-left corner
-    pub min: Vec2,
-    // top-right corner
-    pub max: Vec2,
+/// Frictionless by default, as bodies were before friction was solved
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Friction(pub f32);
+
+/// Overrides the default `CombineRule::Average` for `Restitution`
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct RestitutionCombine(pub CombineRule);
+
+/// Overrides the default `CombineRule::Average` for `Friction`
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct FrictionCombine(pub CombineRule);
+
+/// Entities touched during the last physics step, with the contact normal pointing from this body towards them.
+/// Only maintained for bodies that have it, e.g. a body is grounded when one of the normals points down
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component)]
+pub struct CollidingEntities(pub Vec<(Entity, Vec2)>);
+
+impl CollidingEntities {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.iter().any(|(other, _)| *other == entity)
+    }
+
+    pub fn normal(&self, entity: Entity) -> Option<Vec2> {
+        self.0
+            .iter()
+            .find(|(other, _)| *other == entity)
+            .map(|(_, normal)| *normal)
+    }
+
+    // a body touching the same entity in several substeps keeps the latest normal
+    pub(crate) fn insert(&mut self, entity: Entity, normal: Vec2) {
+        match self.0.iter_mut().find(|(other, _)| *other == entity) {
+            Some((_, old_normal)) => *old_normal = normal,
+            None => self.0.push((entity, normal)),
+        }
+    }
+}
+
+/// Lets bodies through from every side but one, like a platform jumped onto from below.
+/// Contacts are only kept when they push the other body out along `normal`,
+/// and it wasn't already passing through at the start of the substep
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct OneWay {
+    pub normal: Vec2,
 }
 
-impl Aabb {
-    pub fn intersects(&self, other: &Self) -> bool {
-        self.max.x >= other.min.x
-            && self.max.y >= other.min.y
-            && self.min.x <= other.max.x
-            && self.min.y <= other.max.y
+impl Default for OneWay {
+    fn default() -> Self {
+        Self { normal: Vec2::Y }
     }
 }
 
+/// Velocity of the body surface that friction carries touching bodies along with,
+/// without the body itself moving, like a conveyor belt
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SurfaceVelocity(pub Vec2);
+
 #[derive(Bundle, Default)]
 pub struct ParticleBundle {
+    pub rigid_body: RigidBody,
     pub pos: Pos,
     pub prev_pos: PrevPos,
     pub vel: Vel,
     pub pre_solve_vel: PreSolveVel,
     pub mass: Mass,
+    pub inv_mass: InvMass,
+    pub inertia: Inertia,
     pub restitution: Restitution,
+    pub friction: Friction,
     pub collider: CircleCollider,
     pub aabb: Aabb,
+    pub synced_translation: SyncedTranslation,
 }
 
 impl ParticleBundle {
@@ -77,14 +183,19 @@ impl ParticleBundle {
 
 #[derive(Bundle, Default)]
 pub struct DynamicBoxBundle {
+    pub rigid_body: RigidBody,
     pub pos: Pos,
     pub prev_pos: PrevPos,
     pub vel: Vel,
     pub pre_solve_vel: PreSolveVel,
     pub mass: Mass,
+    pub inv_mass: InvMass,
+    pub inertia: Inertia,
     pub restitution: Restitution,
+    pub friction: Friction,
     pub collider: BoxCollider,
     pub aabb: Aabb,
+    pub synced_translation: SyncedTranslation,
 }
 
 impl DynamicBoxBundle {
@@ -98,16 +209,405 @@ impl DynamicBoxBundle {
     }
 }
 
-#[derive(Bundle, Default)]
+#[derive(Bundle)]
 pub struct StaticCircleBundle {
+    pub rigid_body: RigidBody,
     pub pos: Pos,
-    pub collider: CircleCollider,
+    pub prev_pos: PrevPos,
+    pub vel: Vel,
+    pub pre_solve_vel: PreSolveVel,
+    pub mass: Mass,
+    pub inv_mass: InvMass,
     pub restitution: Restitution,
+    pub friction: Friction,
+    pub collider: CircleCollider,
+    pub aabb: Aabb,
+    pub synced_translation: SyncedTranslation,
 }
 
-#[derive(Bundle, Default)]
+impl Default for StaticCircleBundle {
+    fn default() -> Self {
+        Self {
+            rigid_body: RigidBody::Static,
+            pos: default(),
+            prev_pos: default(),
+            vel: default(),
+            pre_solve_vel: default(),
+            mass: default(),
+            inv_mass: InvMass(0.),
+            restitution: default(),
+            friction: default(),
+            collider: default(),
+            aabb: default(),
+            synced_translation: default(),
+        }
+    }
+}
+
+#[derive(Bundle)]
 pub struct StaticBoxBundle {
+    pub rigid_body: RigidBody,
     pub pos: Pos,
+    pub prev_pos: PrevPos,
+    pub vel: Vel,
+    pub pre_solve_vel: PreSolveVel,
+    pub mass: Mass,
+    pub inv_mass: InvMass,
+    pub restitution: Restitution,
+    pub friction: Friction,
     pub collider: BoxCollider,
+    pub aabb: Aabb,
+    pub synced_translation: SyncedTranslation,
+}
+
+impl Default for StaticBoxBundle {
+    fn default() -> Self {
+        Self {
+            rigid_body: RigidBody::Static,
+            pos: default(),
+            prev_pos: default(),
+            vel: default(),
+            pre_solve_vel: default(),
+            mass: default(),
+            inv_mass: InvMass(0.),
+            restitution: default(),
+            friction: default(),
+            collider: default(),
+            aabb: default(),
+            synced_translation: default(),
+        }
+    }
+}
+
+/// Static body with a `TerrainCollider`, e.g. `ChainCollider` or `TileMapCollider`
+#[derive(Bundle)]
+pub struct StaticTerrainBundle<C: TerrainCollider + Component> {
+    pub rigid_body: RigidBody,
+    pub pos: Pos,
+    pub prev_pos: PrevPos,
+    pub vel: Vel,
+    pub pre_solve_vel: PreSolveVel,
+    pub mass: Mass,
+    pub inv_mass: InvMass,
     pub restitution: Restitution,
+    pub friction: Friction,
+    pub collider: C,
+    pub aabb: Aabb,
+    pub synced_translation: SyncedTranslation,
+}
+
+impl<C: TerrainCollider + Component> StaticTerrainBundle<C> {
+    pub fn new(pos: Vec2, collider: C) -> Self {
+        Self {
+            rigid_body: RigidBody::Static,
+            pos: Pos(pos),
+            prev_pos: PrevPos(pos),
+            vel: default(),
+            pre_solve_vel: default(),
+            mass: default(),
+            inv_mass: InvMass(0.),
+            restitution: default(),
+            friction: default(),
+            collider,
+            aabb: default(),
+            synced_translation: default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        resources::Gravity,
+        terrain::SegmentCollider,
+        testing::{run_steps, spawn_ball, spawn_box, spawn_ground, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn locked_door_is_pushed_only_sideways() {
+        let mut app = test_app();
+
+        app.world.resource_mut::<Gravity>().0 = Vec2::ZERO;
+
+        let door = spawn_box(&mut app, Vec2::ZERO, Vec2::ZERO, Vec2::new(1., 4.))
+            .insert(LockedAxes::default().lock_translation_y())
+            .id();
+        // hits the door off-center and at an angle
+        spawn_ball(&mut app, Vec2::new(-3., -2.), Vec2::new(20., 10.), 0.5);
+
+        run_steps(&mut app, 30);
+
+        let pos = app.world.get::<Pos>(door).unwrap().0;
+        let vel = app.world.get::<Vel>(door).unwrap().0;
+
+        assert!(pos.x > 0.1, "{pos:?}");
+        assert_eq!(pos.y, 0.);
+        assert_eq!(vel.y, 0.);
+    }
+
+    #[test]
+    fn max_linear_speed_caps_falling_body() {
+        let mut app = test_app();
+
+        let body = app
+            .world
+            .spawn(ParticleBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::new(3., 0.),
+            ))
+            .insert(MaxLinearSpeed(2.))
+            .id();
+
+        run_steps(&mut app, 60);
+
+        let vel = app.world.get::<Vel>(body).unwrap().0;
+
+        assert!((vel.length() - 2.).abs() < 1e-4, "{vel:?}");
+        assert!(vel.y < -1.9, "{vel:?}");
+    }
+
+    #[test]
+    fn limited_bodies_stay_limited_through_contacts() {
+        let mut app = test_app();
+
+        app.world.spawn(StaticTerrainBundle::new(
+            Vec2::ZERO,
+            SegmentCollider {
+                a: Vec2::new(-10., -10.),
+                b: Vec2::new(10., 10.),
+                one_sided: false,
+            },
+        ));
+        // on a vertical rail, bouncing off a slope that pushes it sideways
+        let railed = spawn_ball(&mut app, Vec2::new(0., 3.), Vec2::ZERO, 0.5)
+            .insert((Restitution(0.5), LockedAxes::default().lock_translation_x()))
+            .id();
+        // hit much faster than its limit by a heavy ball, far above the slope
+        let capped = spawn_ball(&mut app, Vec2::new(-100., 100.), Vec2::ZERO, 0.5)
+            .insert((Restitution(1.), MaxLinearSpeed(2.)))
+            .id();
+        spawn_ball(&mut app, Vec2::new(-102., 100.), Vec2::new(20., 0.), 0.5)
+            .insert((Restitution(1.), Mass(10.)));
+
+        for _ in 0..120 {
+            run_steps(&mut app, 1);
+
+            let vel = app.world.get::<Vel>(railed).unwrap().0;
+            let pos = app.world.get::<Pos>(railed).unwrap().0;
+
+            assert_eq!(vel.x, 0., "{vel:?}");
+            assert_eq!(pos.x, 0., "{pos:?}");
+
+            let vel = app.world.get::<Vel>(capped).unwrap().0;
+
+            assert!(vel.length() < 2. + 1e-4, "{vel:?}");
+        }
+
+        // pushed out of the slope along its rail, so it rests without sinking in
+        let pos = app.world.get::<Pos>(railed).unwrap().0;
+
+        assert!((pos.y - 0.5 * 2f32.sqrt()).abs() < 1e-6, "{pos:?}");
+    }
+
+    #[test]
+    fn soft_ground_gives_way_and_pushes_out_gently() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.)).insert(ContactSoftness {
+            compliance: 1e-3,
+            damping: 100.,
+        });
+        let resting = spawn_box(&mut app, Vec2::new(-10., -4.5), Vec2::ZERO, Vec2::ONE)
+            .insert(Density(1.))
+            .id();
+        // spawned half inside the ground, which would launch it off rigid ground
+        let buried = spawn_box(&mut app, Vec2::new(10., -5.), Vec2::ZERO, Vec2::ONE)
+            .insert(Density(1.))
+            .id();
+        let mut max_speed = 0f32;
+
+        for _ in 0..240 {
+            run_steps(&mut app, 1);
+            max_speed = max_speed.max(app.world.get::<Vel>(buried).unwrap().0.length());
+        }
+
+        let gravity = app.world.resource::<Gravity>().0.length();
+        let sunk = -4.5 - app.world.get::<Pos>(resting).unwrap().0.y;
+        let buried_y = app.world.get::<Pos>(buried).unwrap().0.y;
+
+        // the ground holds the box up like a spring, m * g * compliance deep
+        assert!((sunk - gravity * 1e-3).abs() < 2e-3, "{sunk}");
+        assert!((buried_y - -4.5 + sunk).abs() < 1e-2, "{buried_y}");
+        assert!(max_speed < 5., "{max_speed}");
+    }
+
+    fn bounce_vel(restitution_combine: CombineRule) -> f32 {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.)).insert(Restitution(0.));
+        let ball = spawn_ball(&mut app, Vec2::ZERO, Vec2::new(0., -20.), 1.)
+            .insert((Restitution(1.), RestitutionCombine(restitution_combine)))
+            .id();
+
+        let mut max_vel = 0f32;
+
+        for _ in 0..60 {
+            run_steps(&mut app, 1);
+            max_vel = max_vel.max(app.world.get::<Vel>(ball).unwrap().0.y);
+        }
+
+        max_vel
+    }
+
+    #[test]
+    fn restitution_combine_rules() {
+        assert!(bounce_vel(CombineRule::Max) > 15.);
+        assert!(bounce_vel(CombineRule::Min) < 1.);
+    }
+
+    #[test]
+    fn bodies_without_surface_coefficients_bounce_like_the_bundle_defaults() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.));
+        // spawned by hand, without `Restitution` or `Friction`
+        let body = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                Pos(Vec2::new(-10., 0.)),
+                PrevPos(Vec2::new(-10., 0.)),
+                Vel::default(),
+                PreSolveVel::default(),
+                Mass(1.),
+                InvMass(1.),
+                CircleCollider { radius: 0.5 },
+                Aabb::default(),
+            ))
+            .id();
+        let bundled = spawn_ball(&mut app, Vec2::new(10., 0.), Vec2::ZERO, 0.5).id();
+
+        // bouncing back up off the ground
+        run_steps(&mut app, 70);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+        let bundled_pos = app.world.get::<Pos>(bundled).unwrap().0;
+
+        assert!(
+            (pos.y - bundled_pos.y).abs() < 1e-5,
+            "{pos:?} {bundled_pos:?}"
+        );
+
+        run_steps(&mut app, 120);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+
+        assert!((pos.y - -4.5).abs() < 0.1, "{pos:?}");
+    }
+
+    #[test]
+    fn colliding_entities_lists_touched_bodies() {
+        let mut app = test_app();
+
+        let ground = spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.))
+            .insert(CollidingEntities::default())
+            .id();
+        let ball = spawn_ball(&mut app, Vec2::new(0., 5.), Vec2::ZERO, 1.)
+            .insert((Restitution(0.), CollidingEntities::default()))
+            .id();
+
+        run_steps(&mut app, 10);
+
+        assert!(app
+            .world
+            .get::<CollidingEntities>(ball)
+            .unwrap()
+            .0
+            .is_empty());
+
+        run_steps(&mut app, 110);
+
+        // grounded
+        let normal = app
+            .world
+            .get::<CollidingEntities>(ball)
+            .unwrap()
+            .normal(ground);
+
+        assert_eq!(normal, Some(-Vec2::Y));
+        assert_eq!(
+            app.world
+                .get::<CollidingEntities>(ground)
+                .unwrap()
+                .normal(ball),
+            Some(Vec2::Y)
+        );
+
+        app.world.get_mut::<Vel>(ball).unwrap().0 = Vec2::new(0., 50.);
+        run_steps(&mut app, 2);
+
+        assert!(!app
+            .world
+            .get::<CollidingEntities>(ball)
+            .unwrap()
+            .contains(ground));
+    }
+
+    #[test]
+    fn box_across_two_grounds_touches_both() {
+        let mut app = test_app();
+
+        let grounds: Vec<_> = [-10., 10.]
+            .into_iter()
+            .map(|x| spawn_ground(&mut app, Vec2::new(x, -10.), Vec2::new(20., 10.)).id())
+            .collect();
+        let body = spawn_box(&mut app, Vec2::new(0., -4.), Vec2::ZERO, Vec2::new(4., 2.))
+            .insert(CollidingEntities::default())
+            .id();
+
+        run_steps(&mut app, 60);
+
+        // both grounds push the box up by the same amount, the second finds it already resolved
+        let colliding = app.world.get::<CollidingEntities>(body).unwrap();
+
+        assert!(grounds.iter().all(|ground| colliding.contains(*ground)));
+    }
+
+    #[test]
+    fn ball_jumps_through_one_way_platform() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -1.), Vec2::new(100., 2.)).insert(OneWay::default());
+        let ball = spawn_ball(&mut app, Vec2::new(0., -5.), Vec2::new(0., 15.), 2.).id();
+
+        run_steps(&mut app, 40);
+
+        assert!(app.world.get::<Pos>(ball).unwrap().0.y > 2.5);
+
+        run_steps(&mut app, 260);
+
+        // passed through from below and landed on top
+        let pos = app.world.get::<Pos>(ball).unwrap().0;
+
+        assert!((pos.y - 2.).abs() < 0.1, "{pos:?}");
+    }
+
+    #[test]
+    fn surface_velocity_carries_resting_box() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -5.), Vec2::new(200., 10.))
+            .insert((Friction(0.5), SurfaceVelocity(Vec2::new(5., 0.))));
+        let body = spawn_box(&mut app, Vec2::new(0., 2.), Vec2::ZERO, Vec2::splat(4.))
+            .insert(Friction(0.5))
+            .id();
+
+        run_steps(&mut app, 300);
+
+        let vel = app.world.get::<Vel>(body).unwrap().0;
+
+        assert!(vel.abs_diff_eq(Vec2::new(5., 0.), 0.1), "{vel:?}");
+    }
 }
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+/// Keeps two bodies at `rest_length` from each other.
+/// `compliance` is the inverse stiffness, zero makes the constraint rigid
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DistanceConstraint {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub rest_length: f32,
+    pub compliance: f32,
+}
+
+impl DistanceConstraint {
+    pub fn new(entity_a: Entity, entity_b: Entity, rest_length: f32) -> Self {
+        Self {
+            entity_a,
+            entity_b,
+            rest_length,
+            compliance: 0.,
+        }
+    }
+
+    pub fn with_compliance(self, compliance: f32) -> Self {
+        Self { compliance, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::CircleCollider,
+        components::*,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn distance_constraint_keeps_bodies_apart() {
+        let mut app = test_app();
+
+        let anchor = app
+            .world
+            .spawn(StaticCircleBundle {
+                pos: Pos(Vec2::new(0., 10.)),
+                collider: CircleCollider { radius: 1. },
+                ..default()
+            })
+            .id();
+        let ball = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: 1. },
+                ..ParticleBundle::new_with_pos_and_vel(Vec2::new(5., 10.), Vec2::ZERO)
+            })
+            .id();
+        app.world.spawn(DistanceConstraint::new(anchor, ball, 5.));
+
+        run_steps(&mut app, 120);
+
+        let pos = app.world.get::<Pos>(ball).unwrap().0;
+
+        assert!(
+            (pos.distance(Vec2::new(0., 10.)) - 5.).abs() < 0.1,
+            "{pos:?}"
+        );
+        assert_eq!(app.world.get::<Pos>(anchor).unwrap().0, Vec2::new(0., 10.));
+    }
+}
@@ -0,0 +1,309 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    utils::{Duration, Instant},
+};
+
+use super::{
+    components::{InvMass, Mass, Pos, RigidBody, Vel},
+    plugin::{FixedUpdateStage, PhysicsSet, Step},
+    resources::{CollisionPairs, Contacts},
+    xpdb_loop::last_substep,
+};
+
+/// Adds xpbd entries to `Diagnostics`, measured every frame in which the physics stepped.
+/// Penetrations are what is left of the contacts of the last substep once solved,
+/// timings add up all the steps of the frame. Added after the `XpbdPlugin`,
+/// and needs the `DiagnosticsPlugin`, which is part of the `DefaultPlugins`
+pub struct XpbdDiagnosticsPlugin;
+
+impl Plugin for XpbdDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsTimings>()
+            .init_resource::<Penetrations>()
+            .add_startup_system(XpbdDiagnosticsPlugin::setup_system)
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .with_system(
+                        XpbdDiagnosticsPlugin::start_timer::<BROAD_PHASE>.before(Step::UpdateAabbs),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::stop_timer::<BROAD_PHASE>
+                            .after(Step::BroadPhase)
+                            .after(Step::UpdateAabbs)
+                            .before(Step::Integrate),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::start_timer::<NARROW_PHASE>
+                            .after(Step::Integrate)
+                            .before(Step::NarrowPhase),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::stop_timer::<NARROW_PHASE>
+                            .after(Step::NarrowPhase)
+                            .before(Step::ModifyContacts),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::start_timer::<SOLVER>
+                            .after(Step::ModifyContacts)
+                            .after(PhysicsSet::ModifyContacts)
+                            .before(Step::SolvePositions),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::stop_timer::<SOLVER>.after(Step::SolveVelocities),
+                    ),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .with_run_criteria(last_substep)
+                    .with_system(
+                        XpbdDiagnosticsPlugin::record_penetrations
+                            .after(Step::ModifyContacts)
+                            .after(PhysicsSet::ModifyContacts)
+                            .before(Step::SolvePositions),
+                    )
+                    .with_system(
+                        XpbdDiagnosticsPlugin::measure_penetrations.after(Step::SolvePositions),
+                    ),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                XpbdDiagnosticsPlugin::diagnostic_system,
+            );
+    }
+}
+
+const BROAD_PHASE: usize = 0;
+const NARROW_PHASE: usize = 1;
+const SOLVER: usize = 2;
+
+/// Time spent in every phase since the last measurement, and when the running ones started
+#[derive(Resource, Default, Debug)]
+struct PhysicsTimings {
+    spent: [Duration; 3],
+    started: [Option<Instant>; 3],
+    stepped: bool,
+}
+
+/// Contacts of the last substep as they were before being solved, then what is left of them
+#[derive(Resource, Default, Debug)]
+struct Penetrations {
+    // bodies, normal, penetration and positions of the bodies before solving
+    contacts: Vec<(Entity, Entity, Vec2, f32, Vec2, Vec2)>,
+    max: f32,
+    avg: f32,
+}
+
+impl XpbdDiagnosticsPlugin {
+    pub const BODY_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(82446123663305000227902144981808690388);
+    pub const COLLISION_PAIR_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(264591155946555274213831998215516446424);
+    pub const CONTACT_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(37933212062639758130820971446592907060);
+    pub const MAX_PENETRATION: DiagnosticId =
+        DiagnosticId::from_u128(88577118036956185101339632083015501724);
+    pub const AVG_PENETRATION: DiagnosticId =
+        DiagnosticId::from_u128(105780004426539900249506866633244030856);
+    pub const KINETIC_ENERGY: DiagnosticId =
+        DiagnosticId::from_u128(143175509632318648485641260886382824275);
+    pub const MOMENTUM: DiagnosticId =
+        DiagnosticId::from_u128(316900936120867720754078256910879942836);
+    pub const BROAD_PHASE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(233876205404217400110344997246592682981);
+    pub const NARROW_PHASE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(317575061932505093885421211142775244859);
+    pub const SOLVER_TIME: DiagnosticId =
+        DiagnosticId::from_u128(185145187580751571330684798338031838322);
+
+    fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        diagnostics.add(Diagnostic::new(Self::BODY_COUNT, "xpbd_body_count", 20));
+        diagnostics.add(Diagnostic::new(
+            Self::COLLISION_PAIR_COUNT,
+            "xpbd_collision_pair_count",
+            20,
+        ));
+        diagnostics.add(Diagnostic::new(
+            Self::CONTACT_COUNT,
+            "xpbd_contact_count",
+            20,
+        ));
+        diagnostics.add(Diagnostic::new(
+            Self::MAX_PENETRATION,
+            "xpbd_max_penetration",
+            20,
+        ));
+        diagnostics.add(Diagnostic::new(
+            Self::AVG_PENETRATION,
+            "xpbd_avg_penetration",
+            20,
+        ));
+        diagnostics.add(Diagnostic::new(
+            Self::KINETIC_ENERGY,
+            "xpbd_kinetic_energy",
+            20,
+        ));
+        diagnostics.add(Diagnostic::new(Self::MOMENTUM, "xpbd_momentum", 20));
+        diagnostics.add(
+            Diagnostic::new(Self::BROAD_PHASE_TIME, "xpbd_broad_phase_time", 20).with_suffix("ms"),
+        );
+        diagnostics.add(
+            Diagnostic::new(Self::NARROW_PHASE_TIME, "xpbd_narrow_phase_time", 20)
+                .with_suffix("ms"),
+        );
+        diagnostics
+            .add(Diagnostic::new(Self::SOLVER_TIME, "xpbd_solver_time", 20).with_suffix("ms"));
+    }
+
+    fn start_timer<const PHASE: usize>(mut timings: ResMut<PhysicsTimings>) {
+        timings.started[PHASE] = Some(Instant::now());
+    }
+
+    fn stop_timer<const PHASE: usize>(mut timings: ResMut<PhysicsTimings>) {
+        if let Some(started) = timings.started[PHASE].take() {
+            timings.spent[PHASE] += started.elapsed();
+        }
+
+        timings.stepped = true;
+    }
+
+    fn record_penetrations(
+        bodies: Query<&Pos>,
+        contacts: Res<Contacts>,
+        mut penetrations: ResMut<Penetrations>,
+    ) {
+        penetrations.contacts.clear();
+
+        for (entity_a, entity_b, contact) in contacts.0.iter() {
+            if let Ok([pos_a, pos_b]) = bodies.get_many([*entity_a, *entity_b]) {
+                penetrations.contacts.push((
+                    *entity_a,
+                    *entity_b,
+                    contact.normal,
+                    contact.penetration,
+                    pos_a.0,
+                    pos_b.0,
+                ));
+            }
+        }
+    }
+
+    // what the bodies moved by along the normal of a contact is what got solved of it
+    fn measure_penetrations(bodies: Query<&Pos>, mut penetrations: ResMut<Penetrations>) {
+        let mut max = 0.;
+        let mut sum = 0.;
+
+        for (entity_a, entity_b, normal, penetration, start_a, start_b) in
+            penetrations.contacts.iter()
+        {
+            let Ok([pos_a, pos_b]) = bodies.get_many([*entity_a, *entity_b]) else {
+                continue;
+            };
+            let solved = ((pos_b.0 - *start_b) - (pos_a.0 - *start_a)).dot(*normal);
+            let left = (penetration - solved).max(0.);
+
+            max = f32::max(max, left);
+            sum += left;
+        }
+
+        penetrations.max = max;
+        penetrations.avg = if penetrations.contacts.is_empty() {
+            0.
+        } else {
+            sum / penetrations.contacts.len() as f32
+        };
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn diagnostic_system(
+        mut diagnostics: ResMut<Diagnostics>,
+        bodies: Query<(&Vel, &Mass, &InvMass), With<RigidBody>>,
+        collision_pairs: Res<CollisionPairs>,
+        contacts: Res<Contacts>,
+        penetrations: Res<Penetrations>,
+        mut timings: ResMut<PhysicsTimings>,
+    ) {
+        if !timings.stepped {
+            return;
+        }
+
+        let (kinetic_energy, momentum) = bodies
+            .iter()
+            .filter(|(_, _, inv_mass)| inv_mass.0 > 0.)
+            .fold((0., Vec2::ZERO), |(energy, momentum), (vel, mass, _)| {
+                (
+                    energy + 0.5 * mass.0 * vel.0.length_squared(),
+                    momentum + mass.0 * vel.0,
+                )
+            });
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.;
+
+        diagnostics.add_measurement(Self::BODY_COUNT, || bodies.iter().count() as f64);
+        diagnostics.add_measurement(Self::COLLISION_PAIR_COUNT, || {
+            collision_pairs.0.len() as f64
+        });
+        diagnostics.add_measurement(Self::CONTACT_COUNT, || contacts.0.len() as f64);
+        diagnostics.add_measurement(Self::MAX_PENETRATION, || penetrations.max as f64);
+        diagnostics.add_measurement(Self::AVG_PENETRATION, || penetrations.avg as f64);
+        diagnostics.add_measurement(Self::KINETIC_ENERGY, || kinetic_energy as f64);
+        diagnostics.add_measurement(Self::MOMENTUM, || momentum.length() as f64);
+        diagnostics.add_measurement(Self::BROAD_PHASE_TIME, || {
+            millis(timings.spent[BROAD_PHASE])
+        });
+        diagnostics.add_measurement(Self::NARROW_PHASE_TIME, || {
+            millis(timings.spent[NARROW_PHASE])
+        });
+        diagnostics.add_measurement(Self::SOLVER_TIME, || millis(timings.spent[SOLVER]));
+
+        *timings = PhysicsTimings::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::BoxCollider,
+        components::*,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn diagnostics_track_resting_box() {
+        use bevy::diagnostic::{DiagnosticId, Diagnostics, DiagnosticsPlugin};
+
+        let mut app = test_app();
+
+        app.add_plugin(DiagnosticsPlugin)
+            .add_plugin(XpbdDiagnosticsPlugin);
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -10.)),
+            collider: BoxCollider {
+                size: Vec2::new(100., 10.),
+            },
+            ..default()
+        });
+        app.world.spawn(DynamicBoxBundle {
+            collider: BoxCollider { size: Vec2::ONE },
+            ..DynamicBoxBundle::new_with_pos_and_vel(Vec2::new(0., -4.4), Vec2::ZERO)
+        });
+
+        run_steps(&mut app, 60);
+
+        let diagnostics = app.world.resource::<Diagnostics>();
+        let value = |id: DiagnosticId| diagnostics.get(id).unwrap().value().unwrap();
+
+        assert_eq!(value(XpbdDiagnosticsPlugin::BODY_COUNT), 2.);
+        assert_eq!(value(XpbdDiagnosticsPlugin::COLLISION_PAIR_COUNT), 1.);
+        assert_eq!(value(XpbdDiagnosticsPlugin::CONTACT_COUNT), 1.);
+        assert!(value(XpbdDiagnosticsPlugin::MAX_PENETRATION) < 1e-4);
+        assert!(value(XpbdDiagnosticsPlugin::AVG_PENETRATION) < 1e-4);
+        // gravity pulls the box into the ground every substep, which the solver takes back
+        assert!(value(XpbdDiagnosticsPlugin::KINETIC_ENERGY) < 1e-3);
+        assert!(value(XpbdDiagnosticsPlugin::MOMENTUM) < 0.1);
+        assert!(value(XpbdDiagnosticsPlugin::SOLVER_TIME) > 0.);
+    }
+}
@@ -0,0 +1,205 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{
+    colliders::{BoxCollider, CircleCollider, CompoundCollider},
+    components::{Aabb, CollisionLayers, InvMass, Pos, Vel},
+    forces::Falloff,
+    queries::SpatialQuery,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Explosion {
+    pub center: Vec2,
+    /// Only bodies whose center is closer than this are pushed
+    pub radius: f32,
+    /// Impulse given to a body at the center, scaled down by `falloff` towards the edge
+    pub strength: f32,
+    pub falloff: Falloff,
+    /// Shields bodies hidden from the center behind static colliders
+    pub occluded: bool,
+    pub layers: CollisionLayers,
+}
+
+impl Explosion {
+    pub fn new(center: Vec2, radius: f32, strength: f32, falloff: Falloff) -> Self {
+        Self {
+            center,
+            radius,
+            strength,
+            falloff,
+            occluded: false,
+            layers: default(),
+        }
+    }
+
+    pub fn with_occlusion(self) -> Self {
+        Self {
+            occluded: true,
+            ..self
+        }
+    }
+
+    pub fn with_layers(self, layers: CollisionLayers) -> Self {
+        Self { layers, ..self }
+    }
+}
+
+/// Body caught in an explosion, static bodies are reported without being moved
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExplosionHit {
+    pub entity: Entity,
+    /// Impulse the body received, zero for static bodies
+    pub impulse: Vec2,
+}
+
+/// Applies explosions to the velocity of circle and box bodies, for use in systems
+#[derive(SystemParam)]
+pub struct Explosions<'w, 's> {
+    spatial_query: SpatialQuery<'w, 's>,
+    #[allow(clippy::type_complexity)]
+    bodies: Query<
+        'w,
+        's,
+        (&'static Pos, &'static mut Vel, &'static InvMass),
+        Or<(
+            With<CircleCollider>,
+            With<BoxCollider>,
+            With<CompoundCollider>,
+        )>,
+    >,
+}
+
+impl<'w, 's> Explosions<'w, 's> {
+    /// Pushes the bodies in range away from the center and returns them with the impulse they got
+    pub fn explode(&mut self, explosion: Explosion) -> Vec<ExplosionHit> {
+        let mut candidates = Vec::new();
+        let range = Aabb::from_center(explosion.center, Vec2::splat(explosion.radius));
+
+        self.spatial_query
+            .aabb_intersections(&range, explosion.layers, |entity| candidates.push(entity));
+
+        let mut hits = Vec::new();
+
+        for entity in candidates {
+            let Ok((pos, mut vel, inv_mass)) = self.bodies.get_mut(entity) else {
+                continue;
+            };
+            let offset = pos.0 - explosion.center;
+            let distance = offset.length();
+
+            if distance >= explosion.radius {
+                continue;
+            }
+
+            if explosion.occluded {
+                let spatial_query = &self.spatial_query;
+                let blocked =
+                    spatial_query.cast_ray(explosion.center, offset, explosion.layers, |other| {
+                        other != entity && spatial_query.is_static(other)
+                    });
+
+                if blocked.is_some() {
+                    continue;
+                }
+            }
+
+            // bodies right at the center are thrown upwards
+            let direction = if distance > f32::EPSILON {
+                offset / distance
+            } else {
+                Vec2::Y
+            };
+            let impulse = if inv_mass.0 > 0. {
+                direction
+                    * explosion.strength
+                    * explosion.falloff.factor(distance, explosion.radius)
+            } else {
+                Vec2::ZERO
+            };
+
+            vel.0 += impulse * inv_mass.0;
+            hits.push(ExplosionHit { entity, impulse });
+        }
+
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use crate::xpbd::{
+        components::*,
+        resources::Gravity,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn explosion_pushes_visible_bodies_in_range() {
+        let mut app = test_app();
+
+        app.insert_resource(Gravity(Vec2::ZERO));
+        let wall = app
+            .world
+            .spawn(StaticBoxBundle {
+                pos: Pos(Vec2::new(0., 20.)),
+                collider: BoxCollider {
+                    size: Vec2::new(40., 4.),
+                },
+                ..default()
+            })
+            .id();
+
+        let spawn_box = |app: &mut App, pos| {
+            app.world
+                .spawn(DynamicBoxBundle {
+                    collider: BoxCollider {
+                        size: Vec2::splat(4.),
+                    },
+                    ..DynamicBoxBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                })
+                .id()
+        };
+        let near = spawn_box(&mut app, Vec2::new(25., 0.));
+        let far = spawn_box(&mut app, Vec2::new(-60., 0.));
+        let hidden = spawn_box(&mut app, Vec2::new(0., 40.));
+
+        // aabbs and the static bvh are updated during the step
+        run_steps(&mut app, 1);
+
+        let mut state = SystemState::<Explosions>::new(&mut app.world);
+        let mut explosions = state.get_mut(&mut app.world);
+        let hits = explosions
+            .explode(Explosion::new(Vec2::ZERO, 50., 100., Falloff::Linear).with_occlusion());
+
+        let hit = |entity| hits.iter().find(|hit| hit.entity == entity);
+
+        assert_eq!(hits.len(), 2);
+        assert!(hit(near)
+            .unwrap()
+            .impulse
+            .abs_diff_eq(Vec2::new(50., 0.), 0.001));
+        // reported, but not moved
+        assert_eq!(hit(wall).unwrap().impulse, Vec2::ZERO);
+        assert_eq!(app.world.get::<Vel>(wall).unwrap().0, Vec2::ZERO);
+
+        let inv_mass = app.world.get::<InvMass>(near).unwrap().0;
+
+        assert!(app
+            .world
+            .get::<Vel>(near)
+            .unwrap()
+            .0
+            .abs_diff_eq(Vec2::new(50. * inv_mass, 0.), 0.001));
+        assert_eq!(app.world.get::<Vel>(far).unwrap().0, Vec2::ZERO);
+        assert_eq!(app.world.get::<Vel>(hidden).unwrap().0, Vec2::ZERO);
+
+        let mut explosions = state.get_mut(&mut app.world);
+        let hits = explosions.explode(Explosion::new(Vec2::ZERO, 50., 100., Falloff::Linear));
+
+        assert!(hits.iter().any(|hit| hit.entity == hidden));
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+pub use xpbd_core::fluids::*;
+
+/// Marks a particle as part of a fluid. Fluid particles don't collide with each other,
+/// instead they are kept apart by the density constraint of `FluidSettings`
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct FluidParticle;
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        components::{Pos, Vel},
+        resources::Gravity,
+        testing::{run_steps, spawn_ball, spawn_ground, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn fluid_settles_in_container() {
+        let mut app = test_app();
+        let spacing = 8.;
+
+        app.insert_resource(Gravity(Vec2::new(0., -300.)))
+            .insert_resource(FluidSettings::from_spacing(spacing));
+
+        for (pos, size) in [
+            (Vec2::new(0., -10.), Vec2::new(200., 20.)),
+            (Vec2::new(-90., 100.), Vec2::new(20., 200.)),
+            (Vec2::new(90., 100.), Vec2::new(20., 200.)),
+        ] {
+            spawn_ground(&mut app, pos, size);
+        }
+
+        // a column of water collapsing into a pool
+        let particles: Vec<_> = (0..120)
+            .map(|i| {
+                let pos = Vec2::new(-76., 4.) + Vec2::new((i % 6) as f32, (i / 6) as f32) * spacing;
+
+                spawn_ball(&mut app, pos, Vec2::ZERO, spacing / 2.)
+                    .insert(FluidParticle)
+                    .id()
+            })
+            .collect();
+
+        run_steps(&mut app, 300);
+
+        let positions: Vec<_> = particles
+            .iter()
+            .map(|entity| app.world.get::<Pos>(*entity).unwrap().0)
+            .collect();
+        let top = positions.iter().map(|pos| pos.y).fold(f32::MIN, f32::max);
+        let max_speed = particles
+            .iter()
+            .map(|entity| app.world.get::<Vel>(*entity).unwrap().0.length())
+            .fold(0., f32::max);
+
+        assert!(positions.iter().all(|pos| pos.x.abs() < 80. && pos.y > 0.));
+        // 120 particles spread over the 160 wide floor make a pool about 6 particles deep
+        assert!(top < 7.5 * spacing, "{top}");
+        assert!(max_speed < 20., "{max_speed}");
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+pub use xpbd_core::forces::*;
+
+use super::components::{CollisionLayers, Pos};
+
+#[derive(Bundle, Default)]
+pub struct ForceFieldBundle {
+    pub pos: Pos,
+    pub field: ForceField,
+    pub collision_layers: CollisionLayers,
+}
+
+impl ForceFieldBundle {
+    pub fn new(pos: Vec2, shape: ForceFieldShape, kind: ForceFieldKind) -> Self {
+        Self {
+            pos: Pos(pos),
+            field: ForceField { shape, kind },
+            ..default()
+        }
+    }
+}
+
+/// Gravity and the forces of the fields interacting with a body of `mass` at `pos`
+pub(crate) fn external_force<'a>(
+    gravity: Vec2,
+    fields: impl Iterator<Item = (&'a Pos, &'a ForceField, Option<&'a CollisionLayers>)>,
+    pos: Vec2,
+    vel: Vec2,
+    mass: f32,
+    layers: Option<&CollisionLayers>,
+) -> Vec2 {
+    let layers = layers.copied().unwrap_or_default();
+    let field_forces: Vec2 = fields
+        .filter(|(_, _, field_layers)| {
+            layers.interacts_with(&field_layers.copied().unwrap_or_default())
+        })
+        .map(|(field_pos, field, _)| field.force(pos - field_pos.0, vel, mass))
+        .sum();
+
+    mass * gravity + field_forces
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::CircleCollider,
+        components::*,
+        resources::Gravity,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn point_field_attracts_bodies_on_matching_layers() {
+        let mut app = test_app();
+
+        app.insert_resource(Gravity(Vec2::ZERO));
+        app.world.spawn(ForceFieldBundle {
+            collision_layers: CollisionLayers::new(0b01, 0b01),
+            ..ForceFieldBundle::new(
+                Vec2::ZERO,
+                ForceFieldShape::Circle { radius: 100. },
+                ForceFieldKind::Point {
+                    strength: 50.,
+                    falloff: Falloff::Constant,
+                },
+            )
+        });
+        let spawn_ball = |app: &mut App, pos: Vec2, layers: CollisionLayers| {
+            app.world
+                .spawn(ParticleBundle {
+                    collider: CircleCollider { radius: 1. },
+                    ..ParticleBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+                })
+                .insert(layers)
+                .id()
+        };
+        let attracted = spawn_ball(&mut app, Vec2::new(50., 0.), CollisionLayers::default());
+        let ignored = spawn_ball(
+            &mut app,
+            Vec2::new(-50., 0.),
+            CollisionLayers::new(0b10, 0b10),
+        );
+        let outside = spawn_ball(&mut app, Vec2::new(0., 150.), CollisionLayers::default());
+
+        run_steps(&mut app, 30);
+
+        assert!(app.world.get::<Pos>(attracted).unwrap().0.x < 45.);
+        assert_eq!(
+            app.world.get::<Pos>(ignored).unwrap().0,
+            Vec2::new(-50., 0.)
+        );
+        assert_eq!(
+            app.world.get::<Pos>(outside).unwrap().0,
+            Vec2::new(0., 150.)
+        );
+    }
+
+    #[test]
+    fn drag_field_slows_bodies() {
+        let mut app = test_app();
+
+        app.insert_resource(Gravity(Vec2::ZERO));
+        app.world.spawn(ForceFieldBundle::new(
+            Vec2::ZERO,
+            ForceFieldShape::Box {
+                size: Vec2::splat(1000.),
+            },
+            ForceFieldKind::Drag { coefficient: 2. },
+        ));
+        let body = app
+            .world
+            .spawn(DynamicBoxBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::new(100., 0.),
+            ))
+            .id();
+
+        run_steps(&mut app, 60);
+
+        let vel = app.world.get::<Vel>(body).unwrap().0;
+
+        // exponential decay, e^-2 after one second
+        assert!((vel.x - 100. * (-2f32).exp()).abs() < 1., "{vel:?}");
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use super::components::{CombineRule, ContactSoftness};
+
+/// Shared surface and bulk properties of bodies.
+/// Bodies opt in by adding a `Handle<PhysicsMaterial>`, which keeps their `Density`, `Restitution`, `Friction` and `ContactSoftness` in sync with the asset.
+/// Bodies with `ExplicitMass` keep their `Mass` and get no `Density`
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[serde(default)]
+#[uuid = "c1734950-7471-4180-ade6-0b1ee61ceb79"]
+pub struct PhysicsMaterial {
+    pub density: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub restitution_combine: CombineRule,
+    pub friction_combine: CombineRule,
+    /// Softness of contacts with the surface, rigid by default
+    pub contact_softness: ContactSoftness,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            density: 1.,
+            restitution: 0.3,
+            friction: 0.,
+            restitution_combine: CombineRule::Average,
+            friction_combine: CombineRule::Average,
+            contact_softness: ContactSoftness::default(),
+        }
+    }
+}
@@ -1,7 +1,21 @@
+pub use xpbd_core::{bvh, consts, contact, terrain};
+
+pub mod character;
 pub mod colliders;
 pub mod components;
-pub mod consts;
-pub mod contact;
+pub mod constraints;
+pub mod diagnostics;
+pub mod explosions;
+pub mod fluids;
+pub mod forces;
+pub mod materials;
+pub mod mouse_drag;
 pub mod plugin;
+pub mod queries;
+pub mod recording;
 pub mod resources;
+pub mod scene;
+pub mod soft_bodies;
+#[cfg(test)]
+mod testing;
 pub mod xpdb_loop;
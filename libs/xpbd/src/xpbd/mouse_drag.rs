@@ -0,0 +1,271 @@
+use bevy::{input::InputSystem, prelude::*, render::camera::RenderTarget};
+
+use super::{
+    components::{InvMass, Pos, Vel},
+    constraints::DistanceConstraint,
+    queries::SpatialQuery,
+    terrain::BodyShape,
+};
+
+// radius around the cursor in which bodies are picked
+const PICK_RADIUS: f32 = 0.5;
+// how much of the latest cursor movement goes into the throw velocity every frame
+const CURSOR_VEL_SMOOTHING: f32 = 0.5;
+
+/// Lets dynamic bodies be grabbed with the mouse and dragged around, for tuning scenes.
+/// The cursor is converted to world space with the first camera
+pub struct MouseDragPlugin;
+
+impl Plugin for MouseDragPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MouseDrag>().add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::new()
+                .after(InputSystem)
+                .with_system(MouseDragPlugin::track_cursor)
+                .with_system(MouseDragPlugin::grab.after(MouseDragPlugin::track_cursor))
+                .with_system(MouseDragPlugin::drag.after(MouseDragPlugin::grab)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Grab {
+    body: Entity,
+    anchor: Entity,
+    constraint: Entity,
+    // from the cursor to the body position, kept while dragging
+    offset: Vec2,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct MouseDrag {
+    pub button: MouseButton,
+    /// Compliance of the spring pulling the grabbed body towards the cursor
+    pub compliance: f32,
+    /// Fraction of the cursor velocity given to the body when it is released
+    pub throw_factor: f32,
+    /// Last known cursor position in world space
+    pub cursor: Option<Vec2>,
+    cursor_vel: Vec2,
+    grab: Option<Grab>,
+}
+
+impl Default for MouseDrag {
+    fn default() -> Self {
+        Self {
+            button: MouseButton::Left,
+            compliance: 0.001,
+            throw_factor: 1.,
+            cursor: None,
+            cursor_vel: Vec2::ZERO,
+            grab: None,
+        }
+    }
+}
+
+impl MouseDrag {
+    pub fn grabbed(&self) -> Option<Entity> {
+        self.grab.map(|grab| grab.body)
+    }
+
+    /// Moves the cursor to `world_pos`, estimating its velocity over `delta_seconds`
+    pub fn move_cursor(&mut self, world_pos: Vec2, delta_seconds: f32) {
+        if let (Some(prev), true) = (self.cursor, delta_seconds > 0.) {
+            let vel = (world_pos - prev) / delta_seconds;
+
+            self.cursor_vel = self.cursor_vel.lerp(vel, CURSOR_VEL_SMOOTHING);
+        }
+
+        self.cursor = Some(world_pos);
+    }
+}
+
+impl MouseDragPlugin {
+    fn track_cursor(
+        windows: Res<Windows>,
+        cameras: Query<(&Camera, &GlobalTransform)>,
+        time: Res<Time>,
+        mut mouse_drag: ResMut<MouseDrag>,
+    ) {
+        let Some((camera, camera_transform)) = cameras.iter().next() else {
+            return;
+        };
+        let window = if let RenderTarget::Window(id) = camera.target {
+            windows.get(id)
+        } else {
+            windows.get_primary()
+        };
+        let Some(screen_pos) = window.and_then(|window| {
+            let window_size = Vec2::new(window.width(), window.height());
+
+            window.cursor_position().map(|pos| pos / window_size)
+        }) else {
+            return;
+        };
+
+        // screen to ndc, then undo the projection and camera transform
+        let ndc = screen_pos * 2. - Vec2::ONE;
+        let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+        let world_pos = ndc_to_world.project_point3(ndc.extend(-1.)).truncate();
+
+        mouse_drag.move_cursor(world_pos, time.delta_seconds());
+    }
+
+    fn grab(
+        mut commands: Commands,
+        buttons: Res<Input<MouseButton>>,
+        mut mouse_drag: ResMut<MouseDrag>,
+        spatial_query: SpatialQuery,
+        bodies: Query<&Pos>,
+        mut vels: Query<&mut Vel>,
+    ) {
+        let Some(cursor) = mouse_drag.cursor else {
+            return;
+        };
+
+        // released, or the body is gone
+        if let Some(grab) = mouse_drag.grab {
+            if buttons.pressed(mouse_drag.button) && bodies.contains(grab.body) {
+                return;
+            }
+
+            commands.entity(grab.anchor).despawn();
+            commands.entity(grab.constraint).despawn();
+
+            if let Ok(mut vel) = vels.get_mut(grab.body) {
+                vel.0 = mouse_drag.cursor_vel * mouse_drag.throw_factor;
+            }
+
+            mouse_drag.grab = None;
+        }
+
+        if !buttons.just_pressed(mouse_drag.button) {
+            return;
+        }
+
+        let mut picked: Option<(Entity, f32)> = None;
+
+        spatial_query.shape_intersections(
+            BodyShape::Circle {
+                radius: PICK_RADIUS,
+            },
+            cursor,
+            default(),
+            |entity, contact| {
+                if !spatial_query.is_static(entity)
+                    && picked.is_none_or(|(_, depth)| contact.penetration > depth)
+                {
+                    picked = Some((entity, contact.penetration));
+                }
+            },
+        );
+
+        let Some((body, _)) = picked else {
+            return;
+        };
+        let Ok(pos) = bodies.get(body) else {
+            return;
+        };
+        let anchor = commands.spawn((Pos(pos.0), InvMass(0.))).id();
+        let constraint = commands
+            .spawn(DistanceConstraint::new(anchor, body, 0.).with_compliance(mouse_drag.compliance))
+            .id();
+
+        mouse_drag.cursor_vel = Vec2::ZERO;
+        mouse_drag.grab = Some(Grab {
+            body,
+            anchor,
+            constraint,
+            offset: pos.0 - cursor,
+        });
+    }
+
+    fn drag(mouse_drag: Res<MouseDrag>, mut anchors: Query<&mut Pos>) {
+        let (Some(grab), Some(cursor)) = (mouse_drag.grab, mouse_drag.cursor) else {
+            return;
+        };
+
+        if let Ok(mut pos) = anchors.get_mut(grab.anchor) {
+            pos.0 = cursor + grab.offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::CircleCollider,
+        components::*,
+        consts::DELTA_TIME,
+        resources::Gravity,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn mouse_drags_and_throws_bodies() {
+        let mut app = test_app();
+
+        app.insert_resource(Gravity(Vec2::ZERO))
+            .init_resource::<Windows>()
+            .init_resource::<Input<MouseButton>>()
+            .add_plugin(MouseDragPlugin);
+
+        let ball = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: 2. },
+                ..ParticleBundle::new_with_pos_and_vel(Vec2::ZERO, Vec2::ZERO)
+            })
+            .id();
+
+        run_steps(&mut app, 1);
+
+        let mut cursor = Vec2::new(1., 0.);
+
+        app.world
+            .resource_mut::<MouseDrag>()
+            .move_cursor(cursor, DELTA_TIME);
+        app.world
+            .resource_mut::<Input<MouseButton>>()
+            .press(MouseButton::Left);
+        run_steps(&mut app, 1);
+        app.world.resource_mut::<Input<MouseButton>>().clear();
+
+        assert_eq!(app.world.resource::<MouseDrag>().grabbed(), Some(ball));
+
+        // dragged right at 60 units per second
+        for _ in 0..30 {
+            cursor.x += 1.;
+            app.world
+                .resource_mut::<MouseDrag>()
+                .move_cursor(cursor, DELTA_TIME);
+            run_steps(&mut app, 1);
+        }
+
+        assert!(app.world.get::<Pos>(ball).unwrap().0.x > 25.);
+
+        cursor.x += 1.;
+        app.world
+            .resource_mut::<MouseDrag>()
+            .move_cursor(cursor, DELTA_TIME);
+        app.world
+            .resource_mut::<Input<MouseButton>>()
+            .release(MouseButton::Left);
+        run_steps(&mut app, 1);
+
+        let vel = app.world.get::<Vel>(ball).unwrap().0;
+
+        assert_eq!(app.world.resource::<MouseDrag>().grabbed(), None);
+        assert!(vel.abs_diff_eq(Vec2::new(60., 0.), 1.), "{vel:?}");
+        assert_eq!(
+            app.world
+                .query::<&DistanceConstraint>()
+                .iter(&app.world)
+                .count(),
+            0
+        );
+    }
+}
@@ -1,32 +1,202 @@
-use bevy::prelude::*;
+use bevy::{math::Affine3A, prelude::*, utils::HashSet};
 
 use super::{
+    bvh::Bvh,
+    character::{move_character, CharacterContacts, CharacterController, CharacterShape},
     colliders::*,
     components::*,
-    consts::*,
-    contact::{ball_ball, ball_box, box_box, Contact},
+    constraints::DistanceConstraint,
+    contact::Contact,
+    fluids::{FluidParticle, FluidSettings, FluidSolver},
+    forces::*,
+    materials::PhysicsMaterial,
+    queries::SpatialQuery,
     resources::*,
+    scene::{PhysicsScene, PhysicsSceneInstance, PhysicsSceneLoader},
+    soft_bodies::{shape_match, SoftBody, SoftBodyParticle},
+    terrain::*,
     xpdb_loop::{first_substep, last_substep, run_criteria, XpbdLoop},
 };
+use xpbd_core::{
+    pipeline::{
+        collect_collision_pairs, collect_contacts, collect_terrain_contacts, solve_velocities,
+        BodyPositions, BodyVelocities, BroadPhaseBody, PlacedBody, PositionState, Solver,
+        VelocityState,
+    },
+    solver::{constrain_distance, derive_vel, integrate, limit_motion, limited_vel},
+};
+
+// how deep a body may already be in a one-way body at the start of a substep and still be pushed out of it
+const ONE_WAY_SLOP: f32 = 0.1;
+// cosine of the largest angle between a contact and `OneWay::normal` that still blocks the body
+const ONE_WAY_MIN_ALIGNMENT: f32 = 0.5;
+// how far a transform may be from its body before it counts as moved by the user,
+// the position of parented bodies going through the parent's transform both ways
+const TELEPORT_EPSILON: f32 = 1e-3;
+
+/// Stage running the physics steps, every system in it runs once per substep unless it has a run criteria
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct FixedUpdateStage;
 
+// right before `CoreStage::Last`, which forgets the removed components of the frame first thing
 #[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
-struct FixedUpdateStage;
+struct RecordRemovalsStage;
+
+/// Points of the physics step where user systems are added with `XpbdAppExt::add_physics_system`
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhysicsSet {
+    /// Once per step before anything else, e.g. to apply inputs
+    PrePhysics,
+    /// Every substep, before bodies are integrated
+    Substep,
+    /// Every substep, after contacts are found and before they are solved.
+    /// Entries of `Contacts` can be changed or removed, after `OneWay` and `SurfaceVelocity` were applied
+    ModifyContacts,
+    /// Every substep, after positions are solved and before velocities are derived from them.
+    /// `Contacts` and `CollidingEntities` are up to date
+    PostSolve,
+    /// Once per step after velocities are solved and transforms synced
+    PostPhysics,
+}
+
+pub trait XpbdAppExt {
+    /// Adds a system to the physics stage at `set`, replacing its run criteria
+    fn add_physics_system<Params>(
+        &mut self,
+        set: PhysicsSet,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self;
+}
+
+impl XpbdAppExt for App {
+    fn add_physics_system<Params>(
+        &mut self,
+        set: PhysicsSet,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self {
+        let system = system.label(set);
+        let system = match set {
+            PhysicsSet::PrePhysics => system
+                .with_run_criteria(first_substep)
+                .before(Step::UpdateAabbs)
+                .before(Step::UpdateMassProperties)
+                .before(XpbdPlugin::update_inv_mass)
+                .before(XpbdPlugin::move_characters)
+                .before(XpbdPlugin::clear_colliding_entities),
+            PhysicsSet::Substep => system
+                .after(PhysicsSet::PrePhysics)
+                .after(XpbdPlugin::clear_contacs)
+                .before(XpbdPlugin::integrate),
+            PhysicsSet::ModifyContacts => system
+                .after(Step::ModifyContacts)
+                .before(Step::SolvePositions),
+            PhysicsSet::PostSolve => system
+                .after(XpbdPlugin::update_colliding_entities)
+                .before(XpbdPlugin::update_vel),
+            PhysicsSet::PostPhysics => system
+                .with_run_criteria(last_substep)
+                .after(XpbdPlugin::sync_transforms),
+        };
+
+        self.add_system_to_stage(FixedUpdateStage, system)
+    }
+}
 
 #[derive(SystemLabel)]
-enum Step {
+pub(crate) enum Step {
+    UpdateMassProperties,
+    UpdateAabbs,
+    BroadPhase,
+    Integrate,
+    NarrowPhase,
+    ModifyContacts,
     SolvePositions,
     SolveVelocities,
 }
 
+/// Particles of the same group don't collide with each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParticleGroup {
+    Fluid,
+    SoftBody(Entity),
+}
+
 pub struct XpbdPlugin;
 
 impl Plugin for XpbdPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<XpbdLoop>()
+        app.add_asset::<PhysicsMaterial>()
+            .add_asset::<PhysicsScene>()
+            .init_asset_loader::<PhysicsSceneLoader>()
+            .register_type::<RigidBody>()
+            .register_type::<Pos>()
+            .register_type::<PrevPos>()
+            .register_type::<Vel>()
+            .register_type::<PreSolveVel>()
+            .register_type::<Mass>()
+            .register_type::<InvMass>()
+            .register_type::<Inertia>()
+            .register_type::<Density>()
+            .register_type::<Restitution>()
+            .register_type::<Friction>()
+            .register_type::<CombineRule>()
+            .register_type::<RestitutionCombine>()
+            .register_type::<FrictionCombine>()
+            .register_type::<CollisionLayers>()
+            .register_type::<OneWay>()
+            .register_type::<SurfaceVelocity>()
+            .register_type::<CollidingEntities>()
+            .register_type::<(Entity, Vec2)>()
+            .register_type::<Vec<(Entity, Vec2)>>()
+            .register_type::<LockedAxes>()
+            .register_type::<MaxLinearSpeed>()
+            .register_type::<ContactSoftness>()
+            .register_type::<Aabb>()
+            .register_type::<ForceField>()
+            .register_type::<ForceFieldShape>()
+            .register_type::<ForceFieldKind>()
+            .register_type::<Falloff>()
+            .register_type::<FluidParticle>()
+            .register_type::<FluidSettings>()
+            .register_type::<ExplicitMass>()
+            .register_type::<SyncedTranslation>()
+            .register_type::<CharacterController>()
+            .register_type::<CharacterShape>()
+            .register_type::<CircleCollider>()
+            .register_type::<BoxCollider>()
+            .register_type::<ChildCollider>()
+            .register_type::<CompoundCollider>()
+            .register_type::<BodyShape>()
+            .register_type::<(Vec2, BodyShape)>()
+            .register_type::<Vec<(Vec2, BodyShape)>>()
+            .register_type::<SegmentCollider>()
+            .register_type::<ChainCollider>()
+            .register_type::<Gravity>()
+            .register_type::<RestitutionThreshold>()
+            .register_type::<SolverSettings>()
+            .init_resource::<XpbdLoop>()
             .init_resource::<Gravity>()
+            .init_resource::<RestitutionThreshold>()
+            .init_resource::<SolverSettings>()
             .init_resource::<Contacts>()
-            .init_resource::<StaticContacts>()
             .init_resource::<CollisionPairs>()
+            .init_resource::<StaticBvh>()
+            .init_resource::<RemovedBodies>()
+            .init_resource::<FluidSettings>()
+            .add_system_to_stage(CoreStage::PreUpdate, XpbdPlugin::apply_physics_materials)
+            .add_system_to_stage(CoreStage::PreUpdate, XpbdPlugin::update_compound_colliders)
+            .add_system_to_stage(CoreStage::PreUpdate, XpbdPlugin::teleport_moved_bodies)
+            .add_system_to_stage(CoreStage::PreUpdate, XpbdPlugin::record_removed_bodies)
+            .add_stage_before(
+                CoreStage::Last,
+                RecordRemovalsStage,
+                SystemStage::single(XpbdPlugin::record_removed_bodies),
+            )
+            // despawns happen after material updates, so those never target removed bodies
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                XpbdPlugin::spawn_physics_scenes.after(XpbdPlugin::apply_physics_materials),
+            )
             .add_stage_before(
                 CoreStage::Update,
                 FixedUpdateStage,
@@ -35,32 +205,110 @@ impl Plugin for XpbdPlugin {
                     .with_run_criteria(run_criteria)
                     .with_system_set(
                         SystemSet::new()
-                            .before(XpbdPlugin::collect_collision_pairs)
+                            .label(Step::UpdateAabbs)
                             .with_system(XpbdPlugin::update_aabb_box)
-                            .with_system(XpbdPlugin::update_aabb_circle),
+                            .with_system(XpbdPlugin::update_aabb_circle)
+                            .with_system(XpbdPlugin::update_aabb_compound)
+                            .with_system(XpbdPlugin::update_aabb_terrain::<SegmentCollider>)
+                            .with_system(XpbdPlugin::update_aabb_terrain::<ChainCollider>)
+                            .with_system(XpbdPlugin::update_aabb_terrain::<TileMapCollider>),
+                    )
+                    .with_system_set(
+                        SystemSet::new()
+                            .label(Step::UpdateMassProperties)
+                            .with_system(XpbdPlugin::update_mass_properties::<CircleCollider>)
+                            .with_system(XpbdPlugin::update_mass_properties::<BoxCollider>)
+                            .with_system(XpbdPlugin::update_compound_mass_properties),
+                    )
+                    .with_system(
+                        XpbdPlugin::update_inv_mass
+                            .after(Step::UpdateMassProperties)
+                            .before(XpbdPlugin::integrate),
+                    )
+                    .with_system(
+                        XpbdPlugin::update_static_bvh
+                            .label(Step::BroadPhase)
+                            .with_run_criteria(first_substep)
+                            .after(Step::UpdateAabbs),
+                    )
+                    .with_system(
+                        XpbdPlugin::collect_collision_pairs
+                            .label(Step::BroadPhase)
+                            .with_run_criteria(first_substep)
+                            .after(XpbdPlugin::update_static_bvh),
+                    )
+                    .with_system(
+                        XpbdPlugin::move_characters
+                            .with_run_criteria(first_substep)
+                            .after(XpbdPlugin::update_static_bvh),
                     )
                     .with_system(
-                        XpbdPlugin::collect_collision_pairs.with_run_criteria(first_substep),
+                        XpbdPlugin::integrate
+                            .label(Step::Integrate)
+                            .after(XpbdPlugin::collect_collision_pairs),
                     )
-                    .with_system(XpbdPlugin::integrate.after(XpbdPlugin::collect_collision_pairs))
-                    .with_system(XpbdPlugin::clear_contacs.before(Step::SolvePositions))
+                    .with_system(XpbdPlugin::clear_contacs.after(PhysicsSet::PrePhysics))
+                    .with_system(
+                        XpbdPlugin::clear_colliding_entities
+                            .with_run_criteria(first_substep)
+                            .before(XpbdPlugin::update_colliding_entities),
+                    )
+                    .with_system(XpbdPlugin::update_colliding_entities.after(Step::SolvePositions))
                     .with_system_set(
                         SystemSet::new()
-                            .label(Step::SolvePositions)
+                            .label(Step::NarrowPhase)
                             .after(XpbdPlugin::integrate)
-                            .with_system(XpbdPlugin::solve_pos)
-                            .with_system(XpbdPlugin::solve_pos_box_box)
-                            .with_system(XpbdPlugin::sol_pos_statics)
-                            .with_system(XpbdPlugin::solve_pos_static_boxes)
-                            .with_system(XpbdPlugin::solve_pos_static_box_box),
+                            .after(XpbdPlugin::clear_contacs)
+                            // in a fixed order so contacts are solved the same way every run
+                            .with_system(XpbdPlugin::collect_contacts)
+                            .with_system(
+                                XpbdPlugin::collect_terrain_contacts::<SegmentCollider>
+                                    .after(XpbdPlugin::collect_contacts),
+                            )
+                            .with_system(
+                                XpbdPlugin::collect_terrain_contacts::<ChainCollider>
+                                    .after(XpbdPlugin::collect_terrain_contacts::<SegmentCollider>),
+                            )
+                            .with_system(
+                                XpbdPlugin::collect_terrain_contacts::<TileMapCollider>
+                                    .after(XpbdPlugin::collect_terrain_contacts::<ChainCollider>),
+                            ),
+                    )
+                    .with_system_set(
+                        SystemSet::new()
+                            .label(Step::ModifyContacts)
+                            .after(Step::NarrowPhase)
+                            .with_system(XpbdPlugin::apply_one_way)
+                            .with_system(XpbdPlugin::apply_surface_velocity),
+                    )
+                    .with_system_set(
+                        SystemSet::new()
+                            .label(Step::SolvePositions)
+                            .after(Step::ModifyContacts)
+                            .with_system(XpbdPlugin::solve_contacts)
+                            .with_system(
+                                XpbdPlugin::solve_distance_constraints
+                                    .after(XpbdPlugin::solve_contacts),
+                            )
+                            .with_system(
+                                XpbdPlugin::solve_fluid_density.after(XpbdPlugin::solve_contacts),
+                            )
+                            .with_system(
+                                XpbdPlugin::solve_soft_bodies.after(XpbdPlugin::solve_contacts),
+                            ),
                     )
                     .with_system(XpbdPlugin::update_vel.after(Step::SolvePositions))
+                    .with_system(
+                        XpbdPlugin::solve_fluid_velocities
+                            .after(XpbdPlugin::update_vel)
+                            .before(Step::SolveVelocities),
+                    )
                     .with_system_set(
                         SystemSet::new()
                             .label(Step::SolveVelocities)
                             .after(XpbdPlugin::update_vel)
                             .with_system(XpbdPlugin::solve_vel)
-                            .with_system(XpbdPlugin::solve_vel_static),
+                            .with_system(XpbdPlugin::limit_vel.after(XpbdPlugin::solve_vel)),
                     )
                     .with_system(
                         XpbdPlugin::sync_transforms
@@ -71,253 +319,1599 @@ impl Plugin for XpbdPlugin {
     }
 }
 
+// inverse mass per axis of a body for the position projections
+fn axis_inv_mass(inv_mass: &InvMass, locked_axes: Option<&LockedAxes>) -> Vec2 {
+    locked_axes
+        .copied()
+        .unwrap_or_default()
+        .inv_mass(inv_mass.0)
+}
+
+type PositionQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Pos,
+        &'static PrevPos,
+        &'static InvMass,
+        Option<&'static LockedAxes>,
+        Option<&'static ContactSoftness>,
+    ),
+>;
+
+// bodies of the position pass of `pipeline`
+struct PositionBodies<'q, 'w, 's>(&'q mut PositionQuery<'w, 's>);
+
+impl BodyPositions<Entity> for PositionBodies<'_, '_, '_> {
+    fn get(&self, entity: Entity) -> Option<PositionState> {
+        let (pos, prev_pos, inv_mass, locked_axes, softness) = self.0.get(entity).ok()?;
+
+        Some(PositionState {
+            pos: pos.0,
+            prev_pos: prev_pos.0,
+            inv_mass: axis_inv_mass(inv_mass, locked_axes),
+            contact_softness: softness.copied().unwrap_or_default(),
+        })
+    }
+
+    fn set_pos(&mut self, entity: Entity, new_pos: Vec2) {
+        if let Ok((mut pos, ..)) = self.0.get_mut(entity) {
+            pos.0 = new_pos;
+        }
+    }
+}
+
+type VelocityQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Vel,
+        &'static PreSolveVel,
+        &'static InvMass,
+        Option<&'static LockedAxes>,
+        Option<&'static Restitution>,
+        Option<&'static Friction>,
+        Option<&'static RestitutionCombine>,
+        Option<&'static FrictionCombine>,
+    ),
+>;
+
+// bodies of the velocity pass of `pipeline`. Bodies spawned without `Restitution` or `Friction`
+// bounce and slide like the bundle defaults
+struct VelocityBodies<'q, 'w, 's>(&'q mut VelocityQuery<'w, 's>);
+
+impl BodyVelocities<Entity> for VelocityBodies<'_, '_, '_> {
+    fn get(&self, entity: Entity) -> Option<VelocityState> {
+        let (
+            vel,
+            pre_solve_vel,
+            inv_mass,
+            locked_axes,
+            restitution,
+            friction,
+            restitution_combine,
+            friction_combine,
+        ) = self.0.get(entity).ok()?;
+
+        Some(VelocityState {
+            vel: vel.0,
+            pre_solve_vel: pre_solve_vel.0,
+            inv_mass: axis_inv_mass(inv_mass, locked_axes),
+            restitution: restitution.copied().unwrap_or_default().0,
+            restitution_combine: restitution_combine.copied().unwrap_or_default().0,
+            friction: friction.copied().unwrap_or_default().0,
+            friction_combine: friction_combine.copied().unwrap_or_default().0,
+        })
+    }
+
+    fn set_vel(&mut self, entity: Entity, new_vel: Vec2) {
+        if let Ok((mut vel, ..)) = self.0.get_mut(entity) {
+            vel.0 = new_vel;
+        }
+    }
+}
+
+type SyncQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        Option<&'static Pos>,
+        Option<&'static Parent>,
+        Option<&'static mut SyncedTranslation>,
+    ),
+>;
+
+// where `entity` is in world space after this step, bodies being at their `Pos` whatever their transform says yet
+fn world_affine(query: &SyncQuery, entity: Entity) -> Affine3A {
+    let Ok((_, transform, pos, parent, _)) = query.get(entity) else {
+        return Affine3A::IDENTITY;
+    };
+    let parent_affine = parent.map_or(Affine3A::IDENTITY, |parent| {
+        world_affine(query, parent.get())
+    });
+    let mut affine = parent_affine * transform.compute_affine();
+
+    if let Some(pos) = pos {
+        affine.translation = pos.0.extend(affine.translation.z).into();
+    }
+
+    affine
+}
+
 impl XpbdPlugin {
-    fn update_aabb_circle(mut query: Query<(&mut Aabb, &Pos, &Vel, &CircleCollider)>) {
+    // spawns scenes once they are loaded and respawns them when the asset or the handle changes
+    fn spawn_physics_scenes(
+        mut commands: Commands,
+        mut events: EventReader<AssetEvent<PhysicsScene>>,
+        scenes: Res<Assets<PhysicsScene>>,
+        mut physics_materials: ResMut<Assets<PhysicsMaterial>>,
+        query: Query<(Entity, &Handle<PhysicsScene>, Option<&PhysicsSceneInstance>)>,
+    ) {
+        let modified: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                AssetEvent::Modified { handle } => Some(handle),
+                AssetEvent::Created { .. } | AssetEvent::Removed { .. } => None,
+            })
+            .collect();
+
+        for (entity, handle, instance) in query.iter() {
+            let is_spawned = instance.is_some_and(|instance| instance.scene == handle.id());
+
+            if is_spawned && !modified.contains(&handle) {
+                continue;
+            }
+
+            let Some(scene) = scenes.get(handle) else {
+                continue;
+            };
+
+            for spawned in instance
+                .iter()
+                .flat_map(|instance| instance.entities.iter())
+            {
+                if let Some(spawned) = commands.get_entity(*spawned) {
+                    spawned.despawn_recursive();
+                }
+            }
+
+            let entities = scene.spawn(&mut commands, &mut physics_materials);
+
+            commands.entity(entity).insert(PhysicsSceneInstance {
+                scene: handle.id(),
+                entities,
+            });
+        }
+    }
+
+    // gathers the parts of compound bodies from their children, the body's own shape being at its position.
+    // Only bodies whose children or shapes changed are rebuilt
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    fn update_compound_colliders(
+        mut commands: Commands,
+        changed_children: Query<
+            &Parent,
+            (
+                With<ChildCollider>,
+                Or<(
+                    Changed<ChildCollider>,
+                    Changed<CircleCollider>,
+                    Changed<BoxCollider>,
+                )>,
+            ),
+        >,
+        changed_bodies: Query<
+            Entity,
+            (
+                With<RigidBody>,
+                Or<(
+                    Changed<Children>,
+                    Changed<CircleCollider>,
+                    Changed<BoxCollider>,
+                )>,
+            ),
+        >,
+        removed_children: RemovedComponents<Children>,
+        removed_parts: RemovedComponents<ChildCollider>,
+        compounds: Query<Entity, With<CompoundCollider>>,
+        parts: Query<(
+            &ChildCollider,
+            Option<&CircleCollider>,
+            Option<&BoxCollider>,
+        )>,
+        mut bodies: Query<
+            (
+                Option<&Children>,
+                Option<&CircleCollider>,
+                Option<&BoxCollider>,
+                Option<&mut CompoundCollider>,
+            ),
+            With<RigidBody>,
+        >,
+        mut dirty: Local<HashSet<Entity>>,
+    ) {
+        dirty.clear();
+        dirty.extend(changed_children.iter().map(|parent| parent.get()));
+        dirty.extend(changed_bodies.iter());
+        dirty.extend(removed_children.iter());
+
+        // the parent of a removed part may be gone already, so every compound is checked
+        if removed_parts.iter().next().is_some() {
+            dirty.extend(compounds.iter());
+        }
+
+        for &entity in dirty.iter() {
+            let Ok((children, circle, box_, compound)) = bodies.get_mut(entity) else {
+                continue;
+            };
+            let child_parts: Vec<_> = parts
+                .iter_many(children.into_iter().flatten())
+                .filter_map(|(child, circle, box_)| {
+                    with_body_parts(circle, box_, None, |parts| (child.offset, parts[0].1))
+                })
+                .collect();
+
+            if child_parts.is_empty() {
+                if compound.is_some() {
+                    commands.entity(entity).remove::<CompoundCollider>();
+                }
+
+                continue;
+            }
+
+            let own_part = with_body_parts(circle, box_, None, |parts| parts[0]);
+            let new_compound =
+                CompoundCollider::new(own_part.into_iter().chain(child_parts).collect());
+
+            match compound {
+                Some(compound) if *compound == new_compound => {}
+                Some(mut compound) => *compound = new_compound,
+                None => {
+                    commands.entity(entity).insert(new_compound);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn apply_physics_materials(
+        mut commands: Commands,
+        mut events: EventReader<AssetEvent<PhysicsMaterial>>,
+        physics_materials: Res<Assets<PhysicsMaterial>>,
+        query: Query<(
+            Entity,
+            &Handle<PhysicsMaterial>,
+            ChangeTrackers<Handle<PhysicsMaterial>>,
+            Option<&ExplicitMass>,
+        )>,
+    ) {
+        let modified: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                AssetEvent::Created { handle } | AssetEvent::Modified { handle } => Some(handle),
+                AssetEvent::Removed { .. } => None,
+            })
+            .collect();
+
+        for (entity, handle, tracker, explicit_mass) in query.iter() {
+            if !tracker.is_changed() && !modified.contains(&handle) {
+                continue;
+            }
+
+            if let Some(material) = physics_materials.get(handle) {
+                if explicit_mass.is_none() {
+                    commands.entity(entity).insert(Density(material.density));
+                }
+
+                commands.entity(entity).insert((
+                    Restitution(material.restitution),
+                    Friction(material.friction),
+                    RestitutionCombine(material.restitution_combine),
+                    FrictionCombine(material.friction_combine),
+                    material.contact_softness,
+                ));
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_mass_properties<C: Component + ColliderShape>(
+        mut query: Query<
+            (&mut Mass, Option<&mut Inertia>, &Density, &C),
+            (
+                Or<(Changed<Density>, Changed<C>)>,
+                Without<CompoundCollider>,
+            ),
+        >,
+    ) {
+        for (mut mass, inertia, density, shape) in query.iter_mut() {
+            mass.0 = density.0 * shape.area();
+
+            if let Some(mut inertia) = inertia {
+                inertia.0 = mass.0 * shape.unit_inertia();
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_compound_mass_properties(
+        mut query: Query<
+            (&mut Mass, Option<&mut Inertia>, &Density, &CompoundCollider),
+            Or<(Changed<Density>, Changed<CompoundCollider>)>,
+        >,
+    ) {
+        for (mut mass, inertia, density, compound) in query.iter_mut() {
+            let (compound_mass, compound_inertia) = compound.mass_properties(density.0);
+
+            mass.0 = compound_mass;
+
+            if let Some(mut inertia) = inertia {
+                inertia.0 = compound_inertia;
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_inv_mass(
+        mut query: Query<
+            (&mut InvMass, &Mass, &RigidBody),
+            Or<(Changed<Mass>, Changed<RigidBody>)>,
+        >,
+    ) {
+        for (mut inv_mass, mass, rigid_body) in query.iter_mut() {
+            inv_mass.0 = if rigid_body.is_static() || mass.0 <= 0. {
+                0.
+            } else {
+                1. / mass.0
+            };
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_aabb_circle(
+        mut query: Query<(&mut Aabb, &Pos, &Vel, &CircleCollider), Without<CompoundCollider>>,
+    ) {
         for (mut aabb, pos, vel, circle) in query.iter_mut() {
-            let margin = COLLISION_PAIR_VEL_MARGIN_FACTOR * vel.0.length();
-            let half_extents = Vec2::splat(circle.radius + margin);
+            let new_aabb = Aabb::moving(pos.0, vel.0, Vec2::splat(circle.radius));
 
-            aabb.min = pos.0 - half_extents;
-            aabb.max = pos.0 + half_extents;
+            // resting bodies keep their aabb unchanged, so static ones don't trigger bvh rebuilds
+            if *aabb != new_aabb {
+                *aabb = new_aabb;
+            }
         }
     }
 
-    fn update_aabb_box(mut query: Query<(&mut Aabb, &Pos, &Vel, &BoxCollider)>) {
+    #[allow(clippy::type_complexity)]
+    fn update_aabb_box(
+        mut query: Query<(&mut Aabb, &Pos, &Vel, &BoxCollider), Without<CompoundCollider>>,
+    ) {
         for (mut aabb, pos, vel, box_) in query.iter_mut() {
-            let margin = COLLISION_PAIR_VEL_MARGIN_FACTOR * vel.0.length();
-            let half_extents = box_.size / 2. + Vec2::splat(margin);
+            let new_aabb = Aabb::moving(pos.0, vel.0, box_.size / 2.);
 
-            aabb.min = pos.0 - half_extents;
-            aabb.max = pos.0 + half_extents;
+            if *aabb != new_aabb {
+                *aabb = new_aabb;
+            }
         }
     }
 
-    // TODO: optimize with hash grids
-    fn collect_collision_pairs(
-        query: Query<(Entity, &Aabb)>,
-        mut collision_pairs: ResMut<CollisionPairs>,
-    ) {
-        collision_pairs.0.clear();
+    fn update_aabb_compound(mut query: Query<(&mut Aabb, &Pos, &Vel, &CompoundCollider)>) {
+        for (mut aabb, pos, vel, compound) in query.iter_mut() {
+            let (min, max) = compound.local_bounds();
+            let new_aabb = Aabb::moving(pos.0 + (min + max) / 2., vel.0, (max - min) / 2.);
 
-        let mut iter = query.iter_combinations();
+            if *aabb != new_aabb {
+                *aabb = new_aabb;
+            }
+        }
+    }
 
-        while let Some([(entity_a, aabb_a), (entity_b, aabb_b)]) = iter.fetch_next() {
-            if aabb_a.intersects(aabb_b) {
-                collision_pairs.0.push((entity_a, entity_b));
+    #[allow(clippy::type_complexity)]
+    fn update_aabb_terrain<C: Component + TerrainCollider>(
+        mut query: Query<(&mut Aabb, &Pos, &C), Or<(Changed<Pos>, Changed<C>)>>,
+    ) {
+        for (mut aabb, pos, terrain) in query.iter_mut() {
+            let (min, max) = terrain.local_bounds();
+            let new_aabb = Aabb {
+                min: pos.0 + min,
+                max: pos.0 + max,
+            };
+
+            if *aabb != new_aabb {
+                *aabb = new_aabb;
             }
         }
     }
 
+    // removals are only visible until the start of `CoreStage::Last`, so they are recorded before
+    // the physics and again at the end of the frame, for bodies removed after the physics ran
+    fn record_removed_bodies(
+        removed_rigid_bodies: RemovedComponents<RigidBody>,
+        removed_aabbs: RemovedComponents<Aabb>,
+        mut removed_bodies: ResMut<RemovedBodies>,
+    ) {
+        removed_bodies
+            .0
+            .extend(removed_rigid_bodies.iter().chain(removed_aabbs.iter()));
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_static_bvh(
+        query: Query<(Entity, &Aabb, &RigidBody)>,
+        changed: Query<(Entity, &RigidBody), Or<(Changed<Aabb>, Changed<RigidBody>)>>,
+        mut removed_bodies: ResMut<RemovedBodies>,
+        mut static_bvh: ResMut<StaticBvh>,
+    ) {
+        // drained whether or not anything else changed, so old removals don't rebuild it later
+        let is_removed = removed_bodies
+            .0
+            .drain()
+            .any(|entity| static_bvh.entities.contains(&entity));
+        let is_dirty = is_removed
+            || changed.iter().any(|(entity, rigid_body)| {
+                rigid_body.is_static() || static_bvh.entities.contains(&entity)
+            });
+
+        if !is_dirty {
+            return;
+        }
+
+        let statics: Vec<_> = query
+            .iter()
+            .filter(|(_, _, rigid_body)| rigid_body.is_static())
+            .map(|(entity, aabb, _)| (entity, *aabb))
+            .collect();
+
+        static_bvh.entities = statics.iter().map(|(entity, _)| *entity).collect();
+        static_bvh.bvh = Bvh::build(statics);
+    }
+
+    // TODO: optimize dynamic pairs with hash grids
+    #[allow(clippy::type_complexity)]
+    fn collect_collision_pairs(
+        query: Query<(
+            Entity,
+            &Aabb,
+            &RigidBody,
+            Option<&CollisionLayers>,
+            Option<&FluidParticle>,
+            Option<&SoftBodyParticle>,
+        )>,
+        static_bvh: Res<StaticBvh>,
+        mut collision_pairs: ResMut<CollisionPairs>,
+        mut dynamics: Local<Vec<BroadPhaseBody<Entity, ParticleGroup>>>,
+    ) {
+        dynamics.clear();
+        dynamics.extend(
+            query
+                .iter()
+                .filter(|(_, _, rigid_body, _, _, _)| !rigid_body.is_static())
+                .map(
+                    |(entity, aabb, _, layers, fluid, soft_body)| BroadPhaseBody {
+                        key: entity,
+                        aabb: *aabb,
+                        layers: layers.copied().unwrap_or_default(),
+                        // particles of a group are kept apart by the group's own constraint instead
+                        group: match (fluid, soft_body) {
+                            (Some(_), _) => Some(ParticleGroup::Fluid),
+                            (_, Some(particle)) => Some(ParticleGroup::SoftBody(particle.body)),
+                            _ => None,
+                        },
+                    },
+                ),
+        );
+
+        collect_collision_pairs(
+            &dynamics,
+            &static_bvh.bvh,
+            |entity| {
+                query
+                    .get(entity)
+                    .ok()
+                    .and_then(|(_, _, _, layers, _, _)| layers.copied())
+                    .unwrap_or_default()
+            },
+            &mut collision_pairs.0,
+        );
+    }
+
+    #[allow(clippy::type_complexity)]
     fn integrate(
-        mut query: Query<(&mut Pos, &mut PrevPos, &mut Vel, &mut PreSolveVel, &Mass)>,
+        mut query: Query<
+            (
+                &RigidBody,
+                &mut Pos,
+                &mut PrevPos,
+                &mut Vel,
+                &mut PreSolveVel,
+                &Mass,
+                &InvMass,
+                Option<&CollisionLayers>,
+                Option<&LockedAxes>,
+                Option<&MaxLinearSpeed>,
+            ),
+            Without<ForceField>,
+        >,
+        force_fields: Query<(&Pos, &ForceField, Option<&CollisionLayers>)>,
         gravity: Res<Gravity>,
     ) {
-        for (mut pos, mut prev_pos, mut vel, mut pre_solve_vel, mass) in query.iter_mut() {
+        for (
+            rigid_body,
+            mut pos,
+            mut prev_pos,
+            mut vel,
+            mut pre_solve_vel,
+            mass,
+            inv_mass,
+            layers,
+            locked_axes,
+            max_linear_speed,
+        ) in query.iter_mut()
+        {
             prev_pos.0 = pos.0;
 
-            let gravitation_force = mass.0 * gravity.0;
-            let external_forces = gravitation_force;
+            if rigid_body.is_static() {
+                vel.0 = Vec2::ZERO;
+                pre_solve_vel.0 = Vec2::ZERO;
+                continue;
+            }
+
+            let external_forces =
+                external_force(gravity.0, force_fields.iter(), pos.0, vel.0, mass.0, layers);
 
-            vel.0 += SUB_DT * external_forces / mass.0;
-            pos.0 += SUB_DT * vel.0;
+            integrate(&mut pos.0, &mut vel.0, external_forces, inv_mass.0);
+            limit_motion(
+                &mut pos.0,
+                prev_pos.0,
+                &mut vel.0,
+                locked_axes.copied().unwrap_or_default(),
+                max_linear_speed.copied().unwrap_or_default(),
+            );
             pre_solve_vel.0 = vel.0;
         }
     }
 
-    fn clear_contacs(mut contacts: ResMut<Contacts>, mut static_contacts: ResMut<StaticContacts>) {
+    // characters move once per step, against the bodies where they are at its start
+    fn move_characters(
+        spatial_query: SpatialQuery,
+        mut characters: Query<(
+            &mut CharacterController,
+            &mut CharacterContacts,
+            &mut Pos,
+            Option<&CollisionLayers>,
+        )>,
+    ) {
+        for (mut controller, mut contacts, mut pos, layers) in characters.iter_mut() {
+            pos.0 = move_character(
+                &spatial_query,
+                &controller,
+                layers.copied().unwrap_or_default(),
+                pos.0,
+                &mut contacts,
+            );
+            controller.displacement = Vec2::ZERO;
+        }
+    }
+
+    fn clear_contacs(mut contacts: ResMut<Contacts>) {
         contacts.0.clear();
-        static_contacts.0.clear();
     }
 
-    fn solve_pos(
-        mut query: Query<(&mut Pos, &CircleCollider, &Mass)>,
+    #[allow(clippy::type_complexity)]
+    fn collect_contacts(
+        query: Query<(
+            &Pos,
+            &PrevPos,
+            &InvMass,
+            Option<&CircleCollider>,
+            Option<&BoxCollider>,
+            Option<&CompoundCollider>,
+        )>,
         collision_pairs: Res<CollisionPairs>,
         mut contacts: ResMut<Contacts>,
     ) {
         for (entity_a, entity_b) in collision_pairs.0.iter().cloned() {
-            if let Ok([(mut pos_a, circle_a, mass_a), (mut pos_b, circle_b, mass_b)]) =
-                query.get_many_mut([entity_a, entity_b])
-            {
-                if let Some(Contact {
-                    penetration,
-                    normal,
-                }) = ball_ball(pos_a.0, circle_a.radius, pos_b.0, circle_b.radius)
-                {
-                    constrain_body_positions(
-                        &mut pos_a,
-                        &mut pos_b,
-                        mass_a,
-                        mass_b,
-                        normal,
-                        penetration,
+            let Ok(
+                [(pos_a, prev_pos_a, inv_mass_a, circle_a, box_a, compound_a), (pos_b, prev_pos_b, inv_mass_b, circle_b, box_b, compound_b)],
+            ) = query.get_many([entity_a, entity_b])
+            else {
+                continue;
+            };
+
+            with_body_parts(circle_a, box_a, compound_a, |parts_a| {
+                with_body_parts(circle_b, box_b, compound_b, |parts_b| {
+                    collect_contacts(
+                        &PlacedBody {
+                            key: entity_a,
+                            parts: parts_a,
+                            pos: pos_a.0,
+                            prev_pos: prev_pos_a.0,
+                            inv_mass: inv_mass_a.0,
+                        },
+                        &PlacedBody {
+                            key: entity_b,
+                            parts: parts_b,
+                            pos: pos_b.0,
+                            prev_pos: prev_pos_b.0,
+                            inv_mass: inv_mass_b.0,
+                        },
+                        &mut contacts.0,
                     );
+                })
+            });
+        }
+    }
 
-                    contacts.0.push((entity_a, entity_b, normal));
-                }
-            }
+    #[allow(clippy::type_complexity)]
+    fn collect_terrain_contacts<C: Component + TerrainCollider>(
+        bodies: Query<
+            (
+                &Pos,
+                &PrevPos,
+                &InvMass,
+                Option<&CircleCollider>,
+                Option<&BoxCollider>,
+                Option<&CompoundCollider>,
+            ),
+            Without<C>,
+        >,
+        terrains: Query<(&Pos, &C)>,
+        collision_pairs: Res<CollisionPairs>,
+        mut contacts: ResMut<Contacts>,
+        mut terrain_contacts: Local<Vec<Contact>>,
+    ) {
+        for (entity_a, entity_b) in collision_pairs.0.iter().cloned() {
+            let (body, terrain) = if terrains.contains(entity_b) {
+                (entity_a, entity_b)
+            } else if terrains.contains(entity_a) {
+                (entity_b, entity_a)
+            } else {
+                continue;
+            };
+
+            let Ok((pos, prev_pos, inv_mass, circle, box_, compound)) = bodies.get(body) else {
+                continue;
+            };
+            let (terrain_pos, collider) = terrains.get(terrain).unwrap();
+
+            with_body_parts(circle, box_, compound, |parts| {
+                collect_terrain_contacts(
+                    &PlacedBody {
+                        key: body,
+                        parts,
+                        pos: pos.0,
+                        prev_pos: prev_pos.0,
+                        inv_mass: inv_mass.0,
+                    },
+                    terrain,
+                    terrain_pos.0,
+                    collider,
+                    &mut terrain_contacts,
+                    &mut contacts.0,
+                )
+            });
         }
     }
 
-    fn solve_pos_box_box(
-        mut query: Query<(&mut Pos, &BoxCollider, &Mass)>,
+    fn apply_one_way(
+        one_ways: Query<&OneWay>,
+        bodies: Query<(&Pos, Option<&PrevPos>)>,
         mut contacts: ResMut<Contacts>,
-        collision_paris: Res<CollisionPairs>,
     ) {
-        for (entity_a, entity_b) in collision_paris.0.iter().cloned() {
-            if let Ok([(mut pos_a, box_a, mass_a), (mut pos_b, box_b, mass_b)]) =
-                query.get_many_mut([entity_a, entity_b])
-            {
-                if let Some(Contact {
-                    normal,
-                    penetration,
-                }) = box_box(pos_a.0, box_a.size, pos_b.0, box_b.size)
-                {
-                    constrain_body_positions(
-                        &mut pos_a,
-                        &mut pos_b,
-                        mass_a,
-                        mass_b,
-                        normal,
-                        penetration,
-                    );
+        let moved = |entity| {
+            bodies.get(entity).map_or(Vec2::ZERO, |(pos, prev_pos)| {
+                prev_pos.map_or(Vec2::ZERO, |prev_pos| pos.0 - prev_pos.0)
+            })
+        };
 
-                    contacts.0.push((entity_a, entity_b, normal));
-                }
+        contacts.0.retain_mut(|(entity_a, entity_b, contact)| {
+            // normal from the other body into the one-way body
+            let (body, one_way, normal, one_way_entity) =
+                if let Ok(one_way) = one_ways.get(*entity_b) {
+                    (*entity_a, one_way, contact.normal, *entity_b)
+                } else if let Ok(one_way) = one_ways.get(*entity_a) {
+                    (*entity_b, one_way, -contact.normal, *entity_a)
+                } else {
+                    return true;
+                };
+
+            if -normal.dot(one_way.normal.normalize_or_zero()) < ONE_WAY_MIN_ALIGNMENT {
+                return false;
             }
+
+            // moving back along the substep tells how deep the body was at its start.
+            // Bodies leaving through the far side are let go instead of being pushed back out
+            let relative_move = (moved(body) - moved(one_way_entity)).dot(normal);
+            let prev_penetration = contact.penetration - relative_move;
+
+            contact.one_sided = true;
+
+            prev_penetration <= ONE_WAY_SLOP && relative_move >= 0.
+        });
+    }
+
+    fn apply_surface_velocity(surfaces: Query<&SurfaceVelocity>, mut contacts: ResMut<Contacts>) {
+        if surfaces.is_empty() {
+            return;
+        }
+
+        let surface_vel = |entity| surfaces.get(entity).map_or(Vec2::ZERO, |vel| vel.0);
+
+        for (entity_a, entity_b, contact) in contacts.0.iter_mut() {
+            contact.surface_vel += surface_vel(*entity_b) - surface_vel(*entity_a);
+        }
+    }
+
+    // contacts resolved by earlier ones are kept, for the velocity pass and `CollidingEntities`
+    fn solve_contacts(
+        mut query: PositionQuery,
+        mut contacts: ResMut<Contacts>,
+        settings: Res<SolverSettings>,
+        mut solver: Local<Solver<Entity>>,
+    ) {
+        solver.solve_positions(&settings, &mut contacts.0, &mut PositionBodies(&mut query));
+    }
+
+    fn solve_distance_constraints(
+        constraints: Query<&DistanceConstraint>,
+        mut bodies: Query<(&mut Pos, &InvMass, Option<&LockedAxes>)>,
+    ) {
+        for constraint in constraints.iter() {
+            let Ok(
+                [(mut pos_a, inv_mass_a, locked_axes_a), (mut pos_b, inv_mass_b, locked_axes_b)],
+            ) = bodies.get_many_mut([constraint.entity_a, constraint.entity_b])
+            else {
+                continue;
+            };
+
+            constrain_distance(
+                &mut pos_a.0,
+                &mut pos_b.0,
+                axis_inv_mass(inv_mass_a, locked_axes_a),
+                axis_inv_mass(inv_mass_b, locked_axes_b),
+                constraint.rest_length,
+                constraint.compliance,
+            );
         }
     }
 
-    fn sol_pos_statics(
-        mut dynamics: Query<(Entity, &mut Pos, &CircleCollider), With<Mass>>,
-        statics: Query<(Entity, &Pos, &CircleCollider), Without<Mass>>,
-        mut contacts: ResMut<StaticContacts>,
+    fn solve_fluid_density(
+        mut particles: Query<(&mut Pos, &Mass, &InvMass), With<FluidParticle>>,
+        settings: Res<FluidSettings>,
+        mut solver: Local<FluidSolver>,
+        mut positions: Local<Vec<Vec2>>,
+        mut masses: Local<Vec<f32>>,
+        mut inv_masses: Local<Vec<f32>>,
     ) {
-        for (entity_a, mut pos_a, circle_a) in dynamics.iter_mut() {
-            for (entity_b, pos_b, circle_b) in statics.iter() {
-                if let Some(Contact {
-                    penetration,
-                    normal,
-                }) = ball_ball(pos_a.0, circle_a.radius, pos_b.0, circle_b.radius)
-                {
-                    constrain_body_position(&mut pos_a, normal, penetration);
-
-                    contacts.0.push((entity_a, entity_b, normal));
-                }
-            }
+        positions.clear();
+        masses.clear();
+        inv_masses.clear();
+
+        for (pos, mass, inv_mass) in particles.iter() {
+            positions.push(pos.0);
+            masses.push(mass.0);
+            inv_masses.push(inv_mass.0);
+        }
+
+        solver.solve_density(&mut positions, &masses, &inv_masses, &settings);
+
+        for ((mut pos, _, _), solved) in particles.iter_mut().zip(positions.iter()) {
+            pos.0 = *solved;
         }
     }
 
-    fn solve_pos_static_boxes(
-        mut dynamics: Query<(Entity, &mut Pos, &CircleCollider), With<Mass>>,
-        statics: Query<(Entity, &Pos, &BoxCollider), Without<Mass>>,
-        mut contacts: ResMut<StaticContacts>,
+    fn solve_soft_bodies(
+        soft_bodies: Query<&SoftBody>,
+        mut particles: Query<(&mut Pos, &Mass, &InvMass), With<SoftBodyParticle>>,
+        mut positions: Local<Vec<Vec2>>,
+        mut masses: Local<Vec<f32>>,
+        mut inv_masses: Local<Vec<f32>>,
     ) {
-        for (entity_a, mut pos_a, circle_a) in dynamics.iter_mut() {
-            for (entity_b, pos_b, box_b) in statics.iter() {
-                if let Some(Contact {
-                    normal,
-                    penetration,
-                }) = ball_box(pos_a.0, circle_a.radius, pos_b.0, box_b.size)
-                {
-                    constrain_body_position(&mut pos_a, normal, penetration);
-
-                    contacts.0.push((entity_a, entity_b, normal));
-                }
+        for soft_body in soft_bodies.iter() {
+            positions.clear();
+            masses.clear();
+            inv_masses.clear();
+
+            for (pos, mass, inv_mass) in particles.iter_many(&soft_body.particles) {
+                positions.push(pos.0);
+                masses.push(mass.0);
+                inv_masses.push(inv_mass.0);
+            }
+
+            // despawned particles would mismatch the rest offsets
+            if positions.len() != soft_body.rest_offsets.len() {
+                continue;
+            }
+
+            shape_match(
+                &soft_body.rest_offsets,
+                &mut positions,
+                &masses,
+                &inv_masses,
+                soft_body.substep_stiffness(),
+            );
+
+            let mut iter = particles.iter_many_mut(&soft_body.particles);
+            let mut index = 0;
+
+            while let Some((mut pos, _, _)) = iter.fetch_next() {
+                pos.0 = positions[index];
+                index += 1;
             }
         }
     }
 
-    fn solve_pos_static_box_box(
-        mut dynamics: Query<(Entity, &mut Pos, &BoxCollider), With<Mass>>,
-        statics: Query<(Entity, &Pos, &BoxCollider), Without<Mass>>,
-        mut contacts: ResMut<StaticContacts>,
+    fn solve_fluid_velocities(
+        mut particles: Query<(&Pos, &mut Vel, &Mass), With<FluidParticle>>,
+        settings: Res<FluidSettings>,
+        mut solver: Local<FluidSolver>,
+        mut positions: Local<Vec<Vec2>>,
+        mut vels: Local<Vec<Vec2>>,
+        mut masses: Local<Vec<f32>>,
     ) {
-        for (entity_a, mut pos_a, box_a) in dynamics.iter_mut() {
-            for (entity_b, pos_b, box_b) in statics.iter() {
-                if let Some(Contact {
-                    normal,
-                    penetration,
-                }) = box_box(pos_a.0, box_a.size, pos_b.0, box_b.size)
-                {
-                    constrain_body_position(&mut pos_a, normal, penetration);
-
-                    contacts.0.push((entity_a, entity_b, normal));
-                }
+        positions.clear();
+        vels.clear();
+        masses.clear();
+
+        for (pos, vel, mass) in particles.iter() {
+            positions.push(pos.0);
+            vels.push(vel.0);
+            masses.push(mass.0);
+        }
+
+        solver.solve_velocities(&positions, &mut vels, &masses, &settings);
+
+        for ((_, mut vel, _), solved) in particles.iter_mut().zip(vels.iter()) {
+            vel.0 = *solved;
+        }
+    }
+
+    fn clear_colliding_entities(mut query: Query<&mut CollidingEntities>) {
+        for mut colliding_entities in query.iter_mut() {
+            colliding_entities.0.clear();
+        }
+    }
+
+    // collects the contacts of every substep, so touches resolved within a single substep are kept too
+    fn update_colliding_entities(
+        mut query: Query<&mut CollidingEntities>,
+        contacts: Res<Contacts>,
+    ) {
+        for (entity_a, entity_b, contact) in contacts.0.iter() {
+            if let Ok(mut colliding_entities) = query.get_mut(*entity_a) {
+                colliding_entities.insert(*entity_b, contact.normal);
+            }
+
+            if let Ok(mut colliding_entities) = query.get_mut(*entity_b) {
+                colliding_entities.insert(*entity_a, -contact.normal);
             }
         }
     }
 
-    fn update_vel(mut query: Query<(&Pos, &PrevPos, &mut Vel, &Mass)>) {
-        for (pos, prev_pos, mut vel, _mass) in query.iter_mut() {
-            vel.0 = (pos.0 - prev_pos.0) / SUB_DT;
+    #[allow(clippy::type_complexity)]
+    fn update_vel(
+        mut query: Query<(
+            &mut Pos,
+            &PrevPos,
+            &mut Vel,
+            Option<&LockedAxes>,
+            Option<&MaxLinearSpeed>,
+        )>,
+    ) {
+        for (mut pos, prev_pos, mut vel, locked_axes, max_linear_speed) in query.iter_mut() {
+            vel.0 = derive_vel(pos.0, prev_pos.0);
+
+            // only limited bodies are written to, so the others keep their change ticks
+            if locked_axes.is_some() || max_linear_speed.is_some() {
+                limit_motion(
+                    &mut pos.0,
+                    prev_pos.0,
+                    &mut vel.0,
+                    locked_axes.copied().unwrap_or_default(),
+                    max_linear_speed.copied().unwrap_or_default(),
+                );
+            }
         }
     }
 
     fn solve_vel(
-        mut query: Query<(&mut Vel, &PreSolveVel, &Mass, &Restitution)>,
+        mut query: VelocityQuery,
         contacts: Res<Contacts>,
+        restitution_threshold: Res<RestitutionThreshold>,
     ) {
-        for (entity_a, entity_b, n) in contacts.0.iter().cloned() {
-            let [(mut vel_a, pre_solve_vel_a, mass_a, restitution_a), (mut vel_b, pre_solve_vel_b, mass_b, restitution_b)] =
-                query.get_many_mut([entity_a, entity_b]).unwrap();
+        solve_velocities(
+            &contacts.0,
+            restitution_threshold.0,
+            &mut VelocityBodies(&mut query),
+        );
+    }
 
-            let pre_solve_relative_vel = pre_solve_vel_a.0 - pre_solve_vel_b.0;
-            let pre_solve_normal_vel = Vec2::dot(pre_solve_relative_vel, n);
+    // bounces and friction may push limited bodies along a locked axis or past their speed again
+    #[allow(clippy::type_complexity)]
+    fn limit_vel(
+        mut query: Query<
+            (&mut Vel, Option<&LockedAxes>, Option<&MaxLinearSpeed>),
+            Or<(With<LockedAxes>, With<MaxLinearSpeed>)>,
+        >,
+    ) {
+        for (mut vel, locked_axes, max_linear_speed) in query.iter_mut() {
+            let limited = limited_vel(
+                vel.0,
+                locked_axes.copied().unwrap_or_default(),
+                max_linear_speed.copied().unwrap_or_default(),
+            );
 
-            let relative_vel = vel_a.0 - vel_b.0;
-            let normal_vel = Vec2::dot(relative_vel, n);
-            let restitution = (restitution_a.0 + restitution_b.0) / 2.;
+            if limited != vel.0 {
+                vel.0 = limited;
+            }
+        }
+    }
+
+    // bodies are simulated in world space, so parented ones get the translation that puts them
+    // at their position under their parent. `GlobalTransform`s are only propagated after the physics,
+    // so the parents are placed from their own `Pos` and `Transform` instead
+    fn sync_transforms(mut query: SyncQuery, mut translations: Local<Vec<(Entity, Vec3)>>) {
+        translations.clear();
+        translations.extend(
+            query
+                .iter()
+                .filter_map(|(entity, transform, pos, parent, _)| {
+                    let pos = pos?;
+                    let translation = match parent {
+                        Some(parent) => {
+                            let affine = world_affine(&query, parent.get());
+                            let z = affine.transform_point3(transform.translation).z;
+
+                            affine.inverse().transform_point3(pos.0.extend(z))
+                        }
+                        None => pos.0.extend(transform.translation.z),
+                    };
+
+                    Some((entity, translation))
+                }),
+        );
+
+        for (entity, translation) in translations.iter().copied() {
+            let Ok((_, mut transform, _, _, synced)) = query.get_mut(entity) else {
+                continue;
+            };
 
-            let w_a = 1. / mass_a.0;
-            let w_b = 1. / mass_b.0;
-            let w_sum = w_a + w_b;
+            if transform.translation != translation {
+                transform.translation = translation;
+            }
 
-            vel_a.0 += n * (-normal_vel - restitution * pre_solve_normal_vel) * w_a / w_sum;
-            vel_b.0 -= n * (-normal_vel - restitution * pre_solve_normal_vel) * w_b / w_sum;
+            if let Some(mut synced) = synced.filter(|synced| synced.0 != translation) {
+                synced.0 = translation;
+            }
         }
     }
 
-    fn solve_vel_static(
-        mut dynamics: Query<(&mut Vel, &PreSolveVel, &Restitution), With<Mass>>,
-        statics: Query<&Restitution, Without<Mass>>,
-        contacts: Res<StaticContacts>,
+    // transforms edited outside of the physics, e.g. in an inspector, teleport their body.
+    // Transforms added with the body are ignored, the body position is the one it spawns at.
+    // The physics writes transforms every step, those still holding what it wrote weren't edited
+    // and may lag behind a `Pos` set since
+    #[allow(clippy::type_complexity)]
+    fn teleport_moved_bodies(
+        mut query: Query<
+            (
+                ChangeTrackers<Transform>,
+                &Transform,
+                Option<&Parent>,
+                Option<&SyncedTranslation>,
+                &mut Pos,
+                &mut PrevPos,
+            ),
+            Changed<Transform>,
+        >,
+        parents: Query<&GlobalTransform>,
     ) {
-        for (entity_a, entity_b, n) in contacts.0.iter().cloned() {
-            let (mut vel_a, pre_solve_vel_a, restituin_a) = dynamics.get_mut(entity_a).unwrap();
-            let restituin_b = statics.get(entity_b).unwrap();
+        for (tracker, transform, parent, synced, mut pos, mut prev_pos) in query.iter_mut() {
+            if tracker.is_added() || synced.is_some_and(|synced| synced.0 == transform.translation)
+            {
+                continue;
+            }
+
+            let translation = match parent.and_then(|parent| parents.get(parent.get()).ok()) {
+                Some(parent) => parent.transform_point(transform.translation),
+                None => transform.translation,
+            };
+            let new_pos = translation.truncate();
 
-            let pre_solve_normal_vel = Vec2::dot(pre_solve_vel_a.0, n);
-            let normal_vel = Vec2::dot(vel_a.0, n);
-            let restitution = (restituin_a.0 + restituin_b.0) / 2.;
+            if new_pos.abs_diff_eq(pos.0, TELEPORT_EPSILON) {
+                continue;
+            }
 
-            vel_a.0 += n * (-normal_vel - restitution * pre_solve_normal_vel);
+            pos.0 = new_pos;
+            prev_pos.0 = new_pos;
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        consts::NUM_SUBSTEPS,
+        testing::{run_steps, spawn_box, spawn_ground, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn dynamic_box_rests_on_static_box() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.));
+        let body = spawn_box(&mut app, Vec2::ZERO, Vec2::ZERO, Vec2::ONE).id();
+
+        run_steps(&mut app, 120);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+
+        assert!((pos.y - -4.5).abs() < 0.1, "{pos:?}");
+    }
+
+    #[test]
+    fn plugin_matches_core_world() {
+        use xpbd_core::world::{Body, Collider, World};
+
+        let ground = (Vec2::new(0., -10.), Vec2::new(100., 10.));
+        let boxes = [
+            (Vec2::new(0., -4.), Vec2::ZERO),
+            (Vec2::new(0.3, -2.5), Vec2::ZERO),
+            (Vec2::new(-5., 3.), Vec2::new(4., 0.)),
+        ];
+        let ball = (Vec2::new(2., 5.), Vec2::new(-1., -3.), 0.5);
 
-    fn sync_transforms(mut query: Query<(&mut Transform, &Pos)>) {
-        for (mut transform, pos) in query.iter_mut() {
-            transform.translation = pos.0.extend(0.);
+        let mut app = test_app();
+        let mut world = World::default();
+
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(ground.0),
+            collider: BoxCollider { size: ground.1 },
+            ..default()
+        });
+        world.add_body(Body::fixed(
+            ground.0,
+            Collider::Box(BoxCollider { size: ground.1 }),
+        ));
+
+        let mut bodies = Vec::new();
+
+        for (pos, vel) in boxes {
+            let entity = app
+                .world
+                .spawn(DynamicBoxBundle {
+                    collider: BoxCollider { size: Vec2::ONE },
+                    ..DynamicBoxBundle::new_with_pos_and_vel(pos, vel)
+                })
+                .id();
+            let handle = world.add_body(
+                Body::dynamic(pos, Collider::Box(BoxCollider { size: Vec2::ONE })).with_vel(vel),
+            );
+
+            bodies.push((entity, handle));
+        }
+
+        let entity = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: ball.2 },
+                ..ParticleBundle::new_with_pos_and_vel(ball.0, ball.1)
+            })
+            .id();
+        let handle = world.add_body(
+            Body::dynamic(ball.0, Collider::Circle(CircleCollider { radius: ball.2 }))
+                .with_vel(ball.1),
+        );
+
+        bodies.push((entity, handle));
+
+        for _ in 0..90 {
+            run_steps(&mut app, 1);
+            world.step();
+        }
+
+        for (entity, handle) in bodies {
+            let pos = app.world.get::<Pos>(entity).unwrap().0;
+            let vel = app.world.get::<Vel>(entity).unwrap().0;
+            let body = world.body(handle);
+
+            assert!(pos.abs_diff_eq(body.pos, 1e-4), "{pos:?} {:?}", body.pos);
+            assert!(vel.abs_diff_eq(body.vel, 1e-3), "{vel:?} {:?}", body.vel);
         }
     }
-}
 
-fn constrain_body_positions(
-    pos_a: &mut Pos,
-    pos_b: &mut Pos,
-    mass_a: &Mass,
-    mass_b: &Mass,
-    normal: Vec2,
-    penetration: f32,
-) {
-    let w_a = 1. / mass_a.0;
-    let w_b = 1. / mass_b.0;
-    let w_sum = w_a + w_b;
-    let pos_impulse = normal * (-penetration / w_sum);
-
-    pos_a.0 += pos_impulse * w_a;
-    pos_b.0 -= pos_impulse * w_b;
-}
+    // terrain, constraints, surface coefficients, softness and motion limits, in both solver modes
+    fn plugin_matches_core_world_with_terrain_and_constraints(mode: SolverMode) {
+        use xpbd_core::world::{
+            Body, Collider, DistanceConstraint as WorldDistanceConstraint, World,
+        };
+
+        let mut app = test_app();
+        let mut world = World::default();
+        let soft = ContactSoftness {
+            compliance: 1e-4,
+            damping: 10.,
+        };
+
+        app.world.resource_mut::<SolverSettings>().mode = mode;
+        world.solver.mode = mode;
+
+        let ramp = ChainCollider {
+            points: vec![Vec2::new(-20., 10.), Vec2::new(0., 0.), Vec2::new(20., 0.)],
+            one_sided: false,
+        };
+        let tiles = TileMapCollider::new(Vec2::splat(2.), 3, &[true, false, true]);
+
+        app.world
+            .spawn(StaticTerrainBundle::new(Vec2::ZERO, ramp.clone()))
+            .insert(soft);
+        app.world
+            .spawn(StaticTerrainBundle::new(Vec2::new(8., 0.), tiles.clone()));
+
+        let mut ramp = Body::fixed(Vec2::ZERO, Collider::Chain(ramp));
+
+        ramp.contact_softness = soft;
+        world.add_body(ramp);
+        world.add_body(Body::fixed(Vec2::new(8., 0.), Collider::TileMap(tiles)));
+
+        let mut bodies = Vec::new();
+
+        // slides down the soft ramp with friction
+        let pos = Vec2::new(-15., 9.);
+        let entity = app
+            .world
+            .spawn(DynamicBoxBundle {
+                collider: BoxCollider { size: Vec2::ONE },
+                friction: Friction(0.3),
+                ..DynamicBoxBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+            })
+            .insert(FrictionCombine(CombineRule::Max))
+            .id();
+        let mut body = Body::dynamic(pos, Collider::Box(BoxCollider { size: Vec2::ONE }));
 
-fn constrain_body_position(pos: &mut Pos, normal: Vec2, penetration: f32) {
-    pos.0 -= normal * penetration;
+        body.friction = 0.3;
+        body.friction_combine = CombineRule::Max;
+        bodies.push((entity, world.add_body(body)));
+
+        // a pendulum of two balls falling onto the tile map
+        let mut pendulum = Vec::new();
+
+        for (pos, vel) in [
+            (Vec2::new(8., 6.), Vec2::ZERO),
+            (Vec2::new(10., 6.), Vec2::new(0., 3.)),
+        ] {
+            let entity = app
+                .world
+                .spawn(ParticleBundle {
+                    collider: CircleCollider { radius: 0.5 },
+                    restitution: Restitution(0.8),
+                    ..ParticleBundle::new_with_pos_and_vel(pos, vel)
+                })
+                .insert(RestitutionCombine(CombineRule::Max))
+                .id();
+            let mut body =
+                Body::dynamic(pos, Collider::Circle(CircleCollider { radius: 0.5 })).with_vel(vel);
+
+            body.restitution = 0.8;
+            body.restitution_combine = CombineRule::Max;
+            pendulum.push((entity, world.add_body(body)));
+        }
+
+        app.world
+            .spawn(DistanceConstraint::new(pendulum[0].0, pendulum[1].0, 2.).with_compliance(1e-3));
+        world.add_distance_constraint(WorldDistanceConstraint {
+            body_a: pendulum[0].1,
+            body_b: pendulum[1].1,
+            rest_length: 2.,
+            compliance: 1e-3,
+        });
+        bodies.extend(pendulum);
+
+        // an L-shaped crate built from a child part
+        let pos = Vec2::new(15., 5.);
+        let entity = app
+            .world
+            .spawn(DynamicBoxBundle {
+                collider: BoxCollider { size: Vec2::ONE },
+                ..DynamicBoxBundle::new_with_pos_and_vel(pos, Vec2::ZERO)
+            })
+            .insert(Density(1.))
+            .with_children(|parent| {
+                parent.spawn((
+                    ChildCollider {
+                        offset: Vec2::new(0.5, -1.),
+                    },
+                    BoxCollider {
+                        size: Vec2::new(2., 1.),
+                    },
+                ));
+            })
+            .id();
+        let crate_parts = vec![
+            (Vec2::ZERO, BodyShape::Box { size: Vec2::ONE }),
+            (
+                Vec2::new(0.5, -1.),
+                BodyShape::Box {
+                    size: Vec2::new(2., 1.),
+                },
+            ),
+        ];
+
+        bodies.push((
+            entity,
+            world.add_body(
+                Body::dynamic(pos, Collider::Compound(CompoundCollider::new(crate_parts)))
+                    .with_density(1.),
+            ),
+        ));
+
+        // thrown sideways along a rail, and capped while falling
+        let (pos, vel) = (Vec2::new(-5., 12.), Vec2::new(6., 0.));
+        let entity = app
+            .world
+            .spawn(DynamicBoxBundle {
+                collider: BoxCollider { size: Vec2::ONE },
+                ..DynamicBoxBundle::new_with_pos_and_vel(pos, vel)
+            })
+            .insert((
+                LockedAxes::default().lock_translation_x(),
+                MaxLinearSpeed(4.),
+            ))
+            .id();
+        let mut body =
+            Body::dynamic(pos, Collider::Box(BoxCollider { size: Vec2::ONE })).with_vel(vel);
+
+        body.locked_axes = LockedAxes::default().lock_translation_x();
+        body.max_linear_speed = MaxLinearSpeed(4.);
+        bodies.push((entity, world.add_body(body)));
+
+        for _ in 0..240 {
+            run_steps(&mut app, 1);
+            world.step();
+        }
+
+        for (entity, handle) in bodies {
+            let pos = app.world.get::<Pos>(entity).unwrap().0;
+            let vel = app.world.get::<Vel>(entity).unwrap().0;
+            let body = world.body(handle);
+
+            assert!(pos.abs_diff_eq(body.pos, 1e-4), "{pos:?} {:?}", body.pos);
+            assert!(vel.abs_diff_eq(body.vel, 1e-3), "{vel:?} {:?}", body.vel);
+        }
+    }
+
+    #[test]
+    fn plugin_matches_core_world_with_terrain_and_constraints_gauss_seidel() {
+        plugin_matches_core_world_with_terrain_and_constraints(SolverMode::GaussSeidel);
+    }
+
+    #[test]
+    fn plugin_matches_core_world_with_terrain_and_constraints_jacobi() {
+        plugin_matches_core_world_with_terrain_and_constraints(SolverMode::Jacobi);
+    }
+
+    #[test]
+    fn parented_body_is_synced_in_world_space() {
+        let mut app = test_app();
+
+        app.add_plugin(TransformPlugin);
+        app.world.resource_mut::<Gravity>().0 = Vec2::ZERO;
+
+        let body = app
+            .world
+            .spawn((
+                ParticleBundle::new_with_pos_and_vel(Vec2::new(1., 2.), Vec2::ZERO),
+                TransformBundle::default(),
+            ))
+            .id();
+        app.world
+            .spawn(TransformBundle::from_transform(
+                Transform::from_xyz(10., 5., 0.).with_scale(Vec3::splat(2.)),
+            ))
+            .push_children(&[body]);
+
+        run_steps(&mut app, 10);
+
+        let translation = app
+            .world
+            .get::<GlobalTransform>(body)
+            .unwrap()
+            .translation();
+
+        assert_eq!(app.world.get::<Pos>(body).unwrap().0, Vec2::new(1., 2.));
+        assert!(
+            translation.truncate().abs_diff_eq(Vec2::new(1., 2.), 1e-4),
+            "{translation:?}"
+        );
+    }
+
+    #[test]
+    fn body_parented_to_a_moving_body_is_synced_in_world_space() {
+        let mut app = test_app();
+
+        app.add_plugin(TransformPlugin);
+        app.world.resource_mut::<Gravity>().0 = Vec2::ZERO;
+
+        let child = app
+            .world
+            .spawn((
+                ParticleBundle {
+                    collider: CircleCollider { radius: 0.5 },
+                    ..ParticleBundle::new_with_pos_and_vel(Vec2::new(0., 3.), Vec2::ZERO)
+                },
+                TransformBundle::default(),
+            ))
+            .id();
+        app.world
+            .spawn((
+                ParticleBundle {
+                    collider: CircleCollider { radius: 0.5 },
+                    ..ParticleBundle::new_with_pos_and_vel(Vec2::new(-2., 0.), Vec2::new(5., 0.))
+                },
+                TransformBundle::default(),
+            ))
+            .push_children(&[child]);
+
+        run_steps(&mut app, 10);
+
+        // placed under where the parent is after the step, not where it was propagated to the frame before
+        let translation = app
+            .world
+            .get::<GlobalTransform>(child)
+            .unwrap()
+            .translation();
+
+        assert!(
+            translation.truncate().abs_diff_eq(Vec2::new(0., 3.), 1e-4),
+            "{translation:?}"
+        );
+    }
+
+    #[test]
+    fn edited_transform_teleports_body() {
+        let mut app = test_app();
+
+        app.add_plugin(TransformPlugin);
+
+        let body = app
+            .world
+            .spawn((
+                ParticleBundle::new_with_pos_and_vel(Vec2::ZERO, Vec2::ZERO),
+                TransformBundle::default(),
+            ))
+            .id();
+
+        run_steps(&mut app, 30);
+
+        let vel = app.world.get::<Vel>(body).unwrap().0;
+
+        app.world.get_mut::<Transform>(body).unwrap().translation = Vec3::new(5., 20., 0.);
+        run_steps(&mut app, 1);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+
+        // the body keeps falling from where it was put, without picking up speed from the jump
+        assert_eq!(pos.x, 5.);
+        assert!(pos.y < 20. && pos.y > 19.5, "{pos:?}");
+        assert!(app.world.get::<Vel>(body).unwrap().0.y < vel.y);
+        assert_eq!(
+            app.world.get::<Transform>(body).unwrap().translation,
+            pos.extend(0.)
+        );
+    }
+
+    #[test]
+    fn pos_set_in_update_is_kept() {
+        #[derive(Resource, Default)]
+        struct MoveTo(Option<Vec2>);
+
+        let mut app = test_app();
+
+        app.add_plugin(TransformPlugin)
+            .init_resource::<MoveTo>()
+            .add_system(
+                |mut move_to: ResMut<MoveTo>, mut query: Query<(&mut Pos, &mut PrevPos)>| {
+                    if let Some(new_pos) = move_to.0.take() {
+                        for (mut pos, mut prev_pos) in query.iter_mut() {
+                            pos.0 = new_pos;
+                            prev_pos.0 = new_pos;
+                        }
+                    }
+                },
+            );
+
+        let body = app
+            .world
+            .spawn((
+                ParticleBundle::new_with_pos_and_vel(Vec2::ZERO, Vec2::ZERO),
+                TransformBundle::default(),
+            ))
+            .id();
+
+        run_steps(&mut app, 30);
+
+        // the physics runs before `Update`, so the transform it wrote in the same frame
+        // still holds the old position at the start of the next one
+        app.world.resource_mut::<MoveTo>().0 = Some(Vec2::new(5., 20.));
+        run_steps(&mut app, 2);
+
+        let pos = app.world.get::<Pos>(body).unwrap().0;
+
+        assert_eq!(pos.x, 5.);
+        assert!(pos.y < 20. && pos.y > 19.5, "{pos:?}");
+    }
+
+    #[test]
+    fn body_switched_to_static_stops_moving() {
+        let mut app = test_app();
+
+        let body = app
+            .world
+            .spawn(ParticleBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::new(10., 0.),
+            ))
+            .id();
+
+        run_steps(&mut app, 10);
+
+        *app.world.get_mut::<RigidBody>(body).unwrap() = RigidBody::Static;
+        let frozen_pos = app.world.get::<Pos>(body).unwrap().0;
+
+        run_steps(&mut app, 10);
+
+        assert_eq!(app.world.get::<Pos>(body).unwrap().0, frozen_pos);
+        assert_eq!(app.world.get::<InvMass>(body).unwrap().0, 0.);
+
+        *app.world.get_mut::<RigidBody>(body).unwrap() = RigidBody::Dynamic;
+
+        run_steps(&mut app, 10);
+
+        assert!(app.world.get::<Pos>(body).unwrap().0.y < frozen_pos.y);
+    }
+
+    #[test]
+    fn box_stack_stays_at_rest() {
+        let mut app = test_app();
+        let size = Vec2::splat(20.);
+
+        spawn_ground(&mut app, Vec2::new(0., -size.y), Vec2::new(500., size.y));
+        let boxes: Vec<_> = (0..10)
+            .map(|i| {
+                let pos = Vec2::new((i % 2) as f32 * 2., i as f32 * size.y);
+
+                spawn_box(&mut app, pos, Vec2::ZERO, size)
+                    .insert(Density(1.))
+                    .id()
+            })
+            .collect();
+
+        // 10 seconds
+        run_steps(&mut app, 600);
+
+        for (i, entity) in boxes.into_iter().enumerate() {
+            let pos = app.world.get::<Pos>(entity).unwrap().0;
+            let expected = Vec2::new((i % 2) as f32 * 2., i as f32 * size.y);
+
+            assert!(pos.abs_diff_eq(expected, 0.5), "box {i} moved to {pos:?}");
+            assert!(app.world.get::<Vel>(entity).unwrap().0.length() < 0.1);
+        }
+    }
+
+    #[test]
+    fn physics_components_are_reflected() {
+        let mut app = test_app();
+
+        let body = app
+            .world
+            .spawn(DynamicBoxBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::ZERO,
+            ))
+            .id();
+
+        let registry = app.world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        let reflect_mass = registry
+            .get_with_name(std::any::type_name::<Mass>())
+            .and_then(|registration| registration.data::<ReflectComponent>())
+            .unwrap();
+
+        reflect_mass.apply(&mut app.world, body, &Mass(5.));
+        run_steps(&mut app, 1);
+
+        assert_eq!(app.world.get::<Mass>(body).unwrap().0, 5.);
+        assert_eq!(app.world.get::<InvMass>(body).unwrap().0, 0.2);
+        assert!(registry
+            .get_with_name(std::any::type_name::<BoxCollider>())
+            .is_some());
+        assert!(registry
+            .get_with_name(std::any::type_name::<CollidingEntities>())
+            .and_then(|registration| registration.data::<ReflectComponent>())
+            .is_some());
+        assert!(registry
+            .get_with_name(std::any::type_name::<CompoundCollider>())
+            .and_then(|registration| registration.data::<ReflectComponent>())
+            .is_some());
+        assert!(registry
+            .get_with_name(std::any::type_name::<Gravity>())
+            .and_then(|registration| registration.data::<ReflectResource>())
+            .is_some());
+    }
+
+    #[test]
+    fn physics_sets_run_in_order() {
+        #[derive(Resource, Default)]
+        struct Log(Vec<PhysicsSet>);
+
+        let mut app = test_app();
+
+        app.init_resource::<Log>();
+
+        for set in [
+            PhysicsSet::PostPhysics,
+            PhysicsSet::PostSolve,
+            PhysicsSet::Substep,
+            PhysicsSet::PrePhysics,
+        ] {
+            app.add_physics_system(set, move |mut log: ResMut<Log>| log.0.push(set));
+        }
+
+        run_steps(&mut app, 1);
+
+        let substeps = (0..NUM_SUBSTEPS).flat_map(|_| [PhysicsSet::Substep, PhysicsSet::PostSolve]);
+        let expected: Vec<_> = [PhysicsSet::PrePhysics]
+            .into_iter()
+            .chain(substeps)
+            .chain([PhysicsSet::PostPhysics])
+            .collect();
+
+        assert_eq!(app.world.resource::<Log>().0, expected);
+    }
+
+    #[test]
+    fn contacts_removed_by_user_systems_are_not_solved() {
+        #[derive(Component)]
+        struct Ghost;
+
+        let mut app = test_app();
+
+        app.add_physics_system(
+            PhysicsSet::ModifyContacts,
+            |ghosts: Query<(), With<Ghost>>, mut contacts: ResMut<Contacts>| {
+                contacts
+                    .0
+                    .retain(|(a, b, _)| !ghosts.contains(*a) && !ghosts.contains(*b));
+            },
+        );
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -10.)),
+            collider: BoxCollider {
+                size: Vec2::new(100., 10.),
+            },
+            ..default()
+        });
+        let ball = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: 1. },
+                ..ParticleBundle::new_with_pos_and_vel(Vec2::new(-10., 0.), Vec2::ZERO)
+            })
+            .id();
+        let ghost = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: 1. },
+                ..ParticleBundle::new_with_pos_and_vel(Vec2::new(10., 0.), Vec2::ZERO)
+            })
+            .insert(Ghost)
+            .id();
+
+        run_steps(&mut app, 200);
+
+        assert!(app.world.get::<Pos>(ball).unwrap().0.y > -5.5);
+        assert!(app.world.get::<Pos>(ghost).unwrap().0.y < -20.);
+    }
 }
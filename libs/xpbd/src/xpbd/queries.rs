@@ -0,0 +1,310 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{
+    character::CharacterController,
+    colliders::{with_body_parts, BoxCollider, CircleCollider, CompoundCollider},
+    components::{Aabb, CollisionLayers, Pos, RigidBody},
+    contact::Contact,
+    resources::StaticBvh,
+    terrain::{
+        BodyShape, ChainCollider, ConvexPart, SegmentCollider, TerrainCollider, TileMapCollider,
+    },
+};
+
+// how much deeper than at the start of a cast a shape must be to count as a hit
+const CAST_PENETRATION_EPSILON: f32 = 1e-3;
+
+/// First collider hit by a shape cast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeHit {
+    pub entity: Entity,
+    /// Fraction of the cast travelled before touching the collider, from 0 to 1
+    pub fraction: f32,
+    /// Contact normal pointing from the cast shape towards the collider
+    pub normal: Vec2,
+}
+
+/// First collider hit by a ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Fraction of the ray travelled before hitting the collider, from 0 to 1
+    pub fraction: f32,
+    /// Surface normal at the hit, facing the ray
+    pub normal: Vec2,
+}
+
+/// Overlap tests and shape casts against the colliders of all bodies.
+/// Kinematic characters are not part of the queries
+#[derive(SystemParam)]
+pub struct SpatialQuery<'w, 's> {
+    #[allow(clippy::type_complexity)]
+    colliders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Pos,
+            &'static Aabb,
+            &'static RigidBody,
+            Option<&'static CollisionLayers>,
+            Option<&'static CircleCollider>,
+            Option<&'static BoxCollider>,
+            Option<&'static CompoundCollider>,
+            Option<&'static SegmentCollider>,
+            Option<&'static ChainCollider>,
+            Option<&'static TileMapCollider>,
+        ),
+        Without<CharacterController>,
+    >,
+    static_bvh: Res<'w, StaticBvh>,
+}
+
+impl<'w, 's> SpatialQuery<'w, 's> {
+    /// Calls `on_candidate` for every collider on interacting layers whose aabb intersects `aabb`
+    pub fn aabb_intersections(
+        &self,
+        aabb: &Aabb,
+        layers: CollisionLayers,
+        mut on_candidate: impl FnMut(Entity),
+    ) {
+        let interacts = |other: Option<&CollisionLayers>| {
+            layers.interacts_with(&other.copied().unwrap_or_default())
+        };
+
+        self.static_bvh.bvh.query(aabb, |entity| {
+            if let Ok((_, _, _, _, other_layers, ..)) = self.colliders.get(entity) {
+                if interacts(other_layers) {
+                    on_candidate(entity);
+                }
+            }
+        });
+
+        for (entity, _, other_aabb, rigid_body, other_layers, ..) in self.colliders.iter() {
+            if !rigid_body.is_static() && other_aabb.intersects(aabb) && interacts(other_layers) {
+                on_candidate(entity);
+            }
+        }
+    }
+
+    pub fn is_static(&self, entity: Entity) -> bool {
+        self.static_bvh.entities.contains(&entity)
+    }
+
+    /// Deepest contact between `shape` placed at `pos` and the collider of `entity`,
+    /// with the normal pointing from the shape towards the collider
+    pub fn contact(&self, shape: BodyShape, pos: Vec2, entity: Entity) -> Option<Contact> {
+        let (_, other_pos, _, _, _, circle, r#box, compound, segment, chain, tile_map) =
+            self.colliders.get(entity).ok()?;
+        let other_pos = other_pos.0;
+
+        if let Some(deepest) = with_body_parts(circle, r#box, compound, |parts| {
+            parts
+                .iter()
+                .filter_map(|(offset, part)| shape.contact(pos, part, other_pos + *offset))
+                .max_by(|a, b| a.penetration.total_cmp(&b.penetration))
+        }) {
+            return deepest;
+        }
+
+        let terrain: &dyn TerrainCollider = match (segment, chain, tile_map) {
+            (Some(segment), ..) => segment,
+            (_, Some(chain), _) => chain,
+            (.., Some(tile_map)) => tile_map,
+            _ => return None,
+        };
+        let mut deepest: Option<Contact> = None;
+
+        terrain.contacts(other_pos, pos, pos, shape, &mut |contact| {
+            if deepest.is_none_or(|deepest| contact.penetration > deepest.penetration) {
+                deepest = Some(contact);
+            }
+        });
+
+        deepest
+    }
+
+    /// Calls `on_contact` for every collider overlapping `shape` placed at `pos`
+    pub fn shape_intersections(
+        &self,
+        shape: BodyShape,
+        pos: Vec2,
+        layers: CollisionLayers,
+        mut on_contact: impl FnMut(Entity, Contact),
+    ) {
+        let aabb = Aabb::from_center(pos, shape.half_extents());
+
+        self.aabb_intersections(&aabb, layers, |entity| {
+            if let Some(contact) = self.contact(shape, pos, entity) {
+                on_contact(entity, contact);
+            }
+        });
+    }
+
+    /// Moves `shape` from `origin` by `delta` and returns the first collider it runs into.
+    /// Colliders the shape already overlaps at `origin` only stop it when it moves deeper into them
+    pub fn shape_cast(
+        &self,
+        shape: BodyShape,
+        origin: Vec2,
+        delta: Vec2,
+        layers: CollisionLayers,
+    ) -> Option<ShapeHit> {
+        if delta.length() <= f32::EPSILON {
+            return None;
+        }
+
+        let half_extents = shape.half_extents();
+        let swept = Aabb::from_center(origin, half_extents)
+            .union(&Aabb::from_center(origin + delta, half_extents));
+        let mut closest: Option<ShapeHit> = None;
+
+        self.aabb_intersections(&swept, layers, |entity| {
+            let Ok((_, pos, _, _, _, circle, r#box, compound, segment, chain, tile_map)) =
+                self.colliders.get(entity)
+            else {
+                return;
+            };
+            let depth = self
+                .contact(shape, origin, entity)
+                .map_or(0., |contact| contact.penetration)
+                + CAST_PENETRATION_EPSILON;
+            // each part is cast against on its own, so thin ones aren't stepped over
+            let mut on_part = |part: ConvexPart| {
+                let Some((fraction, normal)) = shape.cast(origin, delta, &part, depth) else {
+                    return;
+                };
+
+                if closest.is_none_or(|closest| fraction < closest.fraction) {
+                    closest = Some(ShapeHit {
+                        entity,
+                        fraction,
+                        normal,
+                    });
+                }
+            };
+
+            if with_body_parts(circle, r#box, compound, |parts| {
+                for (offset, part) in parts {
+                    on_part(ConvexPart::Shape {
+                        shape: *part,
+                        pos: pos.0 + *offset,
+                    });
+                }
+            })
+            .is_some()
+            {
+                return;
+            }
+
+            let terrain: &dyn TerrainCollider = match (segment, chain, tile_map) {
+                (Some(segment), ..) => segment,
+                (_, Some(chain), _) => chain,
+                (.., Some(tile_map)) => tile_map,
+                _ => return,
+            };
+
+            terrain.parts(pos.0, swept.min, swept.max, &mut on_part);
+        });
+
+        closest
+    }
+
+    /// Casts a ray from `origin` along `delta` and returns the first collider it hits
+    /// among the ones for which `predicate` holds
+    pub fn cast_ray(
+        &self,
+        origin: Vec2,
+        delta: Vec2,
+        layers: CollisionLayers,
+        predicate: impl Fn(Entity) -> bool,
+    ) -> Option<RayHit> {
+        let aabb = Aabb {
+            min: origin.min(origin + delta),
+            max: origin.max(origin + delta),
+        };
+        let mut closest: Option<RayHit> = None;
+
+        self.aabb_intersections(&aabb, layers, |entity| {
+            if !predicate(entity) {
+                return;
+            }
+
+            let Ok((_, pos, _, _, _, circle, r#box, compound, segment, chain, tile_map)) =
+                self.colliders.get(entity)
+            else {
+                return;
+            };
+            let hit = with_body_parts(circle, r#box, compound, |parts| {
+                parts
+                    .iter()
+                    .filter_map(|(offset, part)| part.cast_ray(pos.0 + *offset, origin, delta))
+                    .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            })
+            .unwrap_or_else(|| match (segment, chain, tile_map) {
+                (Some(segment), ..) => segment.cast_ray(pos.0, origin, delta),
+                (_, Some(chain), _) => chain.cast_ray(pos.0, origin, delta),
+                (.., Some(tile_map)) => tile_map.cast_ray(pos.0, origin, delta),
+                _ => None,
+            });
+
+            if let Some((fraction, normal)) = hit {
+                if closest.is_none_or(|closest| fraction < closest.fraction) {
+                    closest = Some(RayHit {
+                        entity,
+                        fraction,
+                        normal,
+                    });
+                }
+            }
+        });
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use crate::xpbd::{
+        components::StaticTerrainBundle,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn long_shape_casts_hit_thin_segments() {
+        let mut app = test_app();
+
+        let wall = app
+            .world
+            .spawn(StaticTerrainBundle::new(
+                Vec2::new(503., 0.),
+                SegmentCollider {
+                    a: Vec2::new(0., -1.),
+                    b: Vec2::new(0., 1.),
+                    one_sided: false,
+                },
+            ))
+            .id();
+
+        run_steps(&mut app, 1);
+
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let spatial_query = state.get(&app.world);
+        let hit = spatial_query
+            .shape_cast(
+                BodyShape::Circle { radius: 0.5 },
+                Vec2::ZERO,
+                Vec2::new(1000., 0.),
+                CollisionLayers::default(),
+            )
+            .unwrap();
+
+        assert_eq!(hit.entity, wall);
+        assert!((hit.fraction * 1000. - 502.5).abs() < 0.01, "{hit:?}");
+        assert!(hit.normal.abs_diff_eq(Vec2::X, 1e-3), "{hit:?}");
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+pub use xpbd_core::recording::*;
+
+use super::{
+    colliders::{with_body_parts, BoxCollider, CircleCollider, CompoundCollider},
+    components::{CollisionLayers, Mass, Pos, RigidBody, Vel},
+    forces::{external_force, ForceField},
+    plugin::{FixedUpdateStage, PhysicsSet, Step},
+    resources::Gravity,
+    terrain::{BodyShape, ChainCollider, SegmentCollider, TileMapCollider},
+    xpdb_loop::{first_substep, last_substep},
+};
+
+/// Records every physics step to a file, to be replayed with `Recording::read`,
+/// see the `replay` example. Steps are written and flushed as they happen, so a crash loses at most the last one.
+/// Logs an error and records nothing when the file can't be created. Added after the `XpbdPlugin`
+pub struct PhysicsRecorderPlugin {
+    pub path: PathBuf,
+}
+
+impl PhysicsRecorderPlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Plugin for PhysicsRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        let recorder = PhysicsRecorder::create(&self.path).unwrap_or_else(|error| {
+            error!("can't record physics to {:?}: {error}", self.path);
+
+            PhysicsRecorder::default()
+        });
+
+        app.insert_resource(recorder)
+            .add_system_to_stage(
+                FixedUpdateStage,
+                PhysicsRecorderPlugin::record_step
+                    .with_run_criteria(first_substep)
+                    .after(PhysicsSet::PrePhysics)
+                    .after(Step::UpdateMassProperties)
+                    .before(Step::Integrate),
+            )
+            .add_system_to_stage(
+                FixedUpdateStage,
+                PhysicsRecorderPlugin::record_step_end
+                    .with_run_criteria(last_substep)
+                    .after(Step::SolveVelocities),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PhysicsRecorder {
+    writer: Option<BufWriter<File>>,
+    // bodies with the shape they were last recorded with
+    recorded: HashMap<Entity, RecordedBody>,
+    alive: HashSet<Entity>,
+    // velocities the physics left the bodies with at the end of the previous step
+    end_vels: HashMap<Entity, Vec2>,
+    step: RecordedStep,
+    /// Stops recording steps while `false`, bodies spawned or changed meanwhile are recorded once it resumes.
+    /// Set back to `false` when writing fails
+    pub enabled: bool,
+}
+
+impl PhysicsRecorder {
+    fn create(path: &PathBuf) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write_header(&mut writer)?;
+
+        Ok(Self {
+            writer: Some(writer),
+            enabled: true,
+            ..default()
+        })
+    }
+
+    fn write_step(&mut self) -> io::Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+
+        self.step.write(writer)?;
+        writer.flush()
+    }
+}
+
+impl PhysicsRecorderPlugin {
+    #[allow(clippy::type_complexity)]
+    fn record_step(
+        bodies: Query<(
+            Entity,
+            &RigidBody,
+            &Pos,
+            &Vel,
+            Option<&Mass>,
+            Option<&CollisionLayers>,
+            Option<&CircleCollider>,
+            Option<&BoxCollider>,
+            Option<&CompoundCollider>,
+            Option<&SegmentCollider>,
+            Option<&ChainCollider>,
+            Option<&TileMapCollider>,
+        )>,
+        changed: Query<
+            (),
+            Or<(
+                Changed<RigidBody>,
+                Changed<CircleCollider>,
+                Changed<BoxCollider>,
+                Changed<CompoundCollider>,
+                Changed<SegmentCollider>,
+                Changed<ChainCollider>,
+                Changed<TileMapCollider>,
+            )>,
+        >,
+        force_fields: Query<(&Pos, &ForceField, Option<&CollisionLayers>)>,
+        gravity: Res<Gravity>,
+        mut recorder: ResMut<PhysicsRecorder>,
+    ) {
+        if !recorder.enabled {
+            // changes made meanwhile go unnoticed, so every body is recorded again on resuming
+            recorder.recorded.clear();
+            recorder.end_vels.clear();
+            return;
+        }
+
+        let recorder = &mut *recorder;
+
+        recorder.alive.clear();
+
+        for (
+            entity,
+            rigid_body,
+            pos,
+            vel,
+            mass,
+            layers,
+            circle,
+            box_,
+            compound,
+            segment,
+            chain,
+            tile_map,
+        ) in bodies.iter()
+        {
+            recorder.alive.insert(entity);
+
+            if !recorder.recorded.contains_key(&entity) || changed.contains(entity) {
+                let mut body = RecordedBody {
+                    id: entity.to_bits(),
+                    is_static: rigid_body.is_static(),
+                    parts: with_body_parts(circle, box_, compound, |parts| parts.to_vec())
+                        .unwrap_or_default(),
+                    lines: Vec::new(),
+                };
+
+                if let Some(segment) = segment {
+                    body.lines.push((segment.a, segment.b));
+                }
+
+                if let Some(chain) = chain {
+                    body.lines
+                        .extend(chain.points.windows(2).map(|line| (line[0], line[1])));
+                }
+
+                if let Some(tile_map) = tile_map {
+                    body.parts.extend(tile_map.rects().iter().map(|rect| {
+                        let (center, size) = tile_map.rect_bounds(rect);
+
+                        (center, BodyShape::Box { size })
+                    }));
+                }
+
+                if recorder.recorded.get(&entity) != Some(&body) {
+                    recorder.step.spawned.push(body.clone());
+                    recorder.recorded.insert(entity, body);
+                }
+            }
+
+            let force = match (rigid_body.is_static(), mass) {
+                (false, Some(mass)) => {
+                    external_force(gravity.0, force_fields.iter(), pos.0, vel.0, mass.0, layers)
+                }
+                _ => Vec2::ZERO,
+            };
+            let end_vel = recorder.end_vels.get(&entity).copied().unwrap_or(vel.0);
+
+            recorder.step.states.push(BodyState {
+                id: entity.to_bits(),
+                pos: pos.0,
+                vel: vel.0,
+                force,
+                vel_change: vel.0 - end_vel,
+            });
+        }
+
+        let PhysicsRecorder {
+            recorded,
+            alive,
+            step,
+            ..
+        } = recorder;
+
+        recorded.retain(|entity, _| {
+            let is_alive = alive.contains(entity);
+
+            if !is_alive {
+                step.despawned.push(entity.to_bits());
+            }
+
+            is_alive
+        });
+
+        if let Err(error) = recorder.write_step() {
+            error!("failed to record physics step: {error}");
+            recorder.enabled = false;
+        }
+
+        recorder.step = RecordedStep::default();
+    }
+
+    fn record_step_end(
+        bodies: Query<(Entity, &Vel), With<RigidBody>>,
+        mut recorder: ResMut<PhysicsRecorder>,
+    ) {
+        if !recorder.enabled {
+            return;
+        }
+
+        recorder.end_vels.clear();
+        recorder
+            .end_vels
+            .extend(bodies.iter().map(|(entity, vel)| (entity, vel.0)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        colliders::CircleCollider,
+        components::*,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn recorder_writes_every_step() {
+        let path = std::env::temp_dir().join(format!("xpbd_recording_{}", std::process::id()));
+        let mut app = test_app();
+
+        app.add_plugin(PhysicsRecorderPlugin::new(&path));
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -10.)),
+            collider: BoxCollider {
+                size: Vec2::new(100., 10.),
+            },
+            ..default()
+        });
+        let ball = app
+            .world
+            .spawn(ParticleBundle {
+                collider: CircleCollider { radius: 0.5 },
+                ..ParticleBundle::new_with_pos_and_vel(Vec2::new(0., 5.), Vec2::ZERO)
+            })
+            .id();
+
+        run_steps(&mut app, 5);
+        // pushed and grown by the game between two steps
+        app.world.get_mut::<Vel>(ball).unwrap().0.x += 3.;
+        app.world.get_mut::<CircleCollider>(ball).unwrap().radius = 1.;
+        run_steps(&mut app, 5);
+        app.world.despawn(ball);
+        run_steps(&mut app, 1);
+
+        let recording = Recording::read(&mut std::fs::File::open(&path).unwrap()).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording.steps.len(), 11);
+        assert_eq!(recording.steps[0].spawned.len(), 2);
+        assert_eq!(recording.steps[5].spawned.len(), 1);
+        assert_eq!(recording.steps[10].despawned, [ball.to_bits()]);
+        assert_eq!(recording.steps[10].states.len(), 1);
+
+        let ball_at = |step| {
+            recording
+                .bodies_at(step)
+                .find(|(body, _)| body.id == ball.to_bits())
+                .unwrap()
+        };
+        let (body, state) = ball_at(4);
+
+        assert_eq!(
+            body.parts,
+            [(Vec2::ZERO, BodyShape::Circle { radius: 0.5 })]
+        );
+        assert!(state.pos.y < 5. && state.vel.y < 0., "{state:?}");
+        assert_eq!(state.force, Vec2::new(0., -9.81));
+        assert_eq!(state.vel_change, Vec2::ZERO);
+
+        let (body, state) = ball_at(5);
+
+        assert_eq!(body.parts, [(Vec2::ZERO, BodyShape::Circle { radius: 1. })]);
+        assert_eq!(state.vel_change, Vec2::new(3., 0.));
+    }
+}
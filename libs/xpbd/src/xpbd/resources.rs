@@ -1,6 +1,11 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 
-#[derive(Debug, Resource)]
+use super::{bvh::Bvh, contact::Contact};
+
+pub use xpbd_core::solver::{SolverMode, SolverSettings};
+
+#[derive(Debug, Resource, Reflect)]
+#[reflect(Resource)]
 pub struct Gravity(pub Vec2);
 
 impl Default for Gravity {
@@ -9,11 +14,219 @@ impl Default for Gravity {
     }
 }
 
-#[derive(Default, Debug, Resource)]
-pub struct Contacts(pub Vec<(Entity, Entity, Vec2)>);
+/// Relative normal speed below which contacts don't bounce, so resting bodies don't jitter
+#[derive(Debug, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct RestitutionThreshold(pub f32);
+
+impl Default for RestitutionThreshold {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
 
 #[derive(Default, Debug, Resource)]
-pub struct StaticContacts(pub Vec<(Entity, Entity, Vec2)>);
+pub struct Contacts(pub Vec<(Entity, Entity, Contact)>);
+
+/// Contacts with static bodies used to be kept apart, they are solved with the others now
+#[deprecated(note = "contacts with static bodies are part of `Contacts`")]
+pub type StaticContacts = Contacts;
 
 #[derive(Default, Debug, Resource)]
 pub struct CollisionPairs(pub Vec<(Entity, Entity)>);
+
+/// Bounding volume hierarchy of static bodies used by the broad phase, rebuilt only when they change
+#[derive(Default, Debug, Resource)]
+pub struct StaticBvh {
+    pub bvh: Bvh<Entity>,
+    pub(crate) entities: HashSet<Entity>,
+}
+
+/// Bodies that lost their `RigidBody` or `Aabb` since the static BVH was last updated,
+/// kept across frames without a physics step
+#[derive(Default, Debug, Resource)]
+pub(crate) struct RemovedBodies(pub(crate) HashSet<Entity>);
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::{
+        components::*,
+        terrain::TileMapCollider,
+        testing::{run_steps, spawn_ball, spawn_box, spawn_ground, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn resting_box_reports_both_ends_of_its_bottom_edge() {
+        let mut app = test_app();
+
+        spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.));
+        spawn_box(&mut app, Vec2::new(3., -4.), Vec2::ZERO, Vec2::new(4., 2.));
+
+        run_steps(&mut app, 60);
+
+        let contacts = &app.world.resource::<Contacts>().0;
+
+        assert_eq!(contacts.len(), 1);
+
+        let points = contacts[0].2.points();
+
+        assert_eq!(points.len(), 2);
+        assert!(
+            points[0].abs_diff_eq(Vec2::new(1., -5.), 0.01),
+            "{points:?}"
+        );
+        assert!(
+            points[1].abs_diff_eq(Vec2::new(5., -5.), 0.01),
+            "{points:?}"
+        );
+    }
+
+    // spawns a pyramid of balls between two walls, mirrored around x = 0, and returns
+    // how far apart its mirrored balls end up from being symmetric and its lowest resting height
+    fn ball_pyramid_asymmetry(mode: SolverMode) -> (f32, f32) {
+        let mut app = test_app();
+        let radius = 0.5;
+
+        app.world.resource_mut::<SolverSettings>().mode = mode;
+
+        for (pos, size) in [
+            (Vec2::new(0., -5.), Vec2::new(20., 10.)),
+            (Vec2::new(-6.5, 5.), Vec2::new(10., 10.)),
+            (Vec2::new(6.5, 5.), Vec2::new(10., 10.)),
+        ] {
+            spawn_ground(&mut app, pos, size);
+        }
+
+        let rows = [vec![-1., 0., 1.], vec![-0.5, 0.5], vec![0.]];
+        let balls: Vec<Vec<_>> = rows
+            .iter()
+            .enumerate()
+            .map(|(row, xs)| {
+                xs.iter()
+                    .map(|x| {
+                        let pos = Vec2::new(*x, radius + row as f32 * 0.9);
+
+                        spawn_ball(&mut app, pos, Vec2::ZERO, radius).id()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        run_steps(&mut app, 300);
+
+        let pos = |entity| app.world.get::<Pos>(entity).unwrap().0;
+        let asymmetry = balls
+            .iter()
+            .flat_map(|row| row.iter().zip(row.iter().rev()))
+            .map(|(a, b)| (pos(*a).x + pos(*b).x).abs() + (pos(*a).y - pos(*b).y).abs())
+            .fold(0., f32::max);
+        let lowest = balls
+            .iter()
+            .flatten()
+            .map(|ball| pos(*ball).y)
+            .fold(f32::MAX, f32::min);
+
+        (asymmetry, lowest)
+    }
+
+    #[test]
+    fn jacobi_solver_keeps_piles_symmetric() {
+        let (gauss_seidel, gauss_seidel_lowest) = ball_pyramid_asymmetry(SolverMode::GaussSeidel);
+        let (jacobi, jacobi_lowest) = ball_pyramid_asymmetry(SolverMode::Jacobi);
+
+        // gauss-seidel pushes the pile towards the side of the contacts solved last
+        assert!(gauss_seidel > 5. * jacobi, "{gauss_seidel} {jacobi}");
+        // what jacobi leaves is f32 rounding, mirrored balls summing their corrections in another order.
+        // It builds up over the 3000 substeps to around 6e-5, well below what gauss-seidel leans by
+        assert!(jacobi < 2e-4, "{jacobi}");
+        // both still hold the pile up
+        assert!(gauss_seidel_lowest > 0.45, "{gauss_seidel_lowest}");
+        assert!(jacobi_lowest > 0.45, "{jacobi_lowest}");
+    }
+
+    #[test]
+    fn static_bvh_tracks_static_bodies() {
+        let mut app = test_app();
+
+        let ground = spawn_ground(&mut app, Vec2::new(0., -10.), Vec2::new(100., 10.)).id();
+        let body = app
+            .world
+            .spawn(DynamicBoxBundle::new_with_pos_and_vel(
+                Vec2::ZERO,
+                Vec2::ZERO,
+            ))
+            .id();
+
+        run_steps(&mut app, 60);
+
+        assert_eq!(app.world.resource::<StaticBvh>().bvh.len(), 1);
+
+        // landed bodies can be frozen and become part of the level
+        *app.world.get_mut::<RigidBody>(body).unwrap() = RigidBody::Static;
+        run_steps(&mut app, 2);
+
+        assert_eq!(app.world.resource::<StaticBvh>().bvh.len(), 2);
+
+        // despawned in a frame without a physics step
+        app.world.despawn(ground);
+        app.update();
+        app.update();
+        run_steps(&mut app, 1);
+
+        assert_eq!(app.world.resource::<StaticBvh>().bvh.len(), 1);
+        assert!(!app.world.resource::<StaticBvh>().entities.contains(&ground));
+
+        // removed by a game system, after the physics of that frame
+        app.add_system(remove_marked_rigid_bodies);
+        app.world.entity_mut(body).insert(RemoveRigidBody);
+        app.update();
+        run_steps(&mut app, 1);
+
+        assert_eq!(app.world.resource::<StaticBvh>().bvh.len(), 0);
+    }
+
+    #[derive(Component)]
+    struct RemoveRigidBody;
+
+    fn remove_marked_rigid_bodies(
+        mut commands: Commands,
+        query: Query<Entity, With<RemoveRigidBody>>,
+    ) {
+        for entity in query.iter() {
+            commands.entity(entity).remove::<RigidBody>();
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct BvhRebuilds(u32);
+
+    fn count_bvh_rebuilds(static_bvh: Res<StaticBvh>, mut rebuilds: ResMut<BvhRebuilds>) {
+        if static_bvh.is_changed() {
+            rebuilds.0 += 1;
+        }
+    }
+
+    #[test]
+    fn static_bvh_is_kept_while_bodies_rest_on_terrain() {
+        let mut app = test_app();
+
+        app.init_resource::<BvhRebuilds>()
+            .add_system(count_bvh_rebuilds);
+        app.world.spawn(StaticTerrainBundle::new(
+            Vec2::new(-50., -10.),
+            TileMapCollider::new(Vec2::splat(10.), 10, &[true; 10]),
+        ));
+        app.world.spawn(DynamicBoxBundle::new_with_pos_and_vel(
+            Vec2::new(-1., 2.),
+            Vec2::ZERO,
+        ));
+
+        run_steps(&mut app, 60);
+        app.world.resource_mut::<BvhRebuilds>().0 = 0;
+        run_steps(&mut app, 30);
+
+        assert_eq!(app.world.resource::<BvhRebuilds>().0, 0);
+    }
+}
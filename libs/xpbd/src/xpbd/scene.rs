@@ -0,0 +1,467 @@
+use std::fmt;
+
+use bevy::{
+    asset::{AssetLoader, HandleId, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Deserializer};
+
+use super::{
+    colliders::{BoxCollider, CircleCollider},
+    components::*,
+    constraints::DistanceConstraint,
+    materials::PhysicsMaterial,
+    terrain::{ChainCollider, SegmentCollider, TileMapCollider},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SceneCollider {
+    Circle {
+        radius: f32,
+    },
+    Box {
+        size: Vec2,
+    },
+    Segment {
+        a: Vec2,
+        b: Vec2,
+        #[serde(default)]
+        one_sided: bool,
+    },
+    Chain {
+        points: Vec<Vec2>,
+        #[serde(default)]
+        one_sided: bool,
+    },
+    /// Rows from top to bottom, `#` marks a solid tile
+    TileMap {
+        tile_size: Vec2,
+        rows: Vec<String>,
+    },
+}
+
+impl SceneCollider {
+    /// Segments, chains and tile maps, which only exist as static bodies
+    pub fn is_terrain(&self) -> bool {
+        matches!(
+            self,
+            SceneCollider::Segment { .. }
+                | SceneCollider::Chain { .. }
+                | SceneCollider::TileMap { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SceneBody {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Static for terrain colliders and dynamic for the others when left out, see `SceneBody::rigid_body`
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub rigid_body: Option<RigidBody>,
+    #[serde(default)]
+    pub pos: Vec2,
+    #[serde(default)]
+    pub vel: Vec2,
+    pub collider: SceneCollider,
+    /// Used instead of the mass computed from the density of `material`
+    #[serde(default)]
+    pub mass: Option<f32>,
+    /// Name of an entry in `PhysicsScene::materials`
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+// lets `rigid_body: Static` be written without `Some`, as before it was optional
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl SceneBody {
+    pub fn rigid_body(&self) -> RigidBody {
+        self.rigid_body.unwrap_or(if self.collider.is_terrain() {
+            RigidBody::Static
+        } else {
+            RigidBody::Dynamic
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SceneConstraint {
+    /// Connects bodies by name, keeping their initial distance unless `rest_length` is given
+    Distance {
+        a: String,
+        b: String,
+        #[serde(default)]
+        rest_length: Option<f32>,
+        #[serde(default)]
+        compliance: f32,
+    },
+}
+
+/// Bodies, materials and constraints loaded from `*.physics.ron` files.
+/// Spawned by adding its handle to an entity, which replaces the spawned bodies whenever the asset is reloaded
+#[derive(Debug, Clone, Default, Deserialize, TypeUuid)]
+#[uuid = "65d40129-bf53-419d-800f-4c30e6c4a71c"]
+pub struct PhysicsScene {
+    #[serde(default)]
+    pub materials: HashMap<String, PhysicsMaterial>,
+    #[serde(default)]
+    pub bodies: Vec<SceneBody>,
+    #[serde(default)]
+    pub constraints: Vec<SceneConstraint>,
+}
+
+/// Body of a `PhysicsScene` with a terrain collider that is set to be dynamic
+#[derive(Debug)]
+pub struct DynamicTerrainError {
+    /// Index in `PhysicsScene::bodies`
+    pub body: usize,
+}
+
+impl fmt::Display for DynamicTerrainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "scene body {} is dynamic but has a terrain collider, which is always static",
+            self.body
+        )
+    }
+}
+
+impl std::error::Error for DynamicTerrainError {}
+
+/// Entities spawned from the `PhysicsScene` handle of the same entity
+#[derive(Component, Debug)]
+pub struct PhysicsSceneInstance {
+    pub scene: HandleId,
+    pub entities: Vec<Entity>,
+}
+
+impl PhysicsScene {
+    /// Checked when the scene is loaded, scenes built in code with dynamic terrain spawn it static
+    pub fn validate(&self) -> Result<(), DynamicTerrainError> {
+        match self
+            .bodies
+            .iter()
+            .position(|body| body.collider.is_terrain() && !body.rigid_body().is_static())
+        {
+            Some(body) => Err(DynamicTerrainError { body }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        physics_materials: &mut Assets<PhysicsMaterial>,
+    ) -> Vec<Entity> {
+        let materials: HashMap<_, _> = self
+            .materials
+            .iter()
+            .map(|(name, material)| (name, physics_materials.add(material.clone())))
+            .collect();
+        let mut entities = Vec::with_capacity(self.bodies.len() + self.constraints.len());
+        let mut named = HashMap::new();
+
+        for body in self.bodies.iter() {
+            let entity = spawn_body(commands, body);
+
+            // the density of the material would override it otherwise
+            if let Some(mass) = body.mass {
+                commands.entity(entity).insert((Mass(mass), ExplicitMass));
+            }
+
+            if let Some(material) = body.material.as_ref() {
+                match materials.get(material) {
+                    Some(handle) => {
+                        commands.entity(entity).insert(handle.clone());
+                    }
+                    None => warn!("unknown physics material {material}"),
+                }
+            }
+
+            if let Some(name) = body.name.as_ref() {
+                commands.entity(entity).insert(Name::new(name.clone()));
+                named.insert(name, (entity, body.pos));
+            }
+
+            entities.push(entity);
+        }
+
+        for constraint in self.constraints.iter() {
+            match constraint {
+                SceneConstraint::Distance {
+                    a,
+                    b,
+                    rest_length,
+                    compliance,
+                } => {
+                    let (Some((entity_a, pos_a)), Some((entity_b, pos_b))) =
+                        (named.get(a), named.get(b))
+                    else {
+                        warn!("distance constraint between unknown bodies {a} and {b}");
+                        continue;
+                    };
+                    let rest_length = rest_length.unwrap_or_else(|| pos_a.distance(*pos_b));
+
+                    entities.push(
+                        commands
+                            .spawn(
+                                DistanceConstraint::new(*entity_a, *entity_b, rest_length)
+                                    .with_compliance(*compliance),
+                            )
+                            .id(),
+                    );
+                }
+            }
+        }
+
+        entities
+    }
+}
+
+fn spawn_body(commands: &mut Commands, body: &SceneBody) -> Entity {
+    let transform =
+        TransformBundle::from_transform(Transform::from_translation(body.pos.extend(0.)));
+    let is_static = body.rigid_body().is_static();
+
+    if body.collider.is_terrain() && !is_static {
+        warn!("dynamic terrain isn't supported, spawning it static");
+    }
+
+    let mut entity = match body.collider.clone() {
+        SceneCollider::Circle { radius } if is_static => commands.spawn(StaticCircleBundle {
+            pos: Pos(body.pos),
+            collider: CircleCollider { radius },
+            ..default()
+        }),
+        SceneCollider::Circle { radius } => commands.spawn(ParticleBundle {
+            collider: CircleCollider { radius },
+            ..ParticleBundle::new_with_pos_and_vel(body.pos, body.vel)
+        }),
+        SceneCollider::Box { size } if is_static => commands.spawn(StaticBoxBundle {
+            pos: Pos(body.pos),
+            collider: BoxCollider { size },
+            ..default()
+        }),
+        SceneCollider::Box { size } => commands.spawn(DynamicBoxBundle {
+            collider: BoxCollider { size },
+            ..DynamicBoxBundle::new_with_pos_and_vel(body.pos, body.vel)
+        }),
+        SceneCollider::Segment { a, b, one_sided } => commands.spawn(StaticTerrainBundle::new(
+            body.pos,
+            SegmentCollider { a, b, one_sided },
+        )),
+        SceneCollider::Chain { points, one_sided } => commands.spawn(StaticTerrainBundle::new(
+            body.pos,
+            ChainCollider { points, one_sided },
+        )),
+        SceneCollider::TileMap { tile_size, rows } => {
+            let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+            let solid: Vec<_> = rows
+                .iter()
+                .rev()
+                .flat_map(|row| {
+                    (0..width).map(move |x| row.as_bytes().get(x).copied() == Some(b'#'))
+                })
+                .collect();
+
+            commands.spawn(StaticTerrainBundle::new(
+                body.pos,
+                TileMapCollider::new(tile_size, width as u32, &solid),
+            ))
+        }
+    };
+
+    entity.insert(transform);
+    entity.id()
+}
+
+#[derive(Default)]
+pub struct PhysicsSceneLoader;
+
+impl AssetLoader for PhysicsSceneLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let scene = ron::de::from_bytes::<PhysicsScene>(bytes)?;
+
+            scene.validate()?;
+
+            load_context.set_default_asset(LoadedAsset::new(scene));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["physics.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpbd::testing::{run_steps, test_app};
+
+    use super::*;
+
+    #[test]
+    fn parse_scene() {
+        let scene: PhysicsScene = ron::from_str(
+            r#"(
+                materials: {
+                    "rubber": (density: 2., restitution: 0.9, friction: 0.8, restitution_combine: Max),
+                    "cushion": (contact_softness: (compliance: 0.001)),
+                },
+                bodies: [
+                    (rigid_body: Static, pos: (0., -100.), collider: Box(size: (500., 20.))),
+                    (name: Some("a"), pos: (0., 10.), collider: Circle(radius: 5.), material: Some("rubber")),
+                    (name: Some("b"), pos: (0., 30.), vel: (1., 0.), collider: Circle(radius: 5.), mass: Some(3.)),
+                    (rigid_body: Static, collider: TileMap(tile_size: (10., 10.), rows: ["..#", ".##"])),
+                ],
+                constraints: [
+                    Distance(a: "a", b: "b", compliance: 0.001),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(scene.bodies.len(), 4);
+        assert_eq!(scene.constraints.len(), 1);
+        assert_eq!(scene.materials["rubber"].restitution, 0.9);
+        assert_eq!(
+            scene.materials["cushion"].contact_softness,
+            ContactSoftness {
+                compliance: 0.001,
+                damping: 0.,
+            }
+        );
+        assert_eq!(scene.bodies[0].rigid_body(), RigidBody::Static);
+        assert_eq!(scene.bodies[1].rigid_body(), RigidBody::Dynamic);
+        assert_eq!(scene.bodies[2].mass, Some(3.));
+    }
+
+    #[test]
+    fn terrain_is_static_unless_set_dynamic() {
+        let scene: PhysicsScene = ron::from_str(
+            r#"(
+                bodies: [
+                    (collider: Segment(a: (-10., 0.), b: (10., 0.))),
+                    (collider: TileMap(tile_size: (10., 10.), rows: [".#"])),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert!(scene
+            .bodies
+            .iter()
+            .all(|body| body.rigid_body() == RigidBody::Static));
+        assert!(scene.validate().is_ok());
+
+        let scene: PhysicsScene = ron::from_str(
+            r#"(
+                bodies: [
+                    (collider: Circle(radius: 1.)),
+                    (rigid_body: Dynamic, collider: Chain(points: [(0., 0.), (10., 0.)])),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(scene.validate().unwrap_err().body, 1);
+    }
+
+    #[test]
+    fn physics_scene_is_replaced_on_reload() {
+        let mut app = test_app();
+
+        let scene: PhysicsScene = ron::from_str(
+            r#"(
+                materials: { "bouncy": (restitution: 0.9) },
+                bodies: [
+                    (rigid_body: Static, pos: (0., -10.), collider: Box(size: (100., 10.))),
+                    (name: Some("ball"), collider: Circle(radius: 1.), material: Some("bouncy")),
+                ],
+            )"#,
+        )
+        .unwrap();
+        let handle = app.world.resource_mut::<Assets<PhysicsScene>>().add(scene);
+        let root = app.world.spawn(handle.clone()).id();
+
+        run_steps(&mut app, 2);
+
+        let spawned = app
+            .world
+            .get::<PhysicsSceneInstance>(root)
+            .unwrap()
+            .entities
+            .clone();
+
+        assert_eq!(spawned.len(), 2);
+        assert_eq!(app.world.get::<Restitution>(spawned[1]).unwrap().0, 0.9);
+
+        app.world
+            .resource_mut::<Assets<PhysicsScene>>()
+            .get_mut(&handle)
+            .unwrap()
+            .bodies
+            .truncate(1);
+
+        // asset events are sent at the end of the frame
+        run_steps(&mut app, 2);
+
+        let respawned = &app
+            .world
+            .get::<PhysicsSceneInstance>(root)
+            .unwrap()
+            .entities;
+
+        assert_eq!(respawned.len(), 1);
+        assert!(spawned
+            .iter()
+            .all(|entity| app.world.get_entity(*entity).is_none()));
+    }
+
+    #[test]
+    fn scene_mass_is_kept_for_bodies_with_a_material() {
+        let mut app = test_app();
+
+        let scene: PhysicsScene = ron::from_str(
+            r#"(
+                materials: { "heavy": (density: 10., restitution: 0.9) },
+                bodies: [
+                    (name: Some("light"), collider: Circle(radius: 1.), mass: Some(2.), material: Some("heavy")),
+                    (name: Some("heavy"), collider: Circle(radius: 1.), material: Some("heavy")),
+                ],
+            )"#,
+        )
+        .unwrap();
+        let handle = app.world.resource_mut::<Assets<PhysicsScene>>().add(scene);
+        let root = app.world.spawn(handle).id();
+
+        run_steps(&mut app, 2);
+
+        let spawned = &app
+            .world
+            .get::<PhysicsSceneInstance>(root)
+            .unwrap()
+            .entities;
+        let mass = |entity| app.world.get::<Mass>(entity).unwrap().0;
+
+        assert_eq!(mass(spawned[0]), 2.);
+        assert_eq!(app.world.get::<Restitution>(spawned[0]).unwrap().0, 0.9);
+        assert!((mass(spawned[1]) - 10. * std::f32::consts::PI).abs() < 1e-3);
+    }
+}
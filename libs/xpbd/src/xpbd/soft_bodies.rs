@@ -0,0 +1,347 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology, view::NoFrustumCulling},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashMap,
+};
+pub use xpbd_core::soft_bodies::shape_match;
+
+use super::{
+    colliders::CircleCollider,
+    components::{ParticleBundle, Pos},
+    consts::NUM_SUBSTEPS,
+};
+
+/// Renders soft bodies as meshes that deform with their particles,
+/// colored with the `Handle<ColorMaterial>` of the soft body entity, if any
+pub struct SoftBodyMeshPlugin;
+
+impl Plugin for SoftBodyMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, SoftBodyMeshPlugin::add_meshes)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                SoftBodyMeshPlugin::update_meshes.after(SoftBodyMeshPlugin::add_meshes),
+            );
+    }
+}
+
+impl SoftBodyMeshPlugin {
+    #[allow(clippy::type_complexity)]
+    fn add_meshes(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        query: Query<(Entity, &SoftBody, Option<&Handle<ColorMaterial>>), Added<SoftBody>>,
+    ) {
+        for (entity, soft_body, material) in query.iter() {
+            commands.entity(entity).insert((
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(soft_body.mesh())),
+                    material: material.cloned().unwrap_or_default(),
+                    ..default()
+                },
+                // the mesh bounds are computed once but the vertices move with the particles
+                NoFrustumCulling,
+            ));
+        }
+    }
+
+    // vertices follow the particles, which are in world space as the mesh has an identity transform
+    fn update_meshes(
+        mut meshes: ResMut<Assets<Mesh>>,
+        soft_bodies: Query<(&SoftBody, &Mesh2dHandle)>,
+        particles: Query<&Pos, With<SoftBodyParticle>>,
+    ) {
+        for (soft_body, mesh) in soft_bodies.iter() {
+            let Some(mesh) = meshes.get_mut(&mesh.0) else {
+                continue;
+            };
+            let positions: Vec<[f32; 3]> = particles
+                .iter_many(&soft_body.particles)
+                .map(|pos| pos.0.extend(0.).into())
+                .collect();
+
+            if positions.len() == soft_body.particles.len() {
+                mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+            }
+        }
+    }
+}
+
+/// Group of particles pulled back towards their rest shape, allowing it to bend, squash and rotate.
+/// Spawned with `SoftBody::spawn_circle` or `SoftBody::spawn_box`, and rendered with `SoftBodyMeshPlugin`
+#[derive(Component, Debug, Clone)]
+pub struct SoftBody {
+    pub particles: Vec<Entity>,
+    /// Rest positions of the particles relative to their center of mass
+    pub rest_offsets: Vec<Vec2>,
+    /// Indices into `particles`, three per triangle, covering the shape for rendering
+    pub triangles: Vec<[usize; 3]>,
+    /// Fraction of the way to the rest shape particles are moved every step, from 0 (liquid) to 1 (rigid)
+    pub stiffness: f32,
+}
+
+/// Member of a `SoftBody`. Particles of the same body don't collide with each other
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SoftBodyParticle {
+    pub body: Entity,
+}
+
+impl SoftBody {
+    /// Fills a circle with particles of `particle_radius` and returns the soft body entity
+    pub fn spawn_circle(
+        commands: &mut Commands,
+        center: Vec2,
+        radius: f32,
+        particle_radius: f32,
+        stiffness: f32,
+    ) -> Entity {
+        Self::spawn_filled(
+            commands,
+            center,
+            Vec2::splat(radius * 2.),
+            particle_radius,
+            stiffness,
+            |offset| offset.length() <= radius - particle_radius,
+        )
+    }
+
+    /// Fills a box with particles of `particle_radius` and returns the soft body entity
+    pub fn spawn_box(
+        commands: &mut Commands,
+        center: Vec2,
+        size: Vec2,
+        particle_radius: f32,
+        stiffness: f32,
+    ) -> Entity {
+        Self::spawn_filled(commands, center, size, particle_radius, stiffness, |_| true)
+    }
+
+    // places particles on a grid over `size`, keeping the ones for which `inside` holds,
+    // and triangulates the grid cells they cover
+    fn spawn_filled(
+        commands: &mut Commands,
+        center: Vec2,
+        size: Vec2,
+        particle_radius: f32,
+        stiffness: f32,
+        inside: impl Fn(Vec2) -> bool,
+    ) -> Entity {
+        let spacing = particle_radius * 2.;
+        let cells = ((size - spacing) / spacing)
+            .floor()
+            .as_ivec2()
+            .max(IVec2::ZERO);
+        let start = -cells.as_vec2() * spacing / 2.;
+        let body = commands.spawn_empty().id();
+        let mut indices = HashMap::new();
+        let mut particles = Vec::new();
+        let mut offsets = Vec::new();
+
+        for y in 0..=cells.y {
+            for x in 0..=cells.x {
+                let offset = start + IVec2::new(x, y).as_vec2() * spacing;
+
+                if !inside(offset) {
+                    continue;
+                }
+
+                let particle = commands
+                    .spawn(ParticleBundle {
+                        collider: CircleCollider {
+                            radius: particle_radius,
+                        },
+                        ..ParticleBundle::new_with_pos_and_vel(center + offset, Vec2::ZERO)
+                    })
+                    .insert(SoftBodyParticle { body })
+                    .id();
+
+                indices.insert(IVec2::new(x, y), particles.len());
+                particles.push(particle);
+                offsets.push(offset);
+            }
+        }
+
+        let mut triangles = Vec::new();
+
+        for y in 0..cells.y {
+            for x in 0..cells.x {
+                let corner = |dx, dy| indices.get(&IVec2::new(x + dx, y + dy)).copied();
+
+                match (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)) {
+                    (Some(a), Some(b), Some(c), Some(d)) => {
+                        triangles.push([a, b, c]);
+                        triangles.push([a, c, d]);
+                    }
+                    (Some(a), Some(b), Some(c), None)
+                    | (Some(a), Some(b), None, Some(c))
+                    | (Some(a), None, Some(b), Some(c))
+                    | (None, Some(a), Some(b), Some(c)) => triangles.push([a, b, c]),
+                    _ => {}
+                }
+            }
+        }
+
+        let center_of_mass = offsets.iter().sum::<Vec2>() / offsets.len().max(1) as f32;
+
+        commands.entity(body).insert(SoftBody {
+            particles,
+            rest_offsets: offsets
+                .into_iter()
+                .map(|offset| offset - center_of_mass)
+                .collect(),
+            triangles,
+            stiffness,
+        });
+
+        body
+    }
+
+    /// Mesh of `triangles` with a vertex per particle, placed by `SoftBodyMeshPlugin` every frame
+    pub fn mesh(&self) -> Mesh {
+        let vertex_count = self.particles.len();
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0., 0., 0.]; vertex_count]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; vertex_count]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; vertex_count]);
+        mesh.set_indices(Some(Indices::U32(
+            self.triangles
+                .iter()
+                .flatten()
+                .map(|index| *index as u32)
+                .collect(),
+        )));
+
+        mesh
+    }
+
+    /// Stiffness applied every substep, so that `stiffness` is reached over a whole step
+    pub fn substep_stiffness(&self) -> f32 {
+        1. - (1. - self.stiffness.clamp(0., 1.)).powf(1. / NUM_SUBSTEPS as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use crate::xpbd::{
+        colliders::BoxCollider,
+        components::*,
+        resources::Gravity,
+        testing::{run_steps, test_app},
+    };
+
+    use super::*;
+
+    #[test]
+    fn substep_stiffness_compounds_to_step_stiffness() {
+        let soft_body = SoftBody {
+            particles: Vec::new(),
+            rest_offsets: Vec::new(),
+            triangles: Vec::new(),
+            stiffness: 0.5,
+        };
+        let remaining = (1. - soft_body.substep_stiffness()).powi(NUM_SUBSTEPS as i32);
+
+        assert!((remaining - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn soft_box_keeps_its_shape_on_the_ground() {
+        let mut app = test_app();
+
+        app.insert_resource(Gravity(Vec2::new(0., -300.)));
+        app.world.spawn(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -10.)),
+            collider: BoxCollider {
+                size: Vec2::new(500., 20.),
+            },
+            ..default()
+        });
+
+        let mut commands_queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut commands_queue, &app.world);
+        let body = SoftBody::spawn_box(
+            &mut commands,
+            Vec2::new(0., 50.),
+            Vec2::new(40., 20.),
+            2.,
+            0.5,
+        );
+
+        commands_queue.apply(&mut app.world);
+
+        let soft_body = app.world.get::<SoftBody>(body).unwrap().clone();
+
+        assert_eq!(soft_body.particles.len(), 50);
+        assert_eq!(soft_body.triangles.len(), 2 * 9 * 4);
+
+        run_steps(&mut app, 180);
+
+        let positions: Vec<_> = soft_body
+            .particles
+            .iter()
+            .map(|entity| app.world.get::<Pos>(*entity).unwrap().0)
+            .collect();
+        let (min, max) = positions.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), pos| (min.min(*pos), max.max(*pos)),
+        );
+
+        // landed, slightly squashed but not collapsed
+        assert!((min.y - 2.).abs() < 0.5, "{min:?}");
+        assert!(
+            (max - min).abs_diff_eq(Vec2::new(36., 16.), 2.),
+            "{:?}",
+            max - min
+        );
+    }
+
+    #[test]
+    fn soft_body_mesh_follows_its_particles() {
+        let mut app = test_app();
+
+        app.add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>()
+            .add_plugin(SoftBodyMeshPlugin);
+
+        let material = app
+            .world
+            .resource_mut::<Assets<ColorMaterial>>()
+            .add(ColorMaterial::from(Color::WHITE));
+        let mut commands_queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut commands_queue, &app.world);
+        let body = SoftBody::spawn_circle(&mut commands, Vec2::ZERO, 10., 2., 0.5);
+
+        commands.entity(body).insert(material.clone());
+        commands_queue.apply(&mut app.world);
+        run_steps(&mut app, 10);
+
+        let soft_body = app.world.get::<SoftBody>(body).unwrap();
+        let mesh = app.world.get::<Mesh2dHandle>(body).unwrap();
+        let mesh = app.world.resource::<Assets<Mesh>>().get(&mesh.0).unwrap();
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("soft body mesh without positions");
+        };
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("soft body mesh without indices");
+        };
+
+        assert_eq!(
+            app.world.get::<Handle<ColorMaterial>>(body),
+            Some(&material)
+        );
+        assert_eq!(indices.len(), soft_body.triangles.len() * 3);
+        assert_eq!(positions.len(), soft_body.particles.len());
+
+        for (particle, position) in soft_body.particles.iter().zip(positions) {
+            let pos = app.world.get::<Pos>(*particle).unwrap().0;
+
+            assert_eq!(Vec2::new(position[0], position[1]), pos);
+        }
+    }
+}
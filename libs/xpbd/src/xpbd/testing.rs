@@ -0,0 +1,51 @@
+use bevy::{asset::AssetPlugin, ecs::world::EntityMut, prelude::*};
+
+use super::{
+    colliders::{BoxCollider, CircleCollider},
+    components::{DynamicBoxBundle, ParticleBundle, Pos, StaticBoxBundle},
+    plugin::XpbdPlugin,
+    xpdb_loop::XpbdLoop,
+};
+
+/// App with the physics and nothing else, stepped by hand with `run_steps`
+pub(crate) fn test_app() -> App {
+    let mut app = App::new();
+
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(XpbdPlugin);
+
+    app.world.resource_mut::<XpbdLoop>().paused = true;
+
+    app
+}
+
+pub(crate) fn run_steps(app: &mut App, steps: u32) {
+    for _ in 0..steps {
+        app.world.resource_mut::<XpbdLoop>()._step();
+        app.update();
+    }
+}
+
+/// Static box centered at `pos`, e.g. a ground or a wall
+pub(crate) fn spawn_ground(app: &mut App, pos: Vec2, size: Vec2) -> EntityMut<'_> {
+    app.world.spawn(StaticBoxBundle {
+        pos: Pos(pos),
+        collider: BoxCollider { size },
+        ..default()
+    })
+}
+
+pub(crate) fn spawn_ball(app: &mut App, pos: Vec2, vel: Vec2, radius: f32) -> EntityMut<'_> {
+    app.world.spawn(ParticleBundle {
+        collider: CircleCollider { radius },
+        ..ParticleBundle::new_with_pos_and_vel(pos, vel)
+    })
+}
+
+pub(crate) fn spawn_box(app: &mut App, pos: Vec2, vel: Vec2, size: Vec2) -> EntityMut<'_> {
+    app.world.spawn(DynamicBoxBundle {
+        collider: BoxCollider { size },
+        ..DynamicBoxBundle::new_with_pos_and_vel(pos, vel)
+    })
+}
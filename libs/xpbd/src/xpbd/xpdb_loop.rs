@@ -57,10 +57,10 @@ pub fn run_criteria(time: Res<Time>, mut state: ResMut<XpbdLoop>) -> ShouldRun {
             // We finished a whole step
             if state.paused && state.queued_steps > 0 {
                 state.queued_steps -= 1;
-            } else {
-                state.accumulator -= DELTA_TIME;
             }
 
+            state.accumulator -= DELTA_TIME;
+
             state.current_substep = 0;
             state.substepping = false;
         }
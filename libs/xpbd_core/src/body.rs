@@ -0,0 +1,278 @@
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, FromReflect, Reflect, ReflectComponent};
+use glam::Vec2;
+
+use super::consts::{COLLISION_PAIR_VEL_MARGIN_FACTOR, SUB_DT};
+
+/// Whether a body moves, switchable at runtime, e.g. to freeze a body once it has landed.
+/// Only bodies with a `RigidBody` take part in the broad phase, colliders without one are never paired with anything
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub enum RigidBody {
+    #[default]
+    Dynamic,
+    Static,
+}
+
+impl RigidBody {
+    pub fn is_static(&self) -> bool {
+        *self == RigidBody::Static
+    }
+}
+
+/// How the coefficients of two touching bodies are combined.
+/// When the bodies use different rules the one declared later wins, so `Max` overrides everything else
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub fn combine(rule_a: Self, a: f32, rule_b: Self, b: f32) -> f32 {
+        match rule_a.max(rule_b) {
+            CombineRule::Average => (a + b) / 2.,
+            CombineRule::Min => a.min(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::Max => a.max(b),
+        }
+    }
+}
+
+/// Bit masks of the layers a body belongs to and the layers it interacts with.
+/// Bodies without it belong to and interact with every layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct CollisionLayers {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self {
+            memberships: u32::MAX,
+            filters: u32::MAX,
+        }
+    }
+}
+
+impl CollisionLayers {
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+
+    pub fn interacts_with(&self, other: &Self) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
+}
+
+/// Axes along which a body doesn't move, e.g. a sliding door that only opens sideways.
+/// Contacts can still push other bodies out of it, but never move it along a locked axis
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct LockedAxes {
+    pub translation_x: bool,
+    pub translation_y: bool,
+}
+
+impl LockedAxes {
+    pub fn lock_translation_x(self) -> Self {
+        Self {
+            translation_x: true,
+            ..self
+        }
+    }
+
+    pub fn lock_translation_y(self) -> Self {
+        Self {
+            translation_y: true,
+            ..self
+        }
+    }
+
+    /// `vec` without its components along the locked axes
+    pub fn apply(&self, vec: Vec2) -> Vec2 {
+        Vec2::new(
+            if self.translation_x { 0. } else { vec.x },
+            if self.translation_y { 0. } else { vec.y },
+        )
+    }
+
+    /// Inverse mass along each axis, zero along the locked ones so that contacts and constraints never move the body along them
+    pub fn inv_mass(&self, inv_mass: f32) -> Vec2 {
+        self.apply(Vec2::splat(inv_mass))
+    }
+}
+
+/// Highest speed a body moves at, whatever pushes it
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct MaxLinearSpeed(pub f32);
+
+impl Default for MaxLinearSpeed {
+    fn default() -> Self {
+        Self(f32::INFINITY)
+    }
+}
+
+impl MaxLinearSpeed {
+    pub fn clamp(&self, vel: Vec2) -> Vec2 {
+        vel.clamp_length_max(self.0.max(0.))
+    }
+}
+
+/// How much a body gives way under contacts, e.g. for rubber or cushions. Bodies without it are rigid.
+/// Bodies sink into each other until the penetration is `compliance` times the force between them,
+/// which also spreads the push out of deep penetrations over several steps
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default))]
+pub struct ContactSoftness {
+    /// Inverse stiffness, in penetration per unit of force
+    pub compliance: f32,
+    /// Resistance to the bodies sinking into each other, only has an effect with some compliance
+    pub damping: f32,
+}
+
+impl ContactSoftness {
+    /// Softness of a contact between two bodies, which give way like two springs in series
+    pub fn combine(a: Self, b: Self) -> Self {
+        Self {
+            compliance: a.compliance + b.compliance,
+            damping: a.damping.max(b.damping),
+        }
+    }
+
+    /// Part of `penetration` to push out in a substep from the XPBD lagrange multiplier of the contact,
+    /// `approach` being how much closer the bodies got along the normal during the substep
+    pub fn resolved(&self, penetration: f32, approach: f32, inv_mass_sum: f32) -> f32 {
+        if self.compliance <= 0. {
+            return penetration;
+        }
+
+        let alpha = self.compliance / (SUB_DT * SUB_DT);
+        let gamma = self.compliance * self.damping / SUB_DT;
+        let lambda = (penetration + gamma * approach) / ((1. + gamma) * inv_mass_sum + alpha);
+
+        (lambda * inv_mass_sum).clamp(0., penetration)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct Aabb {
+    // bottom-left corner
+    pub min: Vec2,
+    // top-right corner
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn from_center(center: Vec2, half_extents: Vec2) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    /// Bounds of a body at `pos`, grown by how far it may move during a step at `vel`
+    /// so that its collision pairs are found before it touches anything
+    pub fn moving(pos: Vec2, vel: Vec2, half_extents: Vec2) -> Self {
+        let margin = COLLISION_PAIR_VEL_MARGIN_FACTOR * vel.length();
+
+        Self::from_center(pos, half_extents + Vec2::splat(margin))
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.max.x >= other.min.x
+            && self.max.y >= other.min.y
+            && self.min.x <= other.max.x
+            && self.min.y <= other.max.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combine_rules() {
+        use CombineRule::*;
+
+        assert_eq!(CombineRule::combine(Average, 0.2, Average, 0.6), 0.4);
+        assert_eq!(CombineRule::combine(Min, 0.2, Min, 0.6), 0.2);
+        assert_eq!(CombineRule::combine(Max, 0.2, Max, 0.6), 0.6);
+        assert!((CombineRule::combine(Multiply, 0.2, Multiply, 0.6) - 0.12).abs() < 0.001);
+    }
+
+    #[test]
+    fn collision_layers() {
+        let player = CollisionLayers::new(0b01, 0b10);
+        let enemy = CollisionLayers::new(0b10, 0b11);
+        let ghost = CollisionLayers::new(0b100, 0);
+
+        assert!(player.interacts_with(&enemy));
+        assert!(enemy.interacts_with(&enemy));
+        assert!(!player.interacts_with(&player));
+        assert!(!ghost.interacts_with(&CollisionLayers::default()));
+    }
+
+    #[test]
+    fn soft_contacts_push_out_part_of_the_penetration() {
+        let rigid = ContactSoftness::default();
+        let soft = ContactSoftness {
+            compliance: 1e-4,
+            damping: 0.,
+        };
+        let damped = ContactSoftness {
+            damping: 100.,
+            ..soft
+        };
+
+        assert_eq!(rigid.resolved(0.5, 0.1, 2.), 0.5);
+
+        let resolved = soft.resolved(0.5, 0.1, 2.);
+
+        assert!(resolved > 0. && resolved < 0.5, "{resolved}");
+        // bodies sinking in are pushed back harder, ones already moving apart less
+        assert!(damped.resolved(0.5, 0.1, 2.) > resolved);
+        assert!(damped.resolved(0.5, -0.1, 2.) < resolved);
+        assert_eq!(
+            ContactSoftness::combine(soft, damped),
+            ContactSoftness {
+                compliance: 2e-4,
+                damping: 100.,
+            }
+        );
+    }
+
+    #[test]
+    fn combine_rule_priority() {
+        use CombineRule::*;
+
+        assert_eq!(CombineRule::combine(Max, 0.9, Average, 0.1), 0.9);
+        assert_eq!(CombineRule::combine(Average, 0.1, Max, 0.9), 0.9);
+        assert_eq!(CombineRule::combine(Min, 0.9, Average, 0.1), 0.1);
+    }
+}
@@ -0,0 +1,171 @@
+use glam::Vec2;
+
+use super::body::Aabb;
+
+#[derive(Debug, Clone, Copy)]
+enum BvhNode<T> {
+    Leaf {
+        aabb: Aabb,
+        id: T,
+    },
+    Branch {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl<T> BvhNode<T> {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { aabb, .. } | BvhNode::Branch { aabb, .. } => aabb,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over fixed boxes, built once and queried many times.
+/// Leaves are identified by `T`, e.g. an entity or an index
+#[derive(Debug, Clone)]
+pub struct Bvh<T> {
+    nodes: Vec<BvhNode<T>>,
+    root: Option<usize>,
+}
+
+impl<T> Default for Bvh<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: None,
+        }
+    }
+}
+
+impl<T: Copy> Bvh<T> {
+    pub fn build(mut leaves: Vec<(T, Aabb)>) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(leaves.len() * 2),
+            root: None,
+        };
+
+        if !leaves.is_empty() {
+            bvh.root = Some(bvh.build_node(&mut leaves));
+        }
+
+        bvh
+    }
+
+    // splits the leaves in half along the longest axis of their centers
+    fn build_node(&mut self, leaves: &mut [(T, Aabb)]) -> usize {
+        if let [(id, aabb)] = leaves {
+            self.nodes.push(BvhNode::Leaf {
+                aabb: *aabb,
+                id: *id,
+            });
+
+            return self.nodes.len() - 1;
+        }
+
+        let (min, max) = leaves.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), (_, aabb)| (min.min(aabb.center()), max.max(aabb.center())),
+        );
+        let extent = max - min;
+        let axis = if extent.x > extent.y { 0 } else { 1 };
+
+        leaves.sort_unstable_by(|(_, a), (_, b)| a.center()[axis].total_cmp(&b.center()[axis]));
+
+        let (left_leaves, right_leaves) = leaves.split_at_mut(leaves.len() / 2);
+        let left = self.build_node(left_leaves);
+        let right = self.build_node(right_leaves);
+        let aabb = self.nodes[left].aabb().union(self.nodes[right].aabb());
+
+        self.nodes.push(BvhNode::Branch { aabb, left, right });
+
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| matches!(node, BvhNode::Leaf { .. }))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Calls `on_hit` for every leaf intersecting `aabb`
+    pub fn query(&self, aabb: &Aabb, mut on_hit: impl FnMut(T)) {
+        let Some(root) = self.root else {
+            return;
+        };
+        let mut stack = vec![root];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            if !node.aabb().intersects(aabb) {
+                continue;
+            }
+
+            match *node {
+                BvhNode::Leaf { id, .. } => on_hit(id),
+                BvhNode::Branch { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(min: Vec2, max: Vec2) -> Aabb {
+        Aabb { min, max }
+    }
+
+    #[test]
+    fn query_matches_brute_force() {
+        let leaves: Vec<_> = (0..50)
+            .map(|i| {
+                let min = Vec2::new((i % 10) as f32 * 3., (i / 10) as f32 * 5.);
+
+                (i, aabb(min, min + Vec2::new(2., 4.)))
+            })
+            .collect();
+        let bvh = Bvh::build(leaves.clone());
+
+        assert_eq!(bvh.len(), 50);
+
+        for query in [
+            aabb(Vec2::new(-1., -1.), Vec2::new(0.5, 0.5)),
+            aabb(Vec2::new(4., 4.), Vec2::new(10., 12.)),
+            aabb(Vec2::new(100., 100.), Vec2::new(101., 101.)),
+        ] {
+            let mut hits = Vec::new();
+
+            bvh.query(&query, |id| hits.push(id));
+            hits.sort();
+
+            let expected: Vec<_> = leaves
+                .iter()
+                .filter(|(_, leaf)| leaf.intersects(&query))
+                .map(|(id, _)| *id)
+                .collect();
+
+            assert_eq!(hits, expected);
+        }
+    }
+
+    #[test]
+    fn empty_bvh() {
+        let bvh = Bvh::<u32>::build(Vec::new());
+
+        assert!(bvh.is_empty());
+        bvh.query(&aabb(Vec2::ZERO, Vec2::ONE), |_| panic!("unexpected hit"));
+    }
+}
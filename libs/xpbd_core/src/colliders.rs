@@ -0,0 +1,80 @@
+use std::f32::consts::PI;
+
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, Reflect, ReflectComponent};
+use glam::Vec2;
+
+/// Geometric properties of a collider shape used to derive mass properties from density
+pub trait ColliderShape {
+    fn area(&self) -> f32;
+
+    /// Moment of inertia around the shape center for a body of mass 1
+    fn unit_inertia(&self) -> f32;
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct CircleCollider {
+    pub radius: f32,
+}
+
+impl Default for CircleCollider {
+    fn default() -> Self {
+        Self { radius: 20. }
+    }
+}
+
+impl ColliderShape for CircleCollider {
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    fn unit_inertia(&self) -> f32 {
+        self.radius * self.radius / 2.
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct BoxCollider {
+    pub size: Vec2,
+}
+
+impl Default for BoxCollider {
+    fn default() -> Self {
+        Self { size: Vec2::ONE }
+    }
+}
+
+impl ColliderShape for BoxCollider {
+    fn area(&self) -> f32 {
+        self.size.x * self.size.y
+    }
+
+    fn unit_inertia(&self) -> f32 {
+        self.size.length_squared() / 12.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circle_mass_properties() {
+        let circle = CircleCollider { radius: 2. };
+
+        assert!((circle.area() - 4. * PI).abs() < 0.001);
+        assert!((circle.unit_inertia() - 2.).abs() < 0.001);
+    }
+
+    #[test]
+    fn box_mass_properties() {
+        let box_ = BoxCollider {
+            size: Vec2::new(2., 4.),
+        };
+
+        assert!((box_.area() - 8.).abs() < 0.001);
+        assert!((box_.unit_inertia() - 20. / 12.).abs() < 0.001);
+    }
+}
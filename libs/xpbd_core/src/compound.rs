@@ -0,0 +1,125 @@
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, Reflect, ReflectComponent};
+use glam::Vec2;
+
+use super::{body::Aabb, contact::Contact, terrain::BodyShape};
+
+/// Shape of a body made of several parts, each at an offset from the body position.
+/// Bodies don't rotate, so the parts keep their offsets
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct CompoundCollider {
+    pub parts: Vec<(Vec2, BodyShape)>,
+}
+
+impl CompoundCollider {
+    pub fn new(parts: Vec<(Vec2, BodyShape)>) -> Self {
+        Self { parts }
+    }
+
+    /// Bounds relative to the body position, as bottom-left and top-right corners
+    pub fn local_bounds(&self) -> (Vec2, Vec2) {
+        self.parts
+            .iter()
+            .map(|(offset, shape)| Aabb::from_center(*offset, shape.half_extents()))
+            .reduce(|a, b| a.union(&b))
+            .map_or((Vec2::ZERO, Vec2::ZERO), |aabb| (aabb.min, aabb.max))
+    }
+
+    /// Mass and moment of inertia around the body position for a uniform `density`.
+    /// Overlapping parts are counted twice
+    pub fn mass_properties(&self, density: f32) -> (f32, f32) {
+        self.parts
+            .iter()
+            .fold((0., 0.), |(mass, inertia), (offset, shape)| {
+                let part_mass = density * shape.area();
+
+                (
+                    mass + part_mass,
+                    inertia + part_mass * (shape.unit_inertia() + offset.length_squared()),
+                )
+            })
+    }
+}
+
+/// Calls `on_contact` for every pair of touching parts of two bodies, deepest first,
+/// with normals pointing from the parts at `pos_a` to the ones at `pos_b`
+pub fn parts_contacts(
+    parts_a: &[(Vec2, BodyShape)],
+    pos_a: Vec2,
+    parts_b: &[(Vec2, BodyShape)],
+    pos_b: Vec2,
+    on_contact: &mut dyn FnMut(Contact),
+) {
+    let mut contacts: Vec<_> = parts_a
+        .iter()
+        .flat_map(|(offset_a, shape_a)| {
+            parts_b.iter().filter_map(move |(offset_b, shape_b)| {
+                shape_a.contact(pos_a + *offset_a, shape_b, pos_b + *offset_b)
+            })
+        })
+        .collect();
+
+    // parts touching side by side often report the same penetration, the deepest is solved first
+    // and the others only for what is left of them
+    contacts.sort_by(|a, b| b.penetration.total_cmp(&a.penetration));
+    contacts.into_iter().for_each(on_contact);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l_shape() -> CompoundCollider {
+        CompoundCollider::new(vec![
+            (
+                Vec2::ZERO,
+                BodyShape::Box {
+                    size: Vec2::new(3., 1.),
+                },
+            ),
+            (
+                Vec2::new(-1., 1.5),
+                BodyShape::Box {
+                    size: Vec2::new(1., 2.),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn bounds_and_mass_cover_all_parts() {
+        let compound = l_shape();
+
+        assert_eq!(
+            compound.local_bounds(),
+            (Vec2::new(-1.5, -0.5), Vec2::new(1.5, 2.5))
+        );
+
+        let (mass, inertia) = compound.mass_properties(2.);
+
+        assert_eq!(mass, 10.);
+        assert!((inertia - (6. * 10. / 12. + 4. * (5. / 12. + 3.25))).abs() < 1e-4);
+    }
+
+    #[test]
+    fn contacts_come_from_every_touching_part() {
+        let compound = l_shape();
+        let ball = [(Vec2::ZERO, BodyShape::Circle { radius: 0.5 })];
+        let mut contacts = Vec::new();
+
+        // touches the top of the long arm and the side of the short one
+        parts_contacts(
+            &compound.parts,
+            Vec2::ZERO,
+            &ball,
+            Vec2::new(-0.1, 0.9),
+            &mut |contact| contacts.push(contact),
+        );
+
+        assert_eq!(contacts.len(), 2);
+        assert!(contacts[0].penetration >= contacts[1].penetration);
+        assert!(contacts.iter().any(|contact| contact.normal.y > 0.9));
+        assert!(contacts.iter().any(|contact| contact.normal.x > 0.9));
+    }
+}
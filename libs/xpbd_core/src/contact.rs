@@ -0,0 +1,530 @@
+use glam::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub penetration: f32,
+    // from body a to body b
+    pub normal: Vec2,
+    // world-space contact points, only the first `point_count` are valid.
+    // For gameplay queries only, e.g. to place effects where bodies touch: bodies don't rotate,
+    // so the solver never reads them and only uses the normal and penetration
+    pub points: [Vec2; 2],
+    pub point_count: usize,
+    // velocity of body a relative to body b along the surface that friction drives towards,
+    // zero unless set by a contact modifier such as `SurfaceVelocity`
+    pub surface_vel: Vec2,
+    // with one-sided terrain or a `OneWay` body, which let go of bodies already moving away
+    // instead of holding them back with restitution
+    pub one_sided: bool,
+    // depth the position solve pushed the bodies apart by in this substep, set by the solver
+    pub resolved: f32,
+}
+
+impl Contact {
+    pub fn with_point(normal: Vec2, penetration: f32, point: Vec2) -> Self {
+        Self {
+            penetration,
+            normal,
+            points: [point; 2],
+            point_count: 1,
+            surface_vel: Vec2::ZERO,
+            one_sided: false,
+            resolved: 0.,
+        }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points[..self.point_count]
+    }
+
+    pub fn flipped(self) -> Self {
+        Self {
+            normal: -self.normal,
+            surface_vel: -self.surface_vel,
+            ..self
+        }
+    }
+}
+
+pub fn ball_ball(pos_a: Vec2, radius_a: f32, pos_b: Vec2, radius_b: f32) -> Option<Contact> {
+    let ab = pos_b - pos_a;
+    let combined_radius = radius_a + radius_b;
+    let ab_sqr_len = ab.length_squared();
+
+    if ab_sqr_len < combined_radius * combined_radius {
+        let ab_length = ab_sqr_len.sqrt();
+        let penetration = combined_radius - ab.length();
+        let normal = ab / ab_length;
+        let point = pos_a + normal * (radius_a - penetration / 2.);
+
+        Some(Contact::with_point(normal, penetration, point))
+    } else {
+        None
+    }
+}
+
+pub fn ball_box(pos_a: Vec2, radius_a: f32, pos_b: Vec2, size_b: Vec2) -> Option<Contact> {
+    let box_to_circle = pos_a - pos_b;
+    let box_to_circle_abs = box_to_circle.abs();
+    let half_extends = size_b / 2.;
+    let corner_to_center = box_to_circle_abs - half_extends;
+    let r = radius_a;
+
+    if corner_to_center.x > r || corner_to_center.y > r {
+        return None;
+    }
+
+    let s = box_to_circle.signum();
+
+    let (normal, penetration) = if corner_to_center.x > 0. && corner_to_center.y > 0. {
+        // corner case
+        let corner_to_center_sqr = corner_to_center.length_squared();
+
+        if corner_to_center_sqr > r * r {
+            return None;
+        }
+
+        let cornder_dist = corner_to_center_sqr.sqrt();
+        let penetration = r - cornder_dist;
+        let normal = corner_to_center / cornder_dist * -s;
+
+        (normal, penetration)
+    } else if corner_to_center.x > corner_to_center.y {
+        // closer to vertical edge
+        (Vec2::X * -s.x, -corner_to_center.x + r)
+    } else {
+        (Vec2::Y * -s.y, -corner_to_center.y + r)
+    };
+
+    let point = pos_a + normal * (r - penetration / 2.);
+
+    Some(Contact::with_point(normal, penetration, point))
+}
+
+/// Left-hand normal of the segment `a -> b`
+pub(crate) fn segment_normal(a: Vec2, b: Vec2) -> Vec2 {
+    (b - a).perp().normalize_or_zero()
+}
+
+pub fn ball_segment(
+    pos_a: Vec2,
+    radius_a: f32,
+    seg_a: Vec2,
+    seg_b: Vec2,
+    one_sided: bool,
+) -> Option<Contact> {
+    let seg_normal = segment_normal(seg_a, seg_b);
+
+    if one_sided && (pos_a - seg_a).dot(seg_normal) < 0. {
+        return None;
+    }
+
+    let seg = seg_b - seg_a;
+    let t = ((pos_a - seg_a).dot(seg) / seg.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+    let closest = seg_a + seg * t;
+    let closest_to_center = pos_a - closest;
+    let dist_sqr = closest_to_center.length_squared();
+
+    if dist_sqr >= radius_a * radius_a {
+        return None;
+    }
+
+    let dist = dist_sqr.sqrt();
+    let normal = if dist > f32::EPSILON {
+        -closest_to_center / dist
+    } else {
+        -seg_normal
+    };
+
+    Some(Contact::with_point(normal, radius_a - dist, closest))
+}
+
+/// Separating axis test of an axis-aligned box against a segment.
+/// Contact points are the box corners deepest inside the segment
+pub fn box_segment(
+    pos_a: Vec2,
+    size_a: Vec2,
+    seg_a: Vec2,
+    seg_b: Vec2,
+    one_sided: bool,
+) -> Option<Contact> {
+    let half_a = size_a / 2.;
+    let seg_normal = segment_normal(seg_a, seg_b);
+
+    if one_sided && (pos_a - seg_a).dot(seg_normal) < 0. {
+        return None;
+    }
+
+    let mut best: Option<(f32, Vec2)> = None;
+
+    for axis in [Vec2::X, Vec2::Y, seg_normal] {
+        if axis == Vec2::ZERO {
+            continue;
+        }
+
+        let center = pos_a.dot(axis);
+        let extent = half_a.x * axis.x.abs() + half_a.y * axis.y.abs();
+        let (seg_min, seg_max) = {
+            let (a, b) = (seg_a.dot(axis), seg_b.dot(axis));
+            (a.min(b), a.max(b))
+        };
+        // moving the box towards -axis or +axis, whichever separates it sooner
+        let towards_positive = center + extent - seg_min;
+        let towards_negative = seg_max - (center - extent);
+
+        if towards_positive < 0. || towards_negative < 0. {
+            return None;
+        }
+
+        let (overlap, towards_segment) = if towards_positive < towards_negative {
+            (towards_positive, axis)
+        } else {
+            (towards_negative, -axis)
+        };
+
+        if best.is_none_or(|(penetration, _)| overlap < penetration) {
+            best = Some((overlap, towards_segment));
+        }
+    }
+
+    let (mut penetration, mut normal) = best?;
+
+    if one_sided {
+        // one-sided segments always push out through their front
+        normal = -seg_normal;
+        penetration = half_a.x * seg_normal.x.abs() + half_a.y * seg_normal.y.abs()
+            - (pos_a - seg_a).dot(seg_normal);
+
+        if penetration <= 0. {
+            return None;
+        }
+    }
+
+    let corners = [
+        Vec2::new(-half_a.x, -half_a.y),
+        Vec2::new(half_a.x, -half_a.y),
+        Vec2::new(half_a.x, half_a.y),
+        Vec2::new(-half_a.x, half_a.y),
+    ];
+    let deepest = corners
+        .iter()
+        .map(|corner| corner.dot(normal))
+        .fold(f32::MIN, f32::max);
+    let mut contact = Contact::with_point(normal, penetration, pos_a);
+    let mut point_count = 0;
+
+    for corner in corners {
+        if point_count < 2 && corner.dot(normal) > deepest - 0.001 {
+            contact.points[point_count] = pos_a + corner;
+            point_count += 1;
+        }
+    }
+
+    contact.point_count = point_count.max(1);
+
+    Some(contact)
+}
+
+/// Axis-aligned boxes touch along a whole edge, so the manifold has the two ends of the overlapping edge segment
+pub fn box_box(pos_a: Vec2, size_a: Vec2, pos_b: Vec2, size_b: Vec2) -> Option<Contact> {
+    let half_a = size_a / 2.;
+    let half_b = size_b / 2.;
+    let ab = pos_b - pos_a;
+    let overlap = (half_a + half_b) - ab.abs();
+
+    if overlap.x < 0. || overlap.y < 0. {
+        return None;
+    }
+
+    let (axis, tangent) = if overlap.x < overlap.y {
+        (Vec2::X, Vec2::Y)
+    } else {
+        (Vec2::Y, Vec2::X)
+    };
+    let penetration = overlap.dot(axis);
+    let normal = axis * ab.dot(axis).signum();
+
+    // middle of the overlapping region along the normal
+    let depth = (pos_a + normal * (half_a.dot(axis) - penetration / 2.)).dot(axis);
+    let tangent_min = (pos_a - half_a).max(pos_b - half_b).dot(tangent);
+    let tangent_max = (pos_a + half_a).min(pos_b + half_b).dot(tangent);
+
+    let point_min = axis * depth + tangent * tangent_min;
+    let point_max = axis * depth + tangent * tangent_max;
+
+    Some(Contact {
+        penetration,
+        normal,
+        points: [point_min, point_max],
+        point_count: if tangent_max - tangent_min > f32::EPSILON {
+            2
+        } else {
+            1
+        },
+        surface_vel: Vec2::ZERO,
+        one_sided: false,
+        resolved: 0.,
+    })
+}
+
+/// Fraction of `delta` a ray from `origin` travels before entering the circle, and the surface normal there.
+/// Rays starting inside hit it right away
+pub fn ray_ball(origin: Vec2, delta: Vec2, pos: Vec2, radius: f32) -> Option<(f32, Vec2)> {
+    let offset = origin - pos;
+    let c = offset.length_squared() - radius * radius;
+
+    if c <= 0. {
+        return Some((0., -delta.normalize_or_zero()));
+    }
+
+    let a = delta.length_squared();
+    let b = offset.dot(delta);
+    let discriminant = b * b - a * c;
+
+    if a <= f32::EPSILON || b >= 0. || discriminant < 0. {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+
+    (t <= 1.).then(|| (t, (offset + delta * t).normalize_or_zero()))
+}
+
+/// Slab test of a ray against an axis-aligned box, see `ray_ball`
+pub fn ray_box(origin: Vec2, delta: Vec2, pos: Vec2, size: Vec2) -> Option<(f32, Vec2)> {
+    let half = size / 2.;
+    let (min, max) = (pos - half, pos + half);
+    let mut t_enter = 0.;
+    let mut t_exit = 1.;
+    let mut normal = -delta.normalize_or_zero();
+
+    for axis in [Vec2::X, Vec2::Y] {
+        let (o, d) = (origin.dot(axis), delta.dot(axis));
+        let (lo, hi) = (min.dot(axis), max.dot(axis));
+
+        if d.abs() <= f32::EPSILON {
+            if o < lo || o > hi {
+                return None;
+            }
+
+            continue;
+        }
+
+        let (t_near, t_far) = ((lo - o) / d, (hi - o) / d);
+        let (t_near, t_far) = (t_near.min(t_far), t_near.max(t_far));
+
+        if t_near > t_enter {
+            t_enter = t_near;
+            normal = -axis * d.signum();
+        }
+
+        t_exit = t_far.min(t_exit);
+
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    Some((t_enter, normal))
+}
+
+/// Intersection of a ray with a segment, see `ray_ball`.
+/// One-sided segments are only hit from their left side
+pub fn ray_segment(
+    origin: Vec2,
+    delta: Vec2,
+    seg_a: Vec2,
+    seg_b: Vec2,
+    one_sided: bool,
+) -> Option<(f32, Vec2)> {
+    let seg = seg_b - seg_a;
+    let denominator = delta.perp_dot(seg);
+
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let to_a = seg_a - origin;
+    let t = to_a.perp_dot(seg) / denominator;
+    let u = to_a.perp_dot(delta) / denominator;
+
+    if !(0. ..=1.).contains(&t) || !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    let seg_normal = segment_normal(seg_a, seg_b);
+    let from_left = delta.dot(seg_normal) < 0.;
+
+    if one_sided && !from_left {
+        return None;
+    }
+
+    Some((t, if from_left { seg_normal } else { -seg_normal }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_box_clear() {
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(1.1, 0.), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(-1.1, 0.), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0., 1.1), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0., -1.1), Vec2::ONE).is_none());
+    }
+
+    #[test]
+    fn box_box_intersection() {
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::ZERO, Vec2::ONE).is_some());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0.9, 0.9), Vec2::ONE).is_some());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(-0.9, -0.9), Vec2::ONE).is_some());
+    }
+
+    #[test]
+    fn box_box_contact() {
+        let Contact {
+            normal,
+            penetration,
+            ..
+        } = box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0.9, 0.), Vec2::ONE).unwrap();
+
+        assert!(normal.x > 0.);
+        assert!(normal.y < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn box_box_manifold() {
+        let contact = box_box(
+            Vec2::ZERO,
+            Vec2::new(4., 2.),
+            Vec2::new(1., 1.9),
+            Vec2::new(2., 2.),
+        )
+        .unwrap();
+
+        assert_eq!(contact.normal, Vec2::Y);
+        assert_eq!(contact.points().len(), 2);
+        assert!(contact.points()[0].abs_diff_eq(Vec2::new(0., 0.95), 0.001));
+        assert!(contact.points()[1].abs_diff_eq(Vec2::new(2., 0.95), 0.001));
+    }
+
+    #[test]
+    fn ball_ball_contact_point() {
+        let contact = ball_ball(Vec2::ZERO, 1., Vec2::new(1.5, 0.), 1.).unwrap();
+
+        assert_eq!(contact.normal, Vec2::X);
+        assert_eq!(contact.points().len(), 1);
+        assert!(contact.points()[0].abs_diff_eq(Vec2::new(0.75, 0.), 0.001));
+    }
+
+    #[test]
+    fn ball_box_contacts() {
+        let size = Vec2::new(2., 2.);
+        let edge = ball_box(Vec2::new(0., 1.5), 1., Vec2::ZERO, size).unwrap();
+
+        assert_eq!(edge.normal, -Vec2::Y);
+        assert!((edge.penetration - 0.5).abs() < 0.001);
+        assert!(edge.points()[0].abs_diff_eq(Vec2::new(0., 0.75), 0.001));
+
+        let corner = ball_box(Vec2::new(1.5, 1.5), 1., Vec2::ZERO, size).unwrap();
+
+        assert!(corner.normal.abs_diff_eq(-Vec2::ONE.normalize(), 0.001));
+        assert!((corner.penetration - (1. - 0.5 * 2f32.sqrt())).abs() < 0.001);
+        assert!(ball_box(Vec2::new(1.8, 1.8), 1., Vec2::ZERO, size).is_none());
+    }
+
+    #[test]
+    fn ball_segment_contact() {
+        let contact =
+            ball_segment(Vec2::new(5., 1.), 2., Vec2::ZERO, Vec2::new(10., 0.), false).unwrap();
+
+        assert!(contact.normal.abs_diff_eq(-Vec2::Y, 0.001));
+        assert!((contact.penetration - 1.).abs() < 0.001);
+        assert!(
+            ball_segment(Vec2::new(5., 3.), 2., Vec2::ZERO, Vec2::new(10., 0.), false).is_none()
+        );
+    }
+
+    #[test]
+    fn one_sided_segment() {
+        // left side of a segment going right is up
+        assert!(
+            ball_segment(Vec2::new(5., 1.), 2., Vec2::ZERO, Vec2::new(10., 0.), true).is_some()
+        );
+        assert!(
+            ball_segment(Vec2::new(5., -1.), 2., Vec2::ZERO, Vec2::new(10., 0.), true).is_none()
+        );
+        assert!(box_segment(
+            Vec2::new(5., -1.),
+            Vec2::splat(4.),
+            Vec2::ZERO,
+            Vec2::new(10., 0.),
+            true
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn box_segment_contact() {
+        let contact = box_segment(
+            Vec2::new(5., 1.),
+            Vec2::splat(4.),
+            Vec2::ZERO,
+            Vec2::new(10., 0.),
+            false,
+        )
+        .unwrap();
+
+        assert!(contact.normal.abs_diff_eq(-Vec2::Y, 0.001));
+        assert!((contact.penetration - 1.).abs() < 0.001);
+        assert_eq!(contact.points().len(), 2);
+
+        let sloped = box_segment(
+            Vec2::new(0., 0.5),
+            Vec2::splat(2.),
+            Vec2::new(-10., -10.),
+            Vec2::new(10., 10.),
+            false,
+        );
+
+        assert!(sloped.is_some());
+    }
+
+    #[test]
+    fn ray_hits() {
+        let (t, normal) = ray_ball(Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(5., 0.), 1.).unwrap();
+
+        assert!((t - 0.4).abs() < 0.001);
+        assert_eq!(normal, -Vec2::X);
+        assert!(ray_ball(Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(5., 2.), 1.).is_none());
+
+        let (t, normal) = ray_box(
+            Vec2::ZERO,
+            Vec2::new(0., -10.),
+            Vec2::new(0., -6.),
+            Vec2::splat(2.),
+        )
+        .unwrap();
+
+        assert!((t - 0.5).abs() < 0.001);
+        assert_eq!(normal, Vec2::Y);
+        assert!(ray_box(
+            Vec2::ZERO,
+            Vec2::new(0., -4.),
+            Vec2::new(0., -6.),
+            Vec2::splat(2.)
+        )
+        .is_none());
+
+        let (a, b) = (Vec2::new(-1., 2.), Vec2::new(1., 2.));
+        let (t, normal) = ray_segment(Vec2::ZERO, Vec2::new(0., 4.), a, b, false).unwrap();
+
+        assert!((t - 0.5).abs() < 0.001);
+        assert_eq!(normal, -Vec2::Y);
+        // one-sided segments face left of a -> b, i.e. up here
+        assert!(ray_segment(Vec2::ZERO, Vec2::new(0., 4.), a, b, true).is_none());
+        assert!(ray_segment(Vec2::new(0., 4.), Vec2::new(0., -4.), a, b, true).is_some());
+    }
+}
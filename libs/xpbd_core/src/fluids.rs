@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Reflect, ReflectResource, Resource};
+use glam::{IVec2, Vec2};
+
+use super::consts::SUB_DT;
+
+/// Parameters of a position-based fluid
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource, Reflect), reflect(Resource))]
+pub struct FluidSettings {
+    /// Particles further apart than this don't affect each other
+    pub kernel_radius: f32,
+    /// Density the fluid is kept at, in the units of `density_kernel` weighted by particle mass
+    pub rest_density: f32,
+    /// Softens the density constraint, avoiding jitter when particles have few neighbours
+    pub relaxation: f32,
+    /// How much particles blend their velocity with their neighbours every substep, from 0 to 1
+    pub viscosity: f32,
+    /// Acceleration pulling neighbouring particles together, in kernel radii per second squared.
+    /// Keeps the surface smooth and lets droplets form, zero lets the fluid spread freely
+    pub surface_tension: f32,
+}
+
+impl FluidSettings {
+    /// Settings for particles of unit mass resting `spacing` apart from each other
+    pub fn from_spacing(spacing: f32) -> Self {
+        let kernel_radius = spacing * 2.5;
+        let range = (kernel_radius / spacing).ceil() as i32;
+        let rest_density = (-range..=range)
+            .flat_map(|x| (-range..=range).map(move |y| IVec2::new(x, y)))
+            .map(|offset| density_kernel(offset.as_vec2().length() * spacing / kernel_radius))
+            .sum();
+
+        Self {
+            kernel_radius,
+            rest_density,
+            relaxation: 0.5,
+            viscosity: 0.05,
+            surface_tension: 5.,
+        }
+    }
+}
+
+impl Default for FluidSettings {
+    fn default() -> Self {
+        Self::from_spacing(8.)
+    }
+}
+
+/// Poly6 kernel without its normalization factor, for `q` as distance over the kernel radius
+pub fn density_kernel(q: f32) -> f32 {
+    if q >= 1. {
+        return 0.;
+    }
+
+    let t = 1. - q * q;
+
+    t * t * t
+}
+
+/// Magnitude of the spiky kernel gradient without its normalization factor
+pub fn gradient_kernel(q: f32) -> f32 {
+    if q >= 1. {
+        return 0.;
+    }
+
+    -3. * (1. - q) * (1. - q)
+}
+
+/// Cohesion kernel scaled to peak at 1, attracting beyond about a quarter of the kernel radius and repelling closer
+pub fn cohesion_kernel(q: f32) -> f32 {
+    if q >= 1. {
+        return 0.;
+    }
+
+    let t = (1. - q) * (1. - q) * (1. - q) * q * q * q;
+
+    if q > 0.5 {
+        64. * t
+    } else {
+        128. * t - 1.
+    }
+}
+
+/// Uniform grid bucketing points by cell, for finding the neighbours of every particle in linear time
+#[derive(Debug, Default, Clone)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Removes every point but keeps the allocated cells for the next rebuild
+    pub fn clear(&mut self) {
+        for indices in self.cells.values_mut() {
+            indices.clear();
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, pos: Vec2) {
+        let cell = self.cell(pos);
+
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Calls `on_point` for every point in the cells around `pos`, a superset of the points within `cell_size` of it
+    pub fn query(&self, pos: Vec2, mut on_point: impl FnMut(usize)) {
+        let center = self.cell(pos);
+
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if let Some(indices) = self.cells.get(&(center + IVec2::new(x, y))) {
+                    indices.iter().copied().for_each(&mut on_point);
+                }
+            }
+        }
+    }
+}
+
+/// Neighbour lists of all particles, stored back to back
+#[derive(Debug, Default)]
+struct Neighbours {
+    hash: SpatialHash,
+    offsets: Vec<usize>,
+    indices: Vec<usize>,
+}
+
+impl Neighbours {
+    /// Finds every pair of points closer than `radius`, excluding a point from its own list
+    fn build(&mut self, points: &[Vec2], radius: f32) {
+        self.hash.cell_size = radius;
+        self.hash.clear();
+
+        for (index, pos) in points.iter().enumerate() {
+            self.hash.insert(index, *pos);
+        }
+
+        self.offsets.clear();
+        self.indices.clear();
+
+        for (index, pos) in points.iter().enumerate() {
+            self.offsets.push(self.indices.len());
+
+            let indices = &mut self.indices;
+
+            self.hash.query(*pos, |other| {
+                if other != index && points[other].distance_squared(*pos) < radius * radius {
+                    indices.push(other);
+                }
+            });
+        }
+
+        self.offsets.push(self.indices.len());
+    }
+
+    fn of(&self, index: usize) -> &[usize] {
+        &self.indices[self.offsets[index]..self.offsets[index + 1]]
+    }
+}
+
+/// Solves the fluid constraints of a set of particles given as parallel slices,
+/// keeping its buffers between substeps
+#[derive(Debug, Default)]
+pub struct FluidSolver {
+    neighbours: Neighbours,
+    points: Vec<Vec2>,
+    lambdas: Vec<f32>,
+}
+
+impl FluidSolver {
+    // positions scaled by the kernel radius, so that the settings don't depend on the world scale
+    fn find_neighbours(&mut self, positions: &[Vec2], kernel_radius: f32) {
+        self.points.clear();
+        self.points
+            .extend(positions.iter().map(|pos| *pos / kernel_radius));
+        self.neighbours.build(&self.points, 1.);
+    }
+
+    /// One jacobi iteration of position based fluids (Macklin and Müller 2013)
+    pub fn solve_density(
+        &mut self,
+        positions: &mut [Vec2],
+        masses: &[f32],
+        inv_masses: &[f32],
+        settings: &FluidSettings,
+    ) {
+        let h = settings.kernel_radius;
+
+        if h <= 0. || settings.rest_density <= 0. {
+            return;
+        }
+
+        self.find_neighbours(positions, h);
+        self.lambdas.clear();
+
+        let points = &self.points;
+
+        for (i, point) in points.iter().enumerate() {
+            let mut density = masses[i] * density_kernel(0.);
+            let mut grad_i = Vec2::ZERO;
+            let mut grad_sum = 0.;
+
+            for &j in self.neighbours.of(i) {
+                let offset = *point - points[j];
+                let q = offset.length();
+                let grad = offset.normalize_or_zero() * gradient_kernel(q) / settings.rest_density;
+
+                density += masses[j] * density_kernel(q);
+                grad_i += grad * masses[j];
+                grad_sum += inv_masses[j] * (grad * masses[j]).length_squared();
+            }
+
+            grad_sum += inv_masses[i] * grad_i.length_squared();
+
+            // the fluid only resists compression, sparse particles are pulled together by the surface tension instead
+            let c = (density / settings.rest_density - 1.).max(0.);
+
+            self.lambdas.push(-c / (grad_sum + settings.relaxation));
+        }
+
+        for (i, pos) in positions.iter_mut().enumerate() {
+            let mut delta = Vec2::ZERO;
+
+            for &j in self.neighbours.of(i) {
+                let offset = points[i] - points[j];
+                let grad = offset.normalize_or_zero() * gradient_kernel(offset.length());
+
+                delta += (self.lambdas[i] * masses[j] + self.lambdas[j] * masses[i]) * grad;
+            }
+
+            *pos += delta * inv_masses[i] / settings.rest_density * h;
+        }
+    }
+
+    /// Xsph viscosity and cohesion (Akinci et al. 2013) between neighbouring particles
+    pub fn solve_velocities(
+        &mut self,
+        positions: &[Vec2],
+        vels: &mut [Vec2],
+        masses: &[f32],
+        settings: &FluidSettings,
+    ) {
+        let h = settings.kernel_radius;
+
+        if h <= 0. {
+            return;
+        }
+
+        self.find_neighbours(positions, h);
+
+        let prev_vels = vels.to_vec();
+        let points = &self.points;
+
+        for (i, vel) in vels.iter_mut().enumerate() {
+            let mut vel_sum = Vec2::ZERO;
+            let mut weight = 0.;
+            let mut cohesion = Vec2::ZERO;
+
+            for &j in self.neighbours.of(i) {
+                let offset = points[i] - points[j];
+                let q = offset.length();
+                let w = density_kernel(q);
+
+                vel_sum += (prev_vels[j] - prev_vels[i]) * w;
+                weight += w;
+                cohesion -= offset.normalize_or_zero() * cohesion_kernel(q) * masses[j];
+            }
+
+            if weight > 0. {
+                *vel += vel_sum / weight * settings.viscosity;
+            }
+
+            *vel += cohesion * settings.surface_tension * h * SUB_DT;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spatial_hash_finds_neighbours() {
+        let points: Vec<_> = (0..100)
+            .map(|i| Vec2::new((i % 10) as f32 * 3.7, (i / 10) as f32 * 2.9))
+            .collect();
+        let mut neighbours = Neighbours::default();
+
+        neighbours.build(&points, 5.);
+
+        for (index, pos) in points.iter().enumerate() {
+            let mut found = neighbours.of(index).to_vec();
+            let expected: Vec<_> = (0..points.len())
+                .filter(|&other| other != index && points[other].distance(*pos) < 5.)
+                .collect();
+
+            found.sort();
+
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn grid_at_spacing_has_rest_density() {
+        let spacing = 8.;
+        let settings = FluidSettings::from_spacing(spacing);
+        let center = Vec2::new(5., 5.) * spacing;
+        let density: f32 = (0..11)
+            .flat_map(|x| (0..11).map(move |y| Vec2::new(x as f32, y as f32) * spacing))
+            .map(|pos| density_kernel(pos.distance(center) / settings.kernel_radius))
+            .sum();
+
+        assert!((density - settings.rest_density).abs() < 0.001);
+    }
+}
@@ -0,0 +1,154 @@
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, FromReflect, Reflect, ReflectComponent};
+use glam::Vec2;
+
+/// Region of a force field relative to its position
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+pub enum ForceFieldShape {
+    Circle { radius: f32 },
+    Box { size: Vec2 },
+}
+
+impl Default for ForceFieldShape {
+    fn default() -> Self {
+        ForceFieldShape::Circle { radius: 100. }
+    }
+}
+
+impl ForceFieldShape {
+    pub fn contains(&self, offset: Vec2) -> bool {
+        match *self {
+            ForceFieldShape::Circle { radius } => offset.length_squared() <= radius * radius,
+            ForceFieldShape::Box { size } => offset.abs().cmple(size / 2.).all(),
+        }
+    }
+
+    /// Distance from the center to the farthest point of the shape
+    pub fn extent(&self) -> f32 {
+        match *self {
+            ForceFieldShape::Circle { radius } => radius,
+            ForceFieldShape::Box { size } => size.length() / 2.,
+        }
+    }
+}
+
+/// How the strength of a point field fades from its center to the edge of its shape
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+pub enum Falloff {
+    #[default]
+    Constant,
+    Linear,
+    Quadratic,
+}
+
+impl Falloff {
+    pub fn factor(&self, distance: f32, extent: f32) -> f32 {
+        let t = if extent > 0. {
+            (1. - distance / extent).max(0.)
+        } else {
+            0.
+        };
+
+        match self {
+            Falloff::Constant => 1.,
+            Falloff::Linear => t,
+            Falloff::Quadratic => t * t,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+pub enum ForceFieldKind {
+    /// Pulls bodies towards the center, or pushes them away with a negative strength.
+    /// Works like gravity, so all bodies are accelerated the same regardless of their mass
+    Point { strength: f32, falloff: Falloff },
+    /// Constant force, light bodies are blown away faster than heavy ones
+    Wind { force: Vec2 },
+    /// Force against the body velocity
+    Drag { coefficient: f32 },
+}
+
+impl Default for ForceFieldKind {
+    fn default() -> Self {
+        ForceFieldKind::Point {
+            strength: 0.,
+            falloff: Falloff::default(),
+        }
+    }
+}
+
+/// Applies forces to the bodies whose center is inside its shape.
+/// Only bodies with `CollisionLayers` interacting with the field's layers are affected
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct ForceField {
+    pub shape: ForceFieldShape,
+    pub kind: ForceFieldKind,
+}
+
+impl ForceField {
+    /// Force on a body at `offset` from the field position
+    pub fn force(&self, offset: Vec2, vel: Vec2, mass: f32) -> Vec2 {
+        if !self.shape.contains(offset) {
+            return Vec2::ZERO;
+        }
+
+        match self.kind {
+            ForceFieldKind::Point { strength, falloff } => {
+                let factor = falloff.factor(offset.length(), self.shape.extent());
+
+                -offset.normalize_or_zero() * strength * factor * mass
+            }
+            ForceFieldKind::Wind { force } => force,
+            ForceFieldKind::Drag { coefficient } => -vel * coefficient,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_field_falloff() {
+        let field = ForceField {
+            shape: ForceFieldShape::Circle { radius: 10. },
+            kind: ForceFieldKind::Point {
+                strength: 4.,
+                falloff: Falloff::Linear,
+            },
+        };
+
+        assert_eq!(
+            field.force(Vec2::new(5., 0.), Vec2::ZERO, 1.),
+            Vec2::new(-2., 0.)
+        );
+        assert_eq!(
+            field.force(Vec2::new(0., -5.), Vec2::ZERO, 2.),
+            Vec2::new(0., 4.)
+        );
+        assert_eq!(field.force(Vec2::new(11., 0.), Vec2::ZERO, 1.), Vec2::ZERO);
+        assert_eq!(Falloff::Quadratic.factor(5., 10.), 0.25);
+    }
+
+    #[test]
+    fn box_field_region() {
+        let field = ForceField {
+            shape: ForceFieldShape::Box {
+                size: Vec2::new(10., 2.),
+            },
+            kind: ForceFieldKind::Wind {
+                force: Vec2::new(3., 0.),
+            },
+        };
+
+        assert_eq!(
+            field.force(Vec2::new(4., 1.), Vec2::ZERO, 1.),
+            Vec2::new(3., 0.)
+        );
+        assert_eq!(field.force(Vec2::new(4., 2.), Vec2::ZERO, 1.), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,20 @@
+//! Engine independent part of the xpbd physics: shapes, contacts and solver steps on plain data,
+//! plus a standalone `World` running them. Only depends on glam, the `bevy` feature adds the derives
+//! the `xpbd` plugin needs to use these types as components and resources.
+//! The plugin runs its own systems rather than a `World`, both go through the broad phase, narrow phase
+//! and contact passes of `pipeline` every substep
+
+pub mod body;
+pub mod bvh;
+pub mod colliders;
+pub mod compound;
+pub mod consts;
+pub mod contact;
+pub mod fluids;
+pub mod forces;
+pub mod pipeline;
+pub mod recording;
+pub mod soft_bodies;
+pub mod solver;
+pub mod terrain;
+pub mod world;
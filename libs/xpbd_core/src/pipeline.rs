@@ -0,0 +1,393 @@
+use std::hash::Hash;
+
+use glam::Vec2;
+
+use super::{
+    body::{Aabb, CollisionLayers, CombineRule, ContactSoftness},
+    bvh::Bvh,
+    compound::parts_contacts,
+    contact::Contact,
+    solver::{
+        solve_contact_vel, ContactBody, ContactCorrections, JacobiCorrections, SolverMode,
+        SolverSettings, VelocityBody,
+    },
+    terrain::{BodyShape, TerrainCollider},
+};
+
+/// Bounds of a moving body for the broad phase, identified by `key`.
+/// Bodies of the same `group` are never paired, e.g. particles kept apart by a constraint of their own
+#[derive(Debug, Clone, Copy)]
+pub struct BroadPhaseBody<K, G> {
+    pub key: K,
+    pub aabb: Aabb,
+    pub layers: CollisionLayers,
+    pub group: Option<G>,
+}
+
+/// Replaces `pairs` with the moving bodies whose bounds overlap, followed for each of them
+/// by the static bodies of `static_bvh` it overlaps, in a fixed order so runs are reproducible
+pub fn collect_collision_pairs<K: Copy, G: PartialEq>(
+    dynamics: &[BroadPhaseBody<K, G>],
+    static_bvh: &Bvh<K>,
+    static_layers: impl Fn(K) -> CollisionLayers,
+    pairs: &mut Vec<(K, K)>,
+) {
+    pairs.clear();
+
+    for (index, a) in dynamics.iter().enumerate() {
+        for b in dynamics[index + 1..].iter() {
+            if a.group.is_some() && a.group == b.group {
+                continue;
+            }
+
+            if a.aabb.intersects(&b.aabb) && a.layers.interacts_with(&b.layers) {
+                pairs.push((a.key, b.key));
+            }
+        }
+
+        static_bvh.query(&a.aabb, |b| {
+            if a.layers.interacts_with(&static_layers(b)) {
+                pairs.push((a.key, b));
+            }
+        });
+    }
+}
+
+/// Shapes of a body where it is in the current substep, for the narrow phase
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedBody<'a, K> {
+    pub key: K,
+    pub parts: &'a [(Vec2, BodyShape)],
+    pub pos: Vec2,
+    /// Position at the start of the substep
+    pub prev_pos: Vec2,
+    pub inv_mass: f32,
+}
+
+/// Adds the contacts between the parts of two bodies, unless neither of them can move
+pub fn collect_contacts<K: Copy>(
+    a: &PlacedBody<'_, K>,
+    b: &PlacedBody<'_, K>,
+    contacts: &mut Vec<(K, K, Contact)>,
+) {
+    if a.inv_mass + b.inv_mass <= 0. {
+        return;
+    }
+
+    parts_contacts(a.parts, a.pos, b.parts, b.pos, &mut |contact| {
+        contacts.push((a.key, b.key, contact))
+    });
+}
+
+/// Adds the contacts between the parts of a body and a terrain collider at `terrain_pos`,
+/// deepest first like the ones of `collect_contacts`. `scratch` is only reused between calls
+pub fn collect_terrain_contacts<K: Copy>(
+    body: &PlacedBody<'_, K>,
+    terrain: K,
+    terrain_pos: Vec2,
+    collider: &dyn TerrainCollider,
+    scratch: &mut Vec<Contact>,
+    contacts: &mut Vec<(K, K, Contact)>,
+) {
+    if body.inv_mass <= 0. {
+        return;
+    }
+
+    scratch.clear();
+
+    for (offset, shape) in body.parts {
+        collider.contacts(
+            terrain_pos,
+            body.pos + *offset,
+            body.prev_pos + *offset,
+            *shape,
+            &mut |contact| scratch.push(contact),
+        );
+    }
+
+    scratch.sort_by(|a, b| b.penetration.total_cmp(&a.penetration));
+    contacts.extend(
+        scratch
+            .drain(..)
+            .map(|contact| (body.key, terrain, contact)),
+    );
+}
+
+/// What the position pass reads of a body
+#[derive(Debug, Clone, Copy)]
+pub struct PositionState {
+    pub pos: Vec2,
+    /// Position at the start of the substep
+    pub prev_pos: Vec2,
+    /// Inverse mass per axis, see `LockedAxes::inv_mass`
+    pub inv_mass: Vec2,
+    pub contact_softness: ContactSoftness,
+}
+
+impl PositionState {
+    fn contact_body<'a, K>(&self, key: K, pos: &'a mut Vec2) -> ContactBody<'a, K> {
+        ContactBody {
+            key,
+            pos,
+            prev_pos: self.prev_pos,
+            inv_mass: self.inv_mass,
+        }
+    }
+}
+
+/// Bodies the position pass reads and moves, e.g. the bodies of a `World` or a query of the plugin
+pub trait BodyPositions<K> {
+    /// `None` for bodies that are gone, their contacts are dropped
+    fn get(&self, key: K) -> Option<PositionState>;
+    /// Only called when the position changed
+    fn set_pos(&mut self, key: K, pos: Vec2);
+}
+
+/// What the velocity pass reads of a body
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityState {
+    pub vel: Vec2,
+    /// Velocity before the positions were solved, restitution is relative to it
+    pub pre_solve_vel: Vec2,
+    /// Inverse mass per axis, see `LockedAxes::inv_mass`
+    pub inv_mass: Vec2,
+    pub restitution: f32,
+    pub restitution_combine: CombineRule,
+    pub friction: f32,
+    pub friction_combine: CombineRule,
+}
+
+/// Bodies the velocity pass reads and bounces, like `BodyPositions`
+pub trait BodyVelocities<K> {
+    fn get(&self, key: K) -> Option<VelocityState>;
+    /// Only called when the velocity changed
+    fn set_vel(&mut self, key: K, vel: Vec2);
+}
+
+/// Position pass of the contacts of a substep, keeping the corrections of the bodies between calls
+#[derive(Debug)]
+pub struct Solver<K> {
+    corrections: ContactCorrections<K>,
+    jacobi_corrections: JacobiCorrections<K>,
+}
+
+impl<K> Default for Solver<K> {
+    fn default() -> Self {
+        Self {
+            corrections: ContactCorrections::default(),
+            jacobi_corrections: JacobiCorrections::default(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> Solver<K> {
+    /// Pushes the bodies out of `contacts` in the mode of `settings`.
+    /// Contacts still touching are kept, for the velocity pass and for gameplay, the others are removed
+    pub fn solve_positions<B: BodyPositions<K> + ?Sized>(
+        &mut self,
+        settings: &SolverSettings,
+        contacts: &mut Vec<(K, K, Contact)>,
+        bodies: &mut B,
+    ) {
+        match settings.mode {
+            SolverMode::GaussSeidel => self.solve_gauss_seidel(contacts, bodies),
+            SolverMode::Jacobi => self.solve_jacobi(settings.relaxation, contacts, bodies),
+        }
+    }
+
+    fn solve_gauss_seidel<B: BodyPositions<K> + ?Sized>(
+        &mut self,
+        contacts: &mut Vec<(K, K, Contact)>,
+        bodies: &mut B,
+    ) {
+        let corrections = &mut self.corrections;
+
+        corrections.clear();
+        contacts.retain_mut(|(a, b, contact)| {
+            let (Some(state_a), Some(state_b)) = (bodies.get(*a), bodies.get(*b)) else {
+                return false;
+            };
+            // solved on copies, so bodies that don't move are never written to
+            let (mut pos_a, mut pos_b) = (state_a.pos, state_b.pos);
+            let touching = corrections.solve(
+                contact,
+                state_a.contact_body(*a, &mut pos_a),
+                state_b.contact_body(*b, &mut pos_b),
+                ContactSoftness::combine(state_a.contact_softness, state_b.contact_softness),
+            );
+
+            if pos_a != state_a.pos {
+                bodies.set_pos(*a, pos_a);
+            }
+            if pos_b != state_b.pos {
+                bodies.set_pos(*b, pos_b);
+            }
+
+            touching
+        });
+    }
+
+    fn solve_jacobi<B: BodyPositions<K> + ?Sized>(
+        &mut self,
+        relaxation: f32,
+        contacts: &mut Vec<(K, K, Contact)>,
+        bodies: &mut B,
+    ) {
+        let corrections = &mut self.jacobi_corrections;
+
+        corrections.clear();
+        contacts.retain_mut(|(a, b, contact)| {
+            let (Some(state_a), Some(state_b)) = (bodies.get(*a), bodies.get(*b)) else {
+                return false;
+            };
+            // only read, the averaged corrections are applied once every contact was added
+            let (mut pos_a, mut pos_b) = (state_a.pos, state_b.pos);
+
+            corrections.add(
+                contact,
+                &state_a.contact_body(*a, &mut pos_a),
+                &state_b.contact_body(*b, &mut pos_b),
+                ContactSoftness::combine(state_a.contact_softness, state_b.contact_softness),
+            )
+        });
+
+        for (key, correction) in corrections.averaged(relaxation) {
+            if let Some(state) = bodies.get(key).filter(|_| correction != Vec2::ZERO) {
+                bodies.set_pos(key, state.pos + correction);
+            }
+        }
+
+        for (a, b, contact) in contacts.iter_mut() {
+            corrections.average_resolved(contact, *a, *b, relaxation);
+        }
+    }
+}
+
+/// Applies restitution and friction to the bodies of every contact, with the coefficients of both bodies
+/// combined by their rules. Contacts whose bodies are gone are skipped
+pub fn solve_velocities<K: Copy, B: BodyVelocities<K> + ?Sized>(
+    contacts: &[(K, K, Contact)],
+    restitution_threshold: f32,
+    bodies: &mut B,
+) {
+    for (a, b, contact) in contacts.iter() {
+        let (Some(state_a), Some(state_b)) = (bodies.get(*a), bodies.get(*b)) else {
+            continue;
+        };
+        let (mut vel_a, mut vel_b) = (state_a.vel, state_b.vel);
+
+        solve_contact_vel(
+            VelocityBody {
+                vel: &mut vel_a,
+                pre_solve_vel: state_a.pre_solve_vel,
+                inv_mass: state_a.inv_mass,
+            },
+            VelocityBody {
+                vel: &mut vel_b,
+                pre_solve_vel: state_b.pre_solve_vel,
+                inv_mass: state_b.inv_mass,
+            },
+            contact,
+            CombineRule::combine(
+                state_a.restitution_combine,
+                state_a.restitution,
+                state_b.restitution_combine,
+                state_b.restitution,
+            ),
+            CombineRule::combine(
+                state_a.friction_combine,
+                state_a.friction,
+                state_b.friction_combine,
+                state_b.friction,
+            ),
+            restitution_threshold,
+        );
+
+        if vel_a != state_a.vel {
+            bodies.set_vel(*a, vel_a);
+        }
+        if vel_b != state_b.vel {
+            bodies.set_vel(*b, vel_b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Bodies(Vec<PositionState>);
+
+    impl BodyPositions<usize> for Bodies {
+        fn get(&self, key: usize) -> Option<PositionState> {
+            self.0.get(key).copied()
+        }
+
+        fn set_pos(&mut self, key: usize, pos: Vec2) {
+            self.0[key].pos = pos;
+        }
+    }
+
+    fn broad_phase_body(key: usize, x: f32, group: Option<u8>) -> BroadPhaseBody<usize, u8> {
+        BroadPhaseBody {
+            key,
+            aabb: Aabb::from_center(Vec2::new(x, 0.), Vec2::ONE),
+            layers: CollisionLayers::default(),
+            group,
+        }
+    }
+
+    #[test]
+    fn collision_pairs_skip_bodies_of_the_same_group() {
+        let dynamics = [
+            broad_phase_body(0, 0., Some(1)),
+            broad_phase_body(1, 1., Some(1)),
+            broad_phase_body(2, 3., None),
+        ];
+        let ground = Bvh::build(vec![(3, Aabb::from_center(Vec2::ZERO, Vec2::ONE))]);
+        let mut pairs = vec![(0, 0)];
+
+        collect_collision_pairs(
+            &dynamics,
+            &ground,
+            |_| CollisionLayers::default(),
+            &mut pairs,
+        );
+
+        assert_eq!(pairs, [(0, 3), (1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn contacts_of_missing_bodies_are_dropped() {
+        let body = PositionState {
+            pos: Vec2::ZERO,
+            prev_pos: Vec2::ZERO,
+            inv_mass: Vec2::ONE,
+            contact_softness: ContactSoftness::default(),
+        };
+        let ground = PositionState {
+            pos: -Vec2::Y,
+            inv_mass: Vec2::ZERO,
+            ..body
+        };
+        let contact = Contact::with_point(-Vec2::Y, 0.1, Vec2::ZERO);
+
+        for mode in [SolverMode::GaussSeidel, SolverMode::Jacobi] {
+            let settings = SolverSettings {
+                mode,
+                ..SolverSettings::default()
+            };
+            let mut bodies = Bodies(vec![body, ground]);
+            let mut contacts = vec![(0, 1, contact), (0, 2, contact)];
+
+            Solver::default().solve_positions(&settings, &mut contacts, &mut bodies);
+
+            assert_eq!(contacts.len(), 1, "{mode:?}");
+            assert!(
+                bodies.0[0].pos.abs_diff_eq(Vec2::new(0., 0.1), 1e-6),
+                "{mode:?}"
+            );
+            assert_eq!(bodies.0[1].pos, -Vec2::Y, "{mode:?}");
+        }
+    }
+}
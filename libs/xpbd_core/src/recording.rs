@@ -0,0 +1,338 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use glam::Vec2;
+
+use super::terrain::BodyShape;
+
+const MAGIC: &[u8; 7] = b"XPBDREC";
+const VERSION: u8 = 2;
+
+const CIRCLE: u8 = 0;
+const BOX: u8 = 1;
+
+/// Shape of a recorded body, as parts at offsets from its position and lines for terrain
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordedBody {
+    pub id: u64,
+    pub is_static: bool,
+    pub parts: Vec<(Vec2, BodyShape)>,
+    pub lines: Vec<(Vec2, Vec2)>,
+}
+
+/// State of a body at the start of a step
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BodyState {
+    pub id: u64,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    /// Gravity and force fields pulling the body, contacts and constraints are left out
+    pub force: Vec2,
+    /// Velocity given to the body outside of the physics since the previous step,
+    /// e.g. by explosions, throws or the game setting it
+    pub vel_change: Vec2,
+}
+
+/// Everything that changed since the previous step
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordedStep {
+    /// Bodies spawned, or whose shape changed, from this step on
+    pub spawned: Vec<RecordedBody>,
+    pub despawned: Vec<u64>,
+    /// Every body alive during the step
+    pub states: Vec<BodyState>,
+}
+
+/// Physics steps read back from a recording
+#[derive(Debug, Default, Clone)]
+pub struct Recording {
+    pub steps: Vec<RecordedStep>,
+    // steps in which each body was spawned or changed shape, in order
+    bodies: HashMap<u64, Vec<usize>>,
+}
+
+impl Recording {
+    /// Reads a recording, leaving out a last step cut short, e.g. by a crash while writing it
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 7];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC || read_u8(reader)? != VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an xpbd recording of a supported version",
+            ));
+        }
+
+        let mut recording = Self::default();
+
+        loop {
+            match RecordedStep::read(reader) {
+                Ok(Some(step)) => recording.push(step),
+                Ok(None) => return Ok(recording),
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(recording),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub fn push(&mut self, step: RecordedStep) {
+        let index = self.steps.len();
+
+        for body in step.spawned.iter() {
+            self.bodies.entry(body.id).or_default().push(index);
+        }
+
+        self.steps.push(step);
+    }
+
+    /// Shape of a body during `step`
+    pub fn body(&self, id: u64, step: usize) -> Option<&RecordedBody> {
+        let changes = self.bodies.get(&id)?;
+        let change = changes[..changes.partition_point(|change| *change <= step)].last()?;
+
+        self.steps[*change]
+            .spawned
+            .iter()
+            .find(|body| body.id == id)
+    }
+
+    /// Bodies alive during `step` with their state
+    pub fn bodies_at(&self, step: usize) -> impl Iterator<Item = (&RecordedBody, &BodyState)> {
+        self.steps
+            .get(step)
+            .into_iter()
+            .flat_map(|step| step.states.iter())
+            .filter_map(move |state| Some((self.body(state.id, step)?, state)))
+    }
+}
+
+/// Writes the start of a recording, followed by its steps written with `RecordedStep::write`
+pub fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])
+}
+
+impl RecordedStep {
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_len(writer, self.spawned.len())?;
+
+        for body in self.spawned.iter() {
+            writer.write_all(&body.id.to_le_bytes())?;
+            writer.write_all(&[body.is_static as u8])?;
+            write_len(writer, body.parts.len())?;
+
+            for (offset, shape) in body.parts.iter() {
+                write_vec(writer, *offset)?;
+
+                match *shape {
+                    BodyShape::Circle { radius } => {
+                        writer.write_all(&[CIRCLE])?;
+                        writer.write_all(&radius.to_le_bytes())?;
+                    }
+                    BodyShape::Box { size } => {
+                        writer.write_all(&[BOX])?;
+                        write_vec(writer, size)?;
+                    }
+                }
+            }
+
+            write_len(writer, body.lines.len())?;
+
+            for (a, b) in body.lines.iter() {
+                write_vec(writer, *a)?;
+                write_vec(writer, *b)?;
+            }
+        }
+
+        write_len(writer, self.despawned.len())?;
+
+        for id in self.despawned.iter() {
+            writer.write_all(&id.to_le_bytes())?;
+        }
+
+        write_len(writer, self.states.len())?;
+
+        for state in self.states.iter() {
+            writer.write_all(&state.id.to_le_bytes())?;
+            write_vec(writer, state.pos)?;
+            write_vec(writer, state.vel)?;
+            write_vec(writer, state.force)?;
+            write_vec(writer, state.vel_change)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the next step, `None` at the end of the recording
+    pub fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut len = [0; 4];
+
+        // the end of the recording is only valid between two steps
+        if reader.read(&mut len[..1])? == 0 {
+            return Ok(None);
+        }
+
+        reader.read_exact(&mut len[1..])?;
+
+        let mut step = Self::default();
+
+        for _ in 0..u32::from_le_bytes(len) {
+            let mut body = RecordedBody {
+                id: read_u64(reader)?,
+                is_static: read_u8(reader)? != 0,
+                ..Default::default()
+            };
+
+            for _ in 0..read_u32(reader)? {
+                let offset = read_vec(reader)?;
+                let shape = match read_u8(reader)? {
+                    CIRCLE => BodyShape::Circle {
+                        radius: read_f32(reader)?,
+                    },
+                    BOX => BodyShape::Box {
+                        size: read_vec(reader)?,
+                    },
+                    _ => {
+                        return Err(io::Error::new(ErrorKind::InvalidData, "unknown shape"));
+                    }
+                };
+
+                body.parts.push((offset, shape));
+            }
+
+            for _ in 0..read_u32(reader)? {
+                body.lines.push((read_vec(reader)?, read_vec(reader)?));
+            }
+
+            step.spawned.push(body);
+        }
+
+        for _ in 0..read_u32(reader)? {
+            step.despawned.push(read_u64(reader)?);
+        }
+
+        for _ in 0..read_u32(reader)? {
+            step.states.push(BodyState {
+                id: read_u64(reader)?,
+                pos: read_vec(reader)?,
+                vel: read_vec(reader)?,
+                force: read_vec(reader)?,
+                vel_change: read_vec(reader)?,
+            });
+        }
+
+        Ok(Some(step))
+    }
+}
+
+fn write_len(writer: &mut impl Write, len: usize) -> io::Result<()> {
+    writer.write_all(&(len as u32).to_le_bytes())
+}
+
+fn write_vec(writer: &mut impl Write, vec: Vec2) -> io::Result<()> {
+    writer.write_all(&vec.x.to_le_bytes())?;
+    writer.write_all(&vec.y.to_le_bytes())
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    Ok(read_bytes::<1>(reader)?[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_vec(reader: &mut impl Read) -> io::Result<Vec2> {
+    Ok(Vec2::new(read_f32(reader)?, read_f32(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_round_trips_and_survives_truncation() {
+        let body = RecordedBody {
+            id: 7,
+            is_static: false,
+            parts: vec![
+                (Vec2::ZERO, BodyShape::Circle { radius: 0.5 }),
+                (Vec2::new(1., 0.), BodyShape::Box { size: Vec2::ONE }),
+            ],
+            lines: vec![(Vec2::ZERO, Vec2::X)],
+        };
+        let frozen = RecordedBody {
+            is_static: true,
+            ..body.clone()
+        };
+        let state = |x| BodyState {
+            id: 7,
+            pos: Vec2::new(x, 1.),
+            vel: Vec2::X,
+            force: Vec2::new(0., -9.81),
+            vel_change: Vec2::ZERO,
+        };
+        let steps = [
+            RecordedStep {
+                spawned: vec![body.clone()],
+                despawned: Vec::new(),
+                states: vec![state(0.)],
+            },
+            RecordedStep {
+                spawned: Vec::new(),
+                despawned: vec![3],
+                states: vec![state(1.)],
+            },
+            // frozen into the level
+            RecordedStep {
+                spawned: vec![frozen.clone()],
+                despawned: Vec::new(),
+                states: vec![state(2.)],
+            },
+        ];
+        let mut bytes = Vec::new();
+
+        write_header(&mut bytes).unwrap();
+        for step in steps.iter() {
+            step.write(&mut bytes).unwrap();
+        }
+
+        let recording = Recording::read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(recording.steps, steps);
+        assert_eq!(
+            recording.bodies_at(1).collect::<Vec<_>>(),
+            [(&body, &state(1.))]
+        );
+        assert_eq!(
+            recording.bodies_at(2).collect::<Vec<_>>(),
+            [(&frozen, &state(2.))]
+        );
+
+        // a crash while writing the last step loses only that step
+        let truncated = Recording::read(&mut &bytes[..bytes.len() - 3]).unwrap();
+
+        assert_eq!(truncated.steps.len(), 2);
+        assert!(Recording::read(&mut &b"XPBDREC\x09"[..]).is_err());
+    }
+}
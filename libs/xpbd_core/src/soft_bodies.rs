@@ -0,0 +1,81 @@
+use glam::Vec2;
+
+/// Moves `positions` towards the best rotated and translated fit of `rest_offsets`.
+/// Particles with zero inverse mass are not moved, but still pin the fit
+pub fn shape_match(
+    rest_offsets: &[Vec2],
+    positions: &mut [Vec2],
+    masses: &[f32],
+    inv_masses: &[f32],
+    stiffness: f32,
+) {
+    let total_mass: f32 = masses.iter().sum();
+
+    if total_mass <= 0. {
+        return;
+    }
+
+    let center = positions
+        .iter()
+        .zip(masses)
+        .map(|(pos, mass)| *pos * *mass)
+        .sum::<Vec2>()
+        / total_mass;
+
+    // rotation of the 2d polar decomposition of sum(m * p * q^T)
+    let (sin, cos) = positions.iter().zip(rest_offsets).zip(masses).fold(
+        (0., 0.),
+        |(sin, cos), ((pos, rest), mass)| {
+            let p = *pos - center;
+
+            (sin + mass * rest.perp_dot(p), cos + mass * rest.dot(p))
+        },
+    );
+    let rotation = Vec2::new(cos, sin).normalize_or_zero();
+    let rotation = if rotation == Vec2::ZERO {
+        Vec2::X
+    } else {
+        rotation
+    };
+
+    for ((pos, rest), inv_mass) in positions.iter_mut().zip(rest_offsets).zip(inv_masses) {
+        if *inv_mass <= 0. {
+            continue;
+        }
+
+        let goal = center + rotation.rotate(*rest);
+
+        *pos += (goal - *pos) * stiffness;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_match_restores_rotated_shape() {
+        let rest = [
+            Vec2::new(-1., -1.),
+            Vec2::new(1., -1.),
+            Vec2::new(1., 1.),
+            Vec2::new(-1., 1.),
+        ];
+        let rotation = Vec2::from_angle(0.5);
+        let translation = Vec2::new(10., 5.);
+        // squashed in its own frame, which doesn't change the best fitting rotation
+        let mut positions: Vec<_> = rest
+            .iter()
+            .map(|rest| translation + rotation.rotate(*rest * Vec2::new(1.3, 0.7)))
+            .collect();
+
+        shape_match(&rest, &mut positions, &[1.; 4], &[1.; 4], 1.);
+
+        for (pos, rest) in positions.iter().zip(rest) {
+            assert!(
+                pos.abs_diff_eq(translation + rotation.rotate(rest), 0.001),
+                "{pos:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,667 @@
+use std::{collections::HashMap, hash::Hash};
+
+#[cfg(feature = "bevy")]
+use bevy::prelude::{FromReflect, Reflect, ReflectResource, Resource};
+use glam::Vec2;
+
+use super::{
+    body::{ContactSoftness, LockedAxes, MaxLinearSpeed},
+    consts::SUB_DT,
+    contact::Contact,
+};
+
+/// How the contacts of a substep are projected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+pub enum SolverMode {
+    /// One contact after the other, each seeing the corrections of the ones before.
+    /// Converges fast, but piles lean towards the contacts solved last
+    #[default]
+    GaussSeidel,
+    /// Every contact from the same positions, bodies moving by the average of their corrections.
+    /// Independent of the order of the contacts, but slower to push bodies apart
+    Jacobi,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource, Reflect), reflect(Resource))]
+pub struct SolverSettings {
+    pub mode: SolverMode,
+    /// Scale of the averaged corrections in `SolverMode::Jacobi`.
+    /// Above one pushes bodies apart faster, but piles start to jitter and blow up well before two
+    pub relaxation: f32,
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        Self {
+            mode: SolverMode::GaussSeidel,
+            relaxation: 1.,
+        }
+    }
+}
+
+/// Moves a body by one substep of semi-implicit euler under `force`
+pub fn integrate(pos: &mut Vec2, vel: &mut Vec2, force: Vec2, inv_mass: f32) {
+    *vel += SUB_DT * force * inv_mass;
+    *pos += SUB_DT * *vel;
+}
+
+/// Velocity of a body that moved from `prev_pos` to `pos` during the substep
+pub fn derive_vel(pos: Vec2, prev_pos: Vec2) -> Vec2 {
+    (pos - prev_pos) / SUB_DT
+}
+
+/// Keeps the move of a body from `prev_pos` within its locked axes and speed limit,
+/// after it was integrated and again after its velocity was derived from the solved position
+pub fn limit_motion(
+    pos: &mut Vec2,
+    prev_pos: Vec2,
+    vel: &mut Vec2,
+    locked_axes: LockedAxes,
+    max_speed: MaxLinearSpeed,
+) {
+    let limited = limited_vel(*vel, locked_axes, max_speed);
+
+    // left untouched otherwise, so that unlimited bodies move exactly as before
+    if limited != *vel {
+        *vel = limited;
+        *pos = prev_pos + limited * SUB_DT;
+    }
+}
+
+/// `vel` within the locked axes and speed limit of a body, e.g. after the velocity solve bounced it
+pub fn limited_vel(vel: Vec2, locked_axes: LockedAxes, max_speed: MaxLinearSpeed) -> Vec2 {
+    locked_axes.apply(max_speed.clamp(vel))
+}
+
+// inverse mass of a body along `dir`, from its inverse mass per axis
+fn inv_mass_along(inv_mass: Vec2, dir: Vec2) -> f32 {
+    inv_mass.dot(dir * dir)
+}
+
+/// Pushes both bodies apart along the contact normal proportionally to their inverse masses per axis,
+/// see `LockedAxes::inv_mass`. Returns `false` when neither body can be moved along the normal
+pub fn constrain_positions(
+    pos_a: &mut Vec2,
+    pos_b: &mut Vec2,
+    inv_mass_a: Vec2,
+    inv_mass_b: Vec2,
+    normal: Vec2,
+    penetration: f32,
+) -> bool {
+    let w_sum = inv_mass_along(inv_mass_a + inv_mass_b, normal);
+
+    if w_sum <= 0. {
+        return false;
+    }
+
+    let pos_impulse = normal * (-penetration / w_sum);
+
+    *pos_a += pos_impulse * inv_mass_a;
+    *pos_b -= pos_impulse * inv_mass_b;
+
+    true
+}
+
+/// Keeps two bodies at `rest_length` from each other, `compliance` being the inverse stiffness.
+/// Inverse masses are per axis like in `constrain_positions`
+pub fn constrain_distance(
+    pos_a: &mut Vec2,
+    pos_b: &mut Vec2,
+    inv_mass_a: Vec2,
+    inv_mass_b: Vec2,
+    rest_length: f32,
+    compliance: f32,
+) {
+    let delta = *pos_a - *pos_b;
+    let length = delta.length();
+
+    if length <= f32::EPSILON {
+        return;
+    }
+
+    let dir = delta / length;
+    let w_sum = inv_mass_along(inv_mass_a + inv_mass_b, dir);
+
+    if w_sum <= 0. {
+        return;
+    }
+
+    // the lagrange multiplier starts at zero every substep, so a single iteration needs no accumulation
+    let c = length - rest_length;
+    let alpha = compliance / (SUB_DT * SUB_DT);
+    let lambda = -c / (w_sum + alpha);
+    let correction = dir * lambda;
+
+    *pos_a += correction * inv_mass_a;
+    *pos_b -= correction * inv_mass_b;
+}
+
+/// Position of one of the bodies of a contact, identified by `key`
+pub struct ContactBody<'a, K> {
+    pub key: K,
+    pub pos: &'a mut Vec2,
+    /// Position at the start of the substep
+    pub prev_pos: Vec2,
+    /// Inverse mass per axis, see `LockedAxes::inv_mass`
+    pub inv_mass: Vec2,
+}
+
+// how much closer the bodies of a contact got along its normal during the substep
+fn approach<K>(normal: Vec2, a: &ContactBody<'_, K>, b: &ContactBody<'_, K>) -> f32 {
+    ((*a.pos - a.prev_pos) - (*b.pos - b.prev_pos)).dot(normal)
+}
+
+/// Sum of the position corrections every body got from the contacts solved so far in a substep
+#[derive(Debug)]
+pub struct ContactCorrections<K> {
+    corrections: HashMap<K, Vec2>,
+}
+
+impl<K> Default for ContactCorrections<K> {
+    fn default() -> Self {
+        Self {
+            corrections: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> ContactCorrections<K> {
+    pub fn clear(&mut self) {
+        self.corrections.clear();
+    }
+
+    fn get(&self, key: K) -> Vec2 {
+        self.corrections.get(&key).copied().unwrap_or_default()
+    }
+
+    /// Pushes the bodies out of `contact`, updating its penetration with what earlier contacts
+    /// of the same bodies already resolved. Contacts are kept like in `JacobiCorrections::add`,
+    /// those resolved by earlier ones still touch and are left with zero penetration
+    pub fn solve(
+        &mut self,
+        contact: &mut Contact,
+        a: ContactBody<'_, K>,
+        b: ContactBody<'_, K>,
+        softness: ContactSoftness,
+    ) -> bool {
+        let overlapped = contact.penetration > 0.;
+
+        contact.penetration -= (self.get(b.key) - self.get(a.key)).dot(contact.normal);
+
+        if contact.penetration <= 0. {
+            return keep_unresolved(contact, overlapped);
+        }
+
+        let start_a = *a.pos;
+        let start_b = *b.pos;
+        let resolved = softness.resolved(
+            contact.penetration,
+            approach(contact.normal, &a, &b),
+            inv_mass_along(a.inv_mass + b.inv_mass, contact.normal),
+        );
+
+        if !constrain_positions(
+            a.pos,
+            b.pos,
+            a.inv_mass,
+            b.inv_mass,
+            contact.normal,
+            resolved,
+        ) {
+            return keep_unresolved(contact, true);
+        }
+
+        contact.resolved = resolved;
+        *self.corrections.entry(a.key).or_default() += *a.pos - start_a;
+        *self.corrections.entry(b.key).or_default() += *b.pos - start_b;
+
+        true
+    }
+}
+
+/// Position corrections of the contacts of a substep summed per body, for `SolverMode::Jacobi`
+#[derive(Debug)]
+pub struct JacobiCorrections<K> {
+    corrections: HashMap<K, (Vec2, u32)>,
+}
+
+impl<K> Default for JacobiCorrections<K> {
+    fn default() -> Self {
+        Self {
+            corrections: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> JacobiCorrections<K> {
+    pub fn clear(&mut self) {
+        self.corrections.clear();
+    }
+
+    /// Adds what pushing the bodies out of `contact` would move them by, without moving them.
+    /// Contacts between bodies that can't be moved along the normal still touch and are kept with nothing resolved.
+    /// Returns `false` for contacts that didn't overlap when found, with nothing left to push out
+    pub fn add(
+        &mut self,
+        contact: &mut Contact,
+        a: &ContactBody<'_, K>,
+        b: &ContactBody<'_, K>,
+        softness: ContactSoftness,
+    ) -> bool {
+        let mut delta_a = Vec2::ZERO;
+        let mut delta_b = Vec2::ZERO;
+        let overlapped = contact.penetration > 0.;
+
+        if contact.penetration <= 0. {
+            return keep_unresolved(contact, overlapped);
+        }
+
+        let resolved = softness.resolved(
+            contact.penetration,
+            approach(contact.normal, a, b),
+            inv_mass_along(a.inv_mass + b.inv_mass, contact.normal),
+        );
+
+        if !constrain_positions(
+            &mut delta_a,
+            &mut delta_b,
+            a.inv_mass,
+            b.inv_mass,
+            contact.normal,
+            resolved,
+        ) {
+            return keep_unresolved(contact, true);
+        }
+
+        contact.resolved = resolved;
+
+        for (key, inv_mass, delta) in [(a.key, a.inv_mass, delta_a), (b.key, b.inv_mass, delta_b)] {
+            if inv_mass_along(inv_mass, contact.normal) > 0. {
+                let (sum, count) = self.corrections.entry(key).or_default();
+
+                *sum += delta;
+                *count += 1;
+            }
+        }
+
+        true
+    }
+
+    /// Average correction of every body that got one, scaled by `relaxation`
+    pub fn averaged(&self, relaxation: f32) -> impl Iterator<Item = (K, Vec2)> + '_ {
+        self.corrections
+            .iter()
+            .map(move |(key, (sum, count))| (*key, *sum * relaxation / *count as f32))
+    }
+
+    /// Part of what `contact` resolved that the averaged corrections push out, as bodies only move
+    /// by the average of their contacts. Keeps the friction of a body resting on several contacts
+    /// the same as in `SolverMode::GaussSeidel`, where the first one resolves all of the penetration
+    pub fn average_resolved(&self, contact: &mut Contact, a: K, b: K, relaxation: f32) {
+        let count = |key| self.corrections.get(&key).map_or(1, |(_, count)| *count);
+
+        contact.resolved *= relaxation / count(a).max(count(b)) as f32;
+    }
+}
+
+// both solver modes keep the contacts that overlapped when found but push nothing out,
+// for the velocity pass and for gameplay
+fn keep_unresolved(contact: &mut Contact, overlapped: bool) -> bool {
+    contact.penetration = contact.penetration.max(0.);
+    contact.resolved = 0.;
+
+    overlapped
+}
+
+/// Velocities of one of the bodies of a contact
+pub struct VelocityBody<'a> {
+    pub vel: &'a mut Vec2,
+    /// Velocity before the positions were solved, restitution is relative to it
+    pub pre_solve_vel: Vec2,
+    /// Inverse mass per axis, see `LockedAxes::inv_mass`
+    pub inv_mass: Vec2,
+}
+
+/// Applies restitution and friction to the bodies of a contact.
+/// Bodies hitting each other slower than `restitution_threshold` don't bounce,
+/// and neither body is changed when neither can be moved along the normal
+pub fn solve_contact_vel(
+    a: VelocityBody<'_>,
+    b: VelocityBody<'_>,
+    contact: &Contact,
+    restitution: f32,
+    friction: f32,
+    restitution_threshold: f32,
+) {
+    let n = contact.normal;
+    let w_sum = inv_mass_along(a.inv_mass + b.inv_mass, n);
+
+    if w_sum <= 0. {
+        return;
+    }
+
+    let pre_solve_relative_vel = a.pre_solve_vel - b.pre_solve_vel;
+    let pre_solve_normal_vel = Vec2::dot(pre_solve_relative_vel, n);
+
+    let relative_vel = *a.vel - *b.vel;
+    let normal_vel = Vec2::dot(relative_vel, n);
+    let surface_vel = contact.surface_vel - n * contact.surface_vel.dot(n);
+    let tangent_vel = relative_vel - n * normal_vel - surface_vel;
+
+    let restitution = if pre_solve_normal_vel.abs() < restitution_threshold {
+        0.
+    } else {
+        restitution
+    };
+
+    // bodies already moving apart, e.g. jumping up through a one-sided platform
+    if contact.one_sided && normal_vel < 0. {
+        return;
+    }
+
+    let normal_delta = -normal_vel - restitution * pre_solve_normal_vel;
+
+    // dynamic friction is bounded by the normal impulse. Resting contacts barely need a velocity
+    // correction, their impulse is what the position solve pushed the bodies apart by
+    let normal_impulse = normal_delta.abs().max(contact.resolved / SUB_DT);
+    let tangent_speed = tangent_vel.length();
+    let friction_delta = if tangent_speed > f32::EPSILON {
+        -tangent_vel / tangent_speed * (friction * normal_impulse).min(tangent_speed)
+    } else {
+        Vec2::ZERO
+    };
+
+    let delta_vel = n * normal_delta + friction_delta;
+
+    *a.vel += delta_vel * a.inv_mass / w_sum;
+    *b.vel -= delta_vel * b.inv_mass / w_sum;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrections_carry_over_between_contacts() {
+        let mut corrections = ContactCorrections::default();
+        let mut pos_a = Vec2::ZERO;
+        let mut pos_b = Vec2::new(1., 0.);
+        let mut first = Contact::with_point(Vec2::X, 0.4, Vec2::ZERO);
+        let mut second = first;
+
+        assert!(corrections.solve(
+            &mut first,
+            ContactBody {
+                key: 0,
+                pos: &mut pos_a,
+                prev_pos: Vec2::ZERO,
+                inv_mass: Vec2::ONE,
+            },
+            ContactBody {
+                key: 1,
+                pos: &mut pos_b,
+                prev_pos: Vec2::new(1., 0.),
+                inv_mass: Vec2::ONE,
+            },
+            ContactSoftness::default(),
+        ));
+        assert!(pos_a.abs_diff_eq(Vec2::new(-0.2, 0.), 1e-6));
+        assert!(pos_b.abs_diff_eq(Vec2::new(1.2, 0.), 1e-6));
+
+        // the same penetration reported twice is only solved once, but still touches
+        assert!(corrections.solve(
+            &mut second,
+            ContactBody {
+                key: 0,
+                pos: &mut pos_a,
+                prev_pos: Vec2::ZERO,
+                inv_mass: Vec2::ONE,
+            },
+            ContactBody {
+                key: 1,
+                pos: &mut pos_b,
+                prev_pos: Vec2::new(1., 0.),
+                inv_mass: Vec2::ONE,
+            },
+            ContactSoftness::default(),
+        ));
+        assert!(pos_b.abs_diff_eq(Vec2::new(1.2, 0.), 1e-6));
+        assert_eq!(second.penetration, 0.);
+    }
+
+    #[test]
+    fn contacts_between_immovable_bodies_are_kept_unresolved() {
+        let mut corrections = ContactCorrections::default();
+        let (mut pos_a, mut pos_b) = (Vec2::ZERO, Vec2::X);
+        let mut contact = Contact {
+            resolved: 0.3,
+            ..Contact::with_point(Vec2::X, 0.4, Vec2::ZERO)
+        };
+
+        // a door locked sideways against a static wall
+        assert!(corrections.solve(
+            &mut contact,
+            ContactBody {
+                key: 0,
+                pos: &mut pos_a,
+                prev_pos: Vec2::ZERO,
+                inv_mass: LockedAxes::default().lock_translation_x().inv_mass(1.),
+            },
+            ContactBody {
+                key: 1,
+                pos: &mut pos_b,
+                prev_pos: Vec2::X,
+                inv_mass: Vec2::ZERO,
+            },
+            ContactSoftness::default(),
+        ));
+        assert_eq!((pos_a, pos_b), (Vec2::ZERO, Vec2::X));
+        assert_eq!(contact.resolved, 0.);
+    }
+
+    #[test]
+    fn jacobi_corrections_are_averaged() {
+        let mut corrections = JacobiCorrections::default();
+        let mut contact = Contact::with_point(Vec2::X, 0.4, Vec2::ZERO);
+        let (mut pos_a, mut pos_b) = (Vec2::ZERO, Vec2::X);
+        let a = ContactBody {
+            key: 0,
+            pos: &mut pos_a,
+            prev_pos: Vec2::ZERO,
+            inv_mass: Vec2::ONE,
+        };
+        let b = ContactBody {
+            key: 1,
+            pos: &mut pos_b,
+            prev_pos: Vec2::X,
+            inv_mass: Vec2::ZERO,
+        };
+        let rigid = ContactSoftness::default();
+
+        // the same contact twice moves the bodies as much as once, whatever the order
+        assert!(corrections.add(&mut contact, &a, &b, rigid));
+        assert!(corrections.add(&mut contact, &a, &b, rigid));
+        assert!(!corrections.add(
+            &mut Contact::with_point(Vec2::X, 0., Vec2::ZERO),
+            &a,
+            &b,
+            rigid
+        ));
+        // kept like in `ContactCorrections::solve`, without moving anything
+        assert!(corrections.add(&mut contact, &b, &b, rigid));
+        assert_eq!(contact.resolved, 0.);
+
+        let averaged: Vec<_> = corrections.averaged(1.).collect();
+
+        assert_eq!(averaged.len(), 1);
+        assert_eq!(averaged[0].0, 0);
+        assert!(averaged[0].1.abs_diff_eq(Vec2::new(-0.4, 0.), 1e-6));
+    }
+
+    #[test]
+    fn motion_limits() {
+        let prev_pos = Vec2::new(1., 1.);
+        let mut pos = prev_pos + Vec2::new(3., 4.) * SUB_DT;
+        let mut vel = Vec2::new(3., 4.);
+
+        limit_motion(
+            &mut pos,
+            prev_pos,
+            &mut vel,
+            LockedAxes::default().lock_translation_y(),
+            MaxLinearSpeed(2.),
+        );
+
+        assert!(vel.abs_diff_eq(Vec2::new(1.2, 0.), 1e-5), "{vel:?}");
+        assert_eq!(pos.y, prev_pos.y);
+        assert!((pos.x - (prev_pos.x + 1.2 * SUB_DT)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn only_one_sided_contacts_let_go_of_separating_bodies() {
+        // a ball that hit the ground at 10 and was already pushed out upwards
+        let bounce = |one_sided| {
+            let mut vel = Vec2::new(0., 2.);
+            let mut ground_vel = Vec2::ZERO;
+            let contact = Contact {
+                one_sided,
+                ..Contact::with_point(-Vec2::Y, 0., Vec2::ZERO)
+            };
+
+            solve_contact_vel(
+                VelocityBody {
+                    vel: &mut vel,
+                    pre_solve_vel: Vec2::new(0., -10.),
+                    inv_mass: Vec2::ONE,
+                },
+                VelocityBody {
+                    vel: &mut ground_vel,
+                    pre_solve_vel: Vec2::ZERO,
+                    inv_mass: Vec2::ZERO,
+                },
+                &contact,
+                0.5,
+                0.,
+                1.,
+            );
+
+            vel
+        };
+
+        assert_eq!(bounce(false), Vec2::new(0., 5.));
+        assert_eq!(bounce(true), Vec2::new(0., 2.));
+    }
+
+    #[test]
+    fn resting_friction_is_bounded_by_the_position_correction() {
+        // a ball sliding along the ground, held up by the position solve alone
+        let slide = |resolved| {
+            let mut vel = Vec2::new(3., 0.);
+            let mut ground_vel = Vec2::ZERO;
+            let contact = Contact {
+                resolved,
+                ..Contact::with_point(-Vec2::Y, resolved, Vec2::ZERO)
+            };
+
+            solve_contact_vel(
+                VelocityBody {
+                    vel: &mut vel,
+                    pre_solve_vel: Vec2::new(3., 0.),
+                    inv_mass: Vec2::ONE,
+                },
+                VelocityBody {
+                    vel: &mut ground_vel,
+                    pre_solve_vel: Vec2::ZERO,
+                    inv_mass: Vec2::ZERO,
+                },
+                &contact,
+                0.,
+                0.5,
+                1.,
+            );
+
+            vel
+        };
+
+        assert!(slide(0.001).abs_diff_eq(Vec2::new(3. - 0.5 * 0.001 / SUB_DT, 0.), 1e-5));
+        // resolved by other contacts, so pressed by none of its own
+        assert_eq!(slide(0.), Vec2::new(3., 0.));
+    }
+
+    #[test]
+    fn contacts_between_immovable_bodies_keep_their_velocities() {
+        // a ball on a rail that only moves sideways, resting on a static floor
+        let mut vel = Vec2::new(3., -2.);
+        let mut ground_vel = Vec2::ZERO;
+
+        solve_contact_vel(
+            VelocityBody {
+                vel: &mut vel,
+                pre_solve_vel: Vec2::new(3., -2.),
+                inv_mass: LockedAxes::default().lock_translation_y().inv_mass(1.),
+            },
+            VelocityBody {
+                vel: &mut ground_vel,
+                pre_solve_vel: Vec2::ZERO,
+                inv_mass: Vec2::ZERO,
+            },
+            &Contact::with_point(-Vec2::Y, 0.1, Vec2::ZERO),
+            0.5,
+            0.5,
+            1.,
+        );
+
+        assert_eq!(vel, Vec2::new(3., -2.));
+        assert_eq!(ground_vel, Vec2::ZERO);
+    }
+
+    #[test]
+    fn distance_constraint_splits_correction_by_inverse_mass() {
+        let mut pos_a = Vec2::ZERO;
+        let mut pos_b = Vec2::new(3., 0.);
+
+        constrain_distance(&mut pos_a, &mut pos_b, Vec2::ONE, Vec2::ZERO, 2., 0.);
+
+        assert!(pos_a.abs_diff_eq(Vec2::new(1., 0.), 1e-6));
+        assert_eq!(pos_b, Vec2::new(3., 0.));
+    }
+
+    #[test]
+    fn locked_axes_are_never_moved_by_projections() {
+        // a body on a vertical rail, pushed out of a slope and pulled sideways by a rope
+        let locked = LockedAxes::default().lock_translation_x().inv_mass(1.);
+        let normal = Vec2::new(1., 1.).normalize();
+        let mut pos_a = Vec2::ZERO;
+        let mut pos_b = Vec2::ZERO;
+
+        assert!(constrain_positions(
+            &mut pos_a,
+            &mut pos_b,
+            locked,
+            Vec2::ZERO,
+            normal,
+            0.1
+        ));
+        assert_eq!(pos_a.x, 0.);
+        // all of the penetration is still resolved along the normal
+        assert!(((pos_b - pos_a).dot(normal) - 0.1).abs() < 1e-6);
+        assert!(!constrain_positions(
+            &mut pos_a,
+            &mut pos_b,
+            locked,
+            Vec2::ZERO,
+            Vec2::X,
+            0.1
+        ));
+
+        let mut pos_a = Vec2::ZERO;
+        let mut pos_b = Vec2::new(3., 1.);
+
+        constrain_distance(&mut pos_a, &mut pos_b, locked, Vec2::ZERO, 2., 0.);
+
+        assert_eq!(pos_a.x, 0.);
+        assert!(pos_a.y > 0.);
+    }
+}
@@ -0,0 +1,729 @@
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, FromReflect, Reflect, ReflectComponent};
+use glam::{IVec2, UVec2, Vec2};
+
+use super::{
+    colliders::{BoxCollider, CircleCollider, ColliderShape},
+    contact::{
+        ball_ball, ball_box, ball_segment, box_box, box_segment, ray_ball, ray_box, ray_segment,
+        segment_normal, Contact,
+    },
+};
+
+// how close to the target depth a cast gets before it counts as a hit
+const CAST_TOLERANCE: f32 = 1e-4;
+const MAX_CAST_ITERATIONS: usize = 32;
+
+/// Shape of a dynamic body tested against terrain
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Reflect, FromReflect))]
+pub enum BodyShape {
+    Circle { radius: f32 },
+    Box { size: Vec2 },
+}
+
+impl BodyShape {
+    pub fn half_extents(&self) -> Vec2 {
+        match *self {
+            BodyShape::Circle { radius } => Vec2::splat(radius),
+            BodyShape::Box { size } => size / 2.,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match *self {
+            BodyShape::Circle { radius } => CircleCollider { radius }.area(),
+            BodyShape::Box { size } => BoxCollider { size }.area(),
+        }
+    }
+
+    pub fn unit_inertia(&self) -> f32 {
+        match *self {
+            BodyShape::Circle { radius } => CircleCollider { radius }.unit_inertia(),
+            BodyShape::Box { size } => BoxCollider { size }.unit_inertia(),
+        }
+    }
+
+    /// Ray from `origin` along `delta` against this shape at `pos`, see `ray_ball`
+    pub fn cast_ray(&self, pos: Vec2, origin: Vec2, delta: Vec2) -> Option<(f32, Vec2)> {
+        match *self {
+            BodyShape::Circle { radius } => ray_ball(origin, delta, pos, radius),
+            BodyShape::Box { size } => ray_box(origin, delta, pos, size),
+        }
+    }
+
+    /// Contact between this shape at `pos` and `other` at `other_pos`, with the normal pointing towards `other`
+    pub fn contact(&self, pos: Vec2, other: &BodyShape, other_pos: Vec2) -> Option<Contact> {
+        match (*self, *other) {
+            (
+                BodyShape::Circle { radius },
+                BodyShape::Circle {
+                    radius: other_radius,
+                },
+            ) => ball_ball(pos, radius, other_pos, other_radius),
+            (BodyShape::Circle { radius }, BodyShape::Box { size }) => {
+                ball_box(pos, radius, other_pos, size)
+            }
+            (BodyShape::Box { size }, BodyShape::Circle { radius }) => {
+                ball_box(other_pos, radius, pos, size).map(Contact::flipped)
+            }
+            (BodyShape::Box { size }, BodyShape::Box { size: other_size }) => {
+                box_box(pos, size, other_pos, other_size)
+            }
+        }
+    }
+
+    /// Distance to `part`, negative inside it, and the direction from the shape towards it
+    pub fn signed_distance(&self, pos: Vec2, part: &ConvexPart) -> (f32, Vec2) {
+        let contact = match *part {
+            ConvexPart::Shape {
+                shape,
+                pos: part_pos,
+            } => self.contact(pos, &shape, part_pos),
+            ConvexPart::Segment { a, b, .. } => segment_contact(pos, pos, *self, a, b, false),
+        };
+
+        if let Some(contact) = contact {
+            return (-contact.penetration, contact.normal);
+        }
+
+        self.separation(pos, part)
+    }
+
+    // distance to `part` when they don't overlap, and the direction towards it
+    fn separation(&self, pos: Vec2, part: &ConvexPart) -> (f32, Vec2) {
+        // from a point of the shape to the closest point of a part rounded by `radius`
+        let rounded = |from: Vec2, to: Vec2, radius: f32| {
+            let gap = to - from;
+
+            (gap.length() - radius, gap.normalize_or_zero())
+        };
+
+        match (*self, *part) {
+            (BodyShape::Circle { radius }, part) => {
+                let (closest, part_radius) = match part {
+                    ConvexPart::Shape {
+                        shape: BodyShape::Circle { radius },
+                        pos: part_pos,
+                    } => (part_pos, radius),
+                    ConvexPart::Shape {
+                        shape: BodyShape::Box { size },
+                        pos: part_pos,
+                    } => (pos.clamp(part_pos - size / 2., part_pos + size / 2.), 0.),
+                    ConvexPart::Segment { a, b, .. } => (closest_on_segment(pos, a, b), 0.),
+                };
+
+                rounded(pos, closest, radius + part_radius)
+            }
+            (
+                BodyShape::Box { size },
+                ConvexPart::Shape {
+                    shape: BodyShape::Circle { radius },
+                    pos: part_pos,
+                },
+            ) => rounded(
+                part_pos.clamp(pos - size / 2., pos + size / 2.),
+                part_pos,
+                radius,
+            ),
+            (
+                BodyShape::Box { size },
+                ConvexPart::Shape {
+                    shape: BodyShape::Box { size: part_size },
+                    pos: part_pos,
+                },
+            ) => {
+                let offset = part_pos - pos;
+                let gaps = offset.abs() - (size + part_size) / 2.;
+                let gap = gaps.max(Vec2::ZERO) * offset.signum();
+
+                // boxes touching along an edge are apart along the axis of that edge
+                if gap == Vec2::ZERO {
+                    let axis = if gaps.x > gaps.y { Vec2::X } else { Vec2::Y };
+
+                    return (0., axis * offset.signum());
+                }
+
+                (gap.length(), gap.normalize())
+            }
+            // the closest points of a box and a segment apart are an end of one of them
+            (BodyShape::Box { size }, ConvexPart::Segment { a, b, .. }) => {
+                let half_extents = size / 2.;
+                let ends = [a, b]
+                    .into_iter()
+                    .map(|end| end - end.clamp(pos - half_extents, pos + half_extents));
+                let corners = [
+                    Vec2::new(-1., -1.),
+                    Vec2::new(1., -1.),
+                    Vec2::new(1., 1.),
+                    Vec2::new(-1., 1.),
+                ]
+                .into_iter()
+                .map(|corner| {
+                    let corner = pos + corner * half_extents;
+
+                    closest_on_segment(corner, a, b) - corner
+                });
+                let gap = ends
+                    .chain(corners)
+                    .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+                    .unwrap_or_default();
+
+                if gap == Vec2::ZERO {
+                    return (
+                        0.,
+                        (closest_on_segment(pos, a, b) - pos).normalize_or_zero(),
+                    );
+                }
+
+                (gap.length(), gap.normalize())
+            }
+        }
+    }
+
+    /// Fraction of `delta` the shape moves from `pos` before it is `depth` inside `part`,
+    /// with the normal pointing towards the part there. Both are convex, so the distance between them only
+    /// shrinks while the shape moves towards the part: the shape is advanced by that distance at each step,
+    /// which never steps over a thin part however long the cast is
+    pub fn cast(
+        &self,
+        pos: Vec2,
+        delta: Vec2,
+        part: &ConvexPart,
+        depth: f32,
+    ) -> Option<(f32, Vec2)> {
+        // one-sided segments only stop shapes coming from their front
+        if let ConvexPart::Segment {
+            a,
+            b,
+            one_sided: true,
+        } = *part
+        {
+            let normal = segment_normal(a, b);
+
+            if (pos - a).dot(normal) < 0. || delta.dot(normal) >= 0. {
+                return None;
+            }
+        }
+
+        // far from the origin, positions can't get closer to the target than a few of their last bits
+        let tolerance =
+            CAST_TOLERANCE.max((pos.abs() + delta.abs()).max_element() * 4. * f32::EPSILON);
+        let mut fraction = 0.;
+
+        for _ in 0..MAX_CAST_ITERATIONS {
+            let (distance, normal) = self.signed_distance(pos + delta * fraction, part);
+            let approach = normal.dot(delta);
+
+            if approach <= 0. {
+                return None;
+            }
+
+            let remaining = distance + depth;
+
+            if remaining <= tolerance {
+                return Some((fraction, normal));
+            }
+
+            fraction += remaining / approach;
+
+            if fraction > 1. {
+                return None;
+            }
+        }
+
+        None
+    }
+}
+
+fn closest_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let seg = b - a;
+    let t = ((point - a).dot(seg) / seg.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+
+    a + seg * t
+}
+
+/// Convex piece of a collider that shapes are cast against, see `BodyShape::cast`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConvexPart {
+    Shape { shape: BodyShape, pos: Vec2 },
+    Segment { a: Vec2, b: Vec2, one_sided: bool },
+}
+
+// how deep a body may already be behind a one-sided part and still be pushed out of it
+const ONE_SIDED_SLOP: f32 = 0.1;
+
+/// Static collider made of many parts. Only the parts near a body are tested against it
+pub trait TerrainCollider {
+    /// Bounds relative to the terrain position, as bottom-left and top-right corners
+    fn local_bounds(&self) -> (Vec2, Vec2);
+
+    /// Calls `on_contact` for every part touching the body, with normals pointing from the body to the terrain.
+    /// `prev_pos` is where the body was at the start of the substep, used by one-sided parts
+    fn contacts(
+        &self,
+        origin: Vec2,
+        pos: Vec2,
+        prev_pos: Vec2,
+        shape: BodyShape,
+        on_contact: &mut dyn FnMut(Contact),
+    );
+
+    /// Closest part hit by a ray from `from` along `delta`, as the fraction of `delta` travelled
+    /// and the surface normal facing the ray
+    fn cast_ray(&self, origin: Vec2, from: Vec2, delta: Vec2) -> Option<(f32, Vec2)>;
+
+    /// Calls `on_part` for every convex part within the world-space bounds `min` and `max`
+    fn parts(&self, origin: Vec2, min: Vec2, max: Vec2, on_part: &mut dyn FnMut(ConvexPart));
+}
+
+fn closest_hit(hits: impl Iterator<Item = (f32, Vec2)>) -> Option<(f32, Vec2)> {
+    hits.min_by(|(a, _), (b, _)| a.total_cmp(b))
+}
+
+fn segment_contact(
+    pos: Vec2,
+    prev_pos: Vec2,
+    shape: BodyShape,
+    a: Vec2,
+    b: Vec2,
+    one_sided: bool,
+) -> Option<Contact> {
+    let contact_at = |pos, one_sided| match shape {
+        BodyShape::Circle { radius } => ball_segment(pos, radius, a, b, one_sided),
+        BodyShape::Box { size } => box_segment(pos, size, a, b, one_sided),
+    };
+
+    let mut contact = contact_at(pos, one_sided)?;
+
+    // bodies passing through a one-sided part from behind are let through until they are clear of it
+    if one_sided {
+        let prev_penetration = contact_at(prev_pos, false).map_or(0., |prev| prev.penetration);
+
+        if prev_penetration > ONE_SIDED_SLOP || contact.penetration < prev_penetration {
+            return None;
+        }
+
+        contact.one_sided = true;
+    }
+
+    Some(contact)
+}
+
+fn segment_near(a: Vec2, b: Vec2, min: Vec2, max: Vec2) -> bool {
+    a.min(b).cmple(max).all() && a.max(b).cmpge(min).all()
+}
+
+/// Line segment between two points relative to the body position.
+/// One-sided segments only push bodies towards the left side of `a -> b`
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct SegmentCollider {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub one_sided: bool,
+}
+
+impl TerrainCollider for SegmentCollider {
+    fn local_bounds(&self) -> (Vec2, Vec2) {
+        (self.a.min(self.b), self.a.max(self.b))
+    }
+
+    fn contacts(
+        &self,
+        origin: Vec2,
+        pos: Vec2,
+        prev_pos: Vec2,
+        shape: BodyShape,
+        on_contact: &mut dyn FnMut(Contact),
+    ) {
+        if let Some(contact) = segment_contact(
+            pos,
+            prev_pos,
+            shape,
+            origin + self.a,
+            origin + self.b,
+            self.one_sided,
+        ) {
+            on_contact(contact);
+        }
+    }
+
+    fn cast_ray(&self, origin: Vec2, from: Vec2, delta: Vec2) -> Option<(f32, Vec2)> {
+        ray_segment(
+            from,
+            delta,
+            origin + self.a,
+            origin + self.b,
+            self.one_sided,
+        )
+    }
+
+    fn parts(&self, origin: Vec2, min: Vec2, max: Vec2, on_part: &mut dyn FnMut(ConvexPart)) {
+        if segment_near(self.a, self.b, min - origin, max - origin) {
+            on_part(ConvexPart::Segment {
+                a: origin + self.a,
+                b: origin + self.b,
+                one_sided: self.one_sided,
+            });
+        }
+    }
+}
+
+/// Polyline through points relative to the body position.
+/// One-sided chains only push bodies towards the left side of the walking direction
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component))]
+pub struct ChainCollider {
+    pub points: Vec<Vec2>,
+    pub one_sided: bool,
+}
+
+impl TerrainCollider for ChainCollider {
+    fn local_bounds(&self) -> (Vec2, Vec2) {
+        self.points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        )
+    }
+
+    fn contacts(
+        &self,
+        origin: Vec2,
+        pos: Vec2,
+        prev_pos: Vec2,
+        shape: BodyShape,
+        on_contact: &mut dyn FnMut(Contact),
+    ) {
+        let half_extents = shape.half_extents();
+        let (min, max) = (pos - origin - half_extents, pos - origin + half_extents);
+
+        for segment in self.points.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+
+            if !segment_near(a, b, min, max) {
+                continue;
+            }
+
+            if let Some(contact) =
+                segment_contact(pos, prev_pos, shape, origin + a, origin + b, self.one_sided)
+            {
+                on_contact(contact);
+            }
+        }
+    }
+
+    fn cast_ray(&self, origin: Vec2, from: Vec2, delta: Vec2) -> Option<(f32, Vec2)> {
+        closest_hit(self.points.windows(2).filter_map(|segment| {
+            ray_segment(
+                from,
+                delta,
+                origin + segment[0],
+                origin + segment[1],
+                self.one_sided,
+            )
+        }))
+    }
+
+    fn parts(&self, origin: Vec2, min: Vec2, max: Vec2, on_part: &mut dyn FnMut(ConvexPart)) {
+        for segment in self.points.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+
+            if segment_near(a, b, min - origin, max - origin) {
+                on_part(ConvexPart::Segment {
+                    a: origin + a,
+                    b: origin + b,
+                    one_sided: self.one_sided,
+                });
+            }
+        }
+    }
+}
+
+/// Rectangle of tiles, in tile coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub min: UVec2,
+    // exclusive
+    pub max: UVec2,
+}
+
+/// Grid of solid tiles merged into as few boxes as possible.
+/// Tile `(0, 0)` has its bottom-left corner at the body position
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "bevy", derive(Component))]
+pub struct TileMapCollider {
+    tile_size: Vec2,
+    size: UVec2,
+    rects: Vec<TileRect>,
+    // index into `rects` for every tile, row by row
+    tile_rects: Vec<Option<usize>>,
+}
+
+impl TileMapCollider {
+    /// `solid` holds `width` tiles per row, starting from the bottom row
+    pub fn new(tile_size: Vec2, width: u32, solid: &[bool]) -> Self {
+        let height = (solid.len() as u32).checked_div(width).unwrap_or(0);
+        let index = |x: u32, y: u32| (y * width + x) as usize;
+        let mut tile_rects = vec![None; (width * height) as usize];
+        let mut rects: Vec<TileRect> = Vec::new();
+
+        for y in 0..height {
+            let mut x = 0;
+
+            while x < width {
+                if !solid[index(x, y)] {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+
+                while x < width && solid[index(x, y)] {
+                    x += 1;
+                }
+
+                // grow the rect of the same run in the row below, if there is one
+                let below = (y > 0)
+                    .then(|| tile_rects[index(start, y - 1)])
+                    .flatten()
+                    .filter(|&rect: &usize| {
+                        rects[rect].min.x == start
+                            && rects[rect].max.x == x
+                            && rects[rect].max.y == y
+                    });
+
+                let rect = match below {
+                    Some(rect) => {
+                        rects[rect].max.y = y + 1;
+                        rect
+                    }
+                    None => {
+                        rects.push(TileRect {
+                            min: UVec2::new(start, y),
+                            max: UVec2::new(x, y + 1),
+                        });
+                        rects.len() - 1
+                    }
+                };
+
+                for tile_x in start..x {
+                    tile_rects[index(tile_x, y)] = Some(rect);
+                }
+            }
+        }
+
+        Self {
+            tile_size,
+            size: UVec2::new(width, height),
+            rects,
+            tile_rects,
+        }
+    }
+
+    pub fn rects(&self) -> &[TileRect] {
+        &self.rects
+    }
+
+    /// Center and size of a rect relative to the body position
+    pub fn rect_bounds(&self, rect: &TileRect) -> (Vec2, Vec2) {
+        let min = rect.min.as_vec2() * self.tile_size;
+        let max = rect.max.as_vec2() * self.tile_size;
+
+        ((min + max) / 2., max - min)
+    }
+}
+
+impl TerrainCollider for TileMapCollider {
+    fn local_bounds(&self) -> (Vec2, Vec2) {
+        (Vec2::ZERO, self.size.as_vec2() * self.tile_size)
+    }
+
+    fn contacts(
+        &self,
+        origin: Vec2,
+        pos: Vec2,
+        _prev_pos: Vec2,
+        shape: BodyShape,
+        on_contact: &mut dyn FnMut(Contact),
+    ) {
+        if self.size.x == 0 || self.size.y == 0 {
+            return;
+        }
+
+        let half_extents = shape.half_extents();
+        let max_tile = self.size.as_ivec2() - IVec2::ONE;
+        let min = ((pos - origin - half_extents) / self.tile_size)
+            .floor()
+            .as_ivec2()
+            .max(IVec2::ZERO);
+        let max = ((pos - origin + half_extents) / self.tile_size)
+            .floor()
+            .as_ivec2()
+            .min(max_tile);
+        let mut visited: Vec<usize> = Vec::new();
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let Some(rect) = self.tile_rects[(y as u32 * self.size.x + x as u32) as usize]
+                else {
+                    continue;
+                };
+
+                if visited.contains(&rect) {
+                    continue;
+                }
+
+                visited.push(rect);
+
+                let (center, size) = self.rect_bounds(&self.rects[rect]);
+                let contact = match shape {
+                    BodyShape::Circle { radius } => ball_box(pos, radius, origin + center, size),
+                    BodyShape::Box { size: box_size } => {
+                        box_box(pos, box_size, origin + center, size)
+                    }
+                };
+
+                if let Some(contact) = contact {
+                    on_contact(contact);
+                }
+            }
+        }
+    }
+
+    fn cast_ray(&self, origin: Vec2, from: Vec2, delta: Vec2) -> Option<(f32, Vec2)> {
+        closest_hit(self.rects.iter().filter_map(|rect| {
+            let (center, size) = self.rect_bounds(rect);
+
+            ray_box(from, delta, origin + center, size)
+        }))
+    }
+
+    fn parts(&self, origin: Vec2, min: Vec2, max: Vec2, on_part: &mut dyn FnMut(ConvexPart)) {
+        for rect in self.rects.iter() {
+            let (center, size) = self.rect_bounds(rect);
+            let pos = origin + center;
+
+            if (pos - size / 2.).cmple(max).all() && (pos + size / 2.).cmpge(min).all() {
+                on_part(ConvexPart::Shape {
+                    shape: BodyShape::Box { size },
+                    pos,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_map_merges_tiles() {
+        #[rustfmt::skip]
+        let solid = [
+            true, true, true, true,
+            true, true, false, false,
+            true, true, false, true,
+        ];
+        let tile_map = TileMapCollider::new(Vec2::ONE, 4, &solid);
+
+        assert_eq!(
+            tile_map.rects(),
+            &[
+                TileRect {
+                    min: UVec2::new(0, 0),
+                    max: UVec2::new(4, 1),
+                },
+                TileRect {
+                    min: UVec2::new(0, 1),
+                    max: UVec2::new(2, 3),
+                },
+                TileRect {
+                    min: UVec2::new(3, 2),
+                    max: UVec2::new(4, 3),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_map_contacts() {
+        let tile_map = TileMapCollider::new(Vec2::splat(10.), 3, &[true, true, true]);
+        let mut contacts = Vec::new();
+
+        tile_map.contacts(
+            Vec2::ZERO,
+            Vec2::new(15., 14.),
+            Vec2::new(15., 14.),
+            BodyShape::Circle { radius: 5. },
+            &mut |contact| contacts.push(contact),
+        );
+
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].normal, -Vec2::Y);
+        assert!((contacts[0].penetration - 1.).abs() < 0.001);
+    }
+
+    #[test]
+    fn long_casts_never_step_over_thin_segments() {
+        let wall = ConvexPart::Segment {
+            a: Vec2::new(5000., -1.),
+            b: Vec2::new(5000., 1.),
+            one_sided: false,
+        };
+        let delta = Vec2::new(10_000., 0.);
+
+        for shape in [
+            BodyShape::Circle { radius: 0.1 },
+            BodyShape::Box {
+                size: Vec2::splat(0.2),
+            },
+        ] {
+            let (fraction, normal) = shape.cast(Vec2::ZERO, delta, &wall, 1e-3).unwrap();
+
+            assert!(
+                (fraction * delta.x - 4999.9).abs() < 0.01,
+                "{shape:?} {fraction}"
+            );
+            assert!(normal.abs_diff_eq(Vec2::X, 1e-3), "{shape:?} {normal:?}");
+        }
+
+        // facing away from the cast, so it is passed from behind
+        let one_sided = ConvexPart::Segment {
+            a: Vec2::new(5000., 1.),
+            b: Vec2::new(5000., -1.),
+            one_sided: true,
+        };
+        let circle = BodyShape::Circle { radius: 0.1 };
+
+        assert_eq!(circle.cast(Vec2::ZERO, delta, &one_sided, 1e-3), None);
+        // running alongside it without ever touching
+        assert_eq!(
+            circle.cast(Vec2::new(0., 1.2), Vec2::new(10_000., 0.), &wall, 1e-3),
+            None
+        );
+    }
+
+    #[test]
+    fn chain_skips_far_segments() {
+        let chain = ChainCollider {
+            points: vec![Vec2::new(0., 0.), Vec2::new(10., 0.), Vec2::new(20., 10.)],
+            one_sided: false,
+        };
+        let mut contacts = Vec::new();
+
+        chain.contacts(
+            Vec2::ZERO,
+            Vec2::new(5., 1.),
+            Vec2::new(5., 1.),
+            BodyShape::Box {
+                size: Vec2::splat(4.),
+            },
+            &mut |contact| contacts.push(contact),
+        );
+
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].normal, -Vec2::Y);
+    }
+}
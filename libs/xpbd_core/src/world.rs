@@ -0,0 +1,670 @@
+use glam::Vec2;
+
+use super::{
+    body::{
+        Aabb, CollisionLayers, CombineRule, ContactSoftness, LockedAxes, MaxLinearSpeed, RigidBody,
+    },
+    bvh::Bvh,
+    colliders::{BoxCollider, CircleCollider, ColliderShape},
+    compound::CompoundCollider,
+    consts::{NUM_SUBSTEPS, SUB_DT},
+    contact::Contact,
+    pipeline::{
+        collect_collision_pairs, collect_contacts, collect_terrain_contacts, solve_velocities,
+        BodyPositions, BodyVelocities, BroadPhaseBody, PlacedBody, PositionState, Solver,
+        VelocityState,
+    },
+    solver::{
+        constrain_distance, derive_vel, integrate, limit_motion, limited_vel, SolverSettings,
+    },
+    terrain::{BodyShape, ChainCollider, SegmentCollider, TerrainCollider, TileMapCollider},
+};
+
+/// Index of a body in its `World`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyHandle(pub usize);
+
+#[derive(Debug, Clone)]
+pub enum Collider {
+    Circle(CircleCollider),
+    Box(BoxCollider),
+    Segment(SegmentCollider),
+    Chain(ChainCollider),
+    TileMap(TileMapCollider),
+    Compound(CompoundCollider),
+}
+
+impl Collider {
+    fn shape(&self) -> Option<BodyShape> {
+        match self {
+            Collider::Circle(circle) => Some(BodyShape::Circle {
+                radius: circle.radius,
+            }),
+            Collider::Box(box_) => Some(BodyShape::Box { size: box_.size }),
+            _ => None,
+        }
+    }
+
+    // calls `f` with the shapes of a body and their offsets, for any collider but terrain
+    fn with_parts<R>(&self, f: impl FnOnce(&[(Vec2, BodyShape)]) -> R) -> Option<R> {
+        if let Collider::Compound(compound) = self {
+            return Some(f(&compound.parts));
+        }
+
+        self.shape().map(|shape| f(&[(Vec2::ZERO, shape)]))
+    }
+
+    fn terrain(&self) -> Option<&dyn TerrainCollider> {
+        match self {
+            Collider::Segment(segment) => Some(segment),
+            Collider::Chain(chain) => Some(chain),
+            Collider::TileMap(tile_map) => Some(tile_map),
+            _ => None,
+        }
+    }
+}
+
+/// Body of a `World`, with the same defaults as the bundles of the plugin
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub rigid_body: RigidBody,
+    pub collider: Collider,
+    pub pos: Vec2,
+    pub prev_pos: Vec2,
+    pub vel: Vec2,
+    pub pre_solve_vel: Vec2,
+    pub mass: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub restitution_combine: CombineRule,
+    pub friction_combine: CombineRule,
+    pub layers: CollisionLayers,
+    pub locked_axes: LockedAxes,
+    pub max_linear_speed: MaxLinearSpeed,
+    pub contact_softness: ContactSoftness,
+    aabb: Aabb,
+}
+
+impl Body {
+    pub fn new(rigid_body: RigidBody, pos: Vec2, collider: Collider) -> Self {
+        Self {
+            rigid_body,
+            collider,
+            pos,
+            prev_pos: pos,
+            vel: Vec2::ZERO,
+            pre_solve_vel: Vec2::ZERO,
+            mass: 1.,
+            restitution: 0.3,
+            friction: 0.,
+            restitution_combine: CombineRule::default(),
+            friction_combine: CombineRule::default(),
+            layers: CollisionLayers::default(),
+            contact_softness: ContactSoftness::default(),
+            locked_axes: LockedAxes::default(),
+            max_linear_speed: MaxLinearSpeed::default(),
+            aabb: Aabb::default(),
+        }
+    }
+
+    pub fn dynamic(pos: Vec2, collider: Collider) -> Self {
+        Self::new(RigidBody::Dynamic, pos, collider)
+    }
+
+    pub fn fixed(pos: Vec2, collider: Collider) -> Self {
+        Self::new(RigidBody::Static, pos, collider)
+    }
+
+    pub fn with_vel(self, vel: Vec2) -> Self {
+        Self {
+            prev_pos: self.pos - vel * SUB_DT,
+            vel,
+            ..self
+        }
+    }
+
+    /// Sets the mass from the collider area, for circles, boxes and compounds
+    pub fn with_density(self, density: f32) -> Self {
+        let mass = match &self.collider {
+            Collider::Circle(circle) => density * circle.area(),
+            Collider::Box(box_) => density * box_.area(),
+            Collider::Compound(compound) => compound.mass_properties(density).0,
+            _ => return self,
+        };
+
+        Self { mass, ..self }
+    }
+
+    pub fn with_layers(self, layers: CollisionLayers) -> Self {
+        Self { layers, ..self }
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        if self.rigid_body.is_static() || self.mass <= 0. {
+            0.
+        } else {
+            1. / self.mass
+        }
+    }
+
+    fn limit_motion(&mut self) {
+        limit_motion(
+            &mut self.pos,
+            self.prev_pos,
+            &mut self.vel,
+            self.locked_axes,
+            self.max_linear_speed,
+        );
+    }
+
+    // calls `f` with the shapes of the body where it is, for any collider but terrain
+    fn with_placed<R>(&self, key: usize, f: impl FnOnce(&PlacedBody<'_, usize>) -> R) -> Option<R> {
+        self.collider.with_parts(|parts| {
+            f(&PlacedBody {
+                key,
+                parts,
+                pos: self.pos,
+                prev_pos: self.prev_pos,
+                inv_mass: self.inv_mass(),
+            })
+        })
+    }
+
+    /// Bounds used by the broad phase during the last step
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
+
+    fn update_aabb(&mut self) {
+        if let Some(shape) = self.collider.shape() {
+            self.aabb = Aabb::moving(self.pos, self.vel, shape.half_extents());
+        } else if let Collider::Compound(compound) = &self.collider {
+            let (min, max) = compound.local_bounds();
+
+            self.aabb = Aabb::moving(self.pos + (min + max) / 2., self.vel, (max - min) / 2.);
+        } else if let Some(terrain) = self.collider.terrain() {
+            let (min, max) = terrain.local_bounds();
+
+            self.aabb = Aabb {
+                min: self.pos + min,
+                max: self.pos + max,
+            };
+        }
+    }
+}
+
+/// Keeps two bodies of a `World` at `rest_length` from each other
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceConstraint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    pub rest_length: f32,
+    pub compliance: f32,
+}
+
+/// Rigid bodies and distance constraints simulated without an engine, in the same order of steps as `XpbdPlugin`.
+/// The plugin doesn't drive a `World`, its systems run the same `pipeline` on components instead.
+/// Both give the same results for the colliders, coefficients, motion limits, contact softness and solver modes of `Body`,
+/// while fluids, soft bodies, force fields, characters and contact modifiers only exist in the plugin
+#[derive(Debug)]
+pub struct World {
+    pub gravity: Vec2,
+    /// Relative normal speed below which contacts don't bounce
+    pub restitution_threshold: f32,
+    pub solver: SolverSettings,
+    bodies: Vec<Body>,
+    distance_constraints: Vec<DistanceConstraint>,
+    // static bodies the BVH was built from, to rebuild it only when they change
+    statics: Vec<(usize, Aabb)>,
+    static_bvh: Bvh<usize>,
+    dynamics: Vec<BroadPhaseBody<usize, ()>>,
+    collision_pairs: Vec<(usize, usize)>,
+    contacts: Vec<(usize, usize, Contact)>,
+    contact_solver: Solver<usize>,
+    terrain_contacts: Vec<Contact>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            gravity: Vec2::new(0., -9.81),
+            restitution_threshold: 1.,
+            solver: SolverSettings::default(),
+            bodies: Vec::new(),
+            distance_constraints: Vec::new(),
+            statics: Vec::new(),
+            static_bvh: Bvh::default(),
+            dynamics: Vec::new(),
+            collision_pairs: Vec::new(),
+            contacts: Vec::new(),
+            contact_solver: Solver::default(),
+            terrain_contacts: Vec::new(),
+        }
+    }
+}
+
+impl World {
+    pub fn add_body(&mut self, body: Body) -> BodyHandle {
+        self.bodies.push(body);
+
+        BodyHandle(self.bodies.len() - 1)
+    }
+
+    pub fn add_distance_constraint(&mut self, constraint: DistanceConstraint) {
+        self.distance_constraints.push(constraint);
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> &Body {
+        &self.bodies[handle.0]
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> &mut Body {
+        &mut self.bodies[handle.0]
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &Body)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .map(|(index, body)| (BodyHandle(index), body))
+    }
+
+    /// Contacts solved in the last substep, with normals pointing from the first body to the second
+    pub fn contacts(&self) -> impl Iterator<Item = (BodyHandle, BodyHandle, &Contact)> {
+        self.contacts
+            .iter()
+            .map(|(a, b, contact)| (BodyHandle(*a), BodyHandle(*b), contact))
+    }
+
+    /// Advances the simulation by `DELTA_TIME`
+    pub fn step(&mut self) {
+        for body in self.bodies.iter_mut() {
+            body.update_aabb();
+        }
+
+        self.update_static_bvh();
+        self.collect_collision_pairs();
+
+        for _ in 0..NUM_SUBSTEPS {
+            self.integrate();
+            self.collect_contacts();
+            self.solve_contacts();
+            self.solve_distance_constraints();
+
+            for body in self.bodies.iter_mut() {
+                body.vel = derive_vel(body.pos, body.prev_pos);
+                body.limit_motion();
+            }
+
+            self.solve_vel();
+
+            for body in self.bodies.iter_mut() {
+                body.vel = limited_vel(body.vel, body.locked_axes, body.max_linear_speed);
+            }
+        }
+    }
+
+    // static bodies rarely move, so the tree is only rebuilt when one was added, moved or switched
+    fn update_static_bvh(&mut self) {
+        let statics = self
+            .bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, body)| body.rigid_body.is_static())
+            .map(|(index, body)| (index, body.aabb));
+
+        if statics.clone().eq(self.statics.iter().copied()) {
+            return;
+        }
+
+        self.statics = statics.collect();
+        self.static_bvh = Bvh::build(self.statics.clone());
+    }
+
+    fn collect_collision_pairs(&mut self) {
+        let bodies = &self.bodies;
+
+        self.dynamics.clear();
+        self.dynamics.extend(
+            bodies
+                .iter()
+                .enumerate()
+                .filter(|(_, body)| !body.rigid_body.is_static())
+                .map(|(index, body)| BroadPhaseBody {
+                    key: index,
+                    aabb: body.aabb,
+                    layers: body.layers,
+                    group: None,
+                }),
+        );
+
+        collect_collision_pairs(
+            &self.dynamics,
+            &self.static_bvh,
+            |index| bodies[index].layers,
+            &mut self.collision_pairs,
+        );
+    }
+
+    fn integrate(&mut self) {
+        for body in self.bodies.iter_mut() {
+            body.prev_pos = body.pos;
+
+            if body.rigid_body.is_static() {
+                body.vel = Vec2::ZERO;
+                body.pre_solve_vel = Vec2::ZERO;
+                continue;
+            }
+
+            let inv_mass = body.inv_mass();
+
+            integrate(
+                &mut body.pos,
+                &mut body.vel,
+                body.mass * self.gravity,
+                inv_mass,
+            );
+            body.limit_motion();
+            body.pre_solve_vel = body.vel;
+        }
+    }
+
+    fn collect_contacts(&mut self) {
+        self.contacts.clear();
+
+        for &(a, b) in self.collision_pairs.iter() {
+            let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+            let contacts = &mut self.contacts;
+
+            let touching = body_a.with_placed(a, |placed_a| {
+                body_b.with_placed(b, |placed_b| collect_contacts(placed_a, placed_b, contacts))
+            });
+
+            if touching.flatten().is_some() {
+                continue;
+            }
+
+            let (body_index, terrain_index) = if body_b.collider.terrain().is_some() {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            let (body, terrain) = (&self.bodies[body_index], &self.bodies[terrain_index]);
+            let Some(collider) = terrain.collider.terrain() else {
+                continue;
+            };
+            let terrain_contacts = &mut self.terrain_contacts;
+
+            body.with_placed(body_index, |placed| {
+                collect_terrain_contacts(
+                    placed,
+                    terrain_index,
+                    terrain.pos,
+                    collider,
+                    terrain_contacts,
+                    contacts,
+                )
+            });
+        }
+    }
+
+    fn solve_contacts(&mut self) {
+        self.contact_solver
+            .solve_positions(&self.solver, &mut self.contacts, &mut self.bodies[..]);
+    }
+
+    fn solve_distance_constraints(&mut self) {
+        for constraint in self.distance_constraints.iter() {
+            let (a, b) = (constraint.body_a.0, constraint.body_b.0);
+
+            if a == b {
+                continue;
+            }
+
+            let [body_a, body_b] = get_pair_mut(&mut self.bodies, a, b);
+            let (inv_mass_a, inv_mass_b) = (
+                body_a.locked_axes.inv_mass(body_a.inv_mass()),
+                body_b.locked_axes.inv_mass(body_b.inv_mass()),
+            );
+
+            constrain_distance(
+                &mut body_a.pos,
+                &mut body_b.pos,
+                inv_mass_a,
+                inv_mass_b,
+                constraint.rest_length,
+                constraint.compliance,
+            );
+        }
+    }
+
+    fn solve_vel(&mut self) {
+        solve_velocities(
+            &self.contacts,
+            self.restitution_threshold,
+            &mut self.bodies[..],
+        );
+    }
+}
+
+impl BodyPositions<usize> for [Body] {
+    fn get(&self, key: usize) -> Option<PositionState> {
+        let body = <[Body]>::get(self, key)?;
+
+        Some(PositionState {
+            pos: body.pos,
+            prev_pos: body.prev_pos,
+            inv_mass: body.locked_axes.inv_mass(body.inv_mass()),
+            contact_softness: body.contact_softness,
+        })
+    }
+
+    fn set_pos(&mut self, key: usize, pos: Vec2) {
+        self[key].pos = pos;
+    }
+}
+
+impl BodyVelocities<usize> for [Body] {
+    fn get(&self, key: usize) -> Option<VelocityState> {
+        let body = <[Body]>::get(self, key)?;
+
+        Some(VelocityState {
+            vel: body.vel,
+            pre_solve_vel: body.pre_solve_vel,
+            inv_mass: body.locked_axes.inv_mass(body.inv_mass()),
+            restitution: body.restitution,
+            restitution_combine: body.restitution_combine,
+            friction: body.friction,
+            friction_combine: body.friction_combine,
+        })
+    }
+
+    fn set_vel(&mut self, key: usize, vel: Vec2) {
+        self[key].vel = vel;
+    }
+}
+
+fn get_pair_mut(bodies: &mut [Body], a: usize, b: usize) -> [&mut Body; 2] {
+    if a < b {
+        let (left, right) = bodies.split_at_mut(b);
+
+        [&mut left[a], &mut right[0]]
+    } else {
+        let (left, right) = bodies.split_at_mut(a);
+
+        [&mut right[0], &mut left[b]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::SolverMode;
+
+    use super::*;
+
+    #[test]
+    fn box_comes_to_rest_on_ground() {
+        let mut world = World::default();
+
+        world.add_body(Body::fixed(
+            Vec2::ZERO,
+            Collider::Box(BoxCollider {
+                size: Vec2::new(10., 1.),
+            }),
+        ));
+
+        let falling = world.add_body(Body::dynamic(
+            Vec2::new(0., 2.),
+            Collider::Box(BoxCollider { size: Vec2::ONE }),
+        ));
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        let body = world.body(falling);
+
+        assert!((body.pos.y - 1.).abs() < 0.01, "{:?}", body.pos);
+        assert!(body.vel.length() < 0.1, "{:?}", body.vel);
+    }
+
+    #[test]
+    fn resting_box_gets_the_same_friction_in_both_solver_modes() {
+        let slide = |mode| {
+            let mut world = World {
+                solver: SolverSettings {
+                    mode,
+                    ..SolverSettings::default()
+                },
+                ..World::default()
+            };
+
+            // across two grounds, so it rests on a contact with each
+            for x in [-5., 5.] {
+                let mut ground = Body::fixed(
+                    Vec2::new(x, 0.),
+                    Collider::Box(BoxCollider {
+                        size: Vec2::new(10., 1.),
+                    }),
+                );
+
+                ground.friction = 0.5;
+                world.add_body(ground);
+            }
+
+            let mut sliding = Body::dynamic(
+                Vec2::new(-0.4, 1.),
+                Collider::Box(BoxCollider { size: Vec2::ONE }),
+            )
+            .with_vel(Vec2::new(3., 0.));
+
+            sliding.friction = 0.5;
+
+            let sliding = world.add_body(sliding);
+
+            for _ in 0..20 {
+                world.step();
+            }
+
+            assert_eq!(world.contacts().count(), 2, "{mode:?}");
+
+            world.body(sliding).vel.x
+        };
+        let (gauss_seidel, jacobi) = (slide(SolverMode::GaussSeidel), slide(SolverMode::Jacobi));
+
+        // slowed down by 0.5 * 9.81 over a third of a second in both
+        assert!(
+            (gauss_seidel - (3. - 0.5 * 9.81 / 3.)).abs() < 0.1,
+            "{gauss_seidel}"
+        );
+        assert!(
+            (gauss_seidel - jacobi).abs() < 1e-3,
+            "{gauss_seidel} {jacobi}"
+        );
+    }
+
+    #[test]
+    fn moved_static_bodies_are_found_where_they_are() {
+        let mut world = World::default();
+        let ground = world.add_body(Body::fixed(
+            Vec2::ZERO,
+            Collider::Box(BoxCollider {
+                size: Vec2::new(10., 1.),
+            }),
+        ));
+        let falling = world.add_body(Body::dynamic(
+            Vec2::new(0., 2.),
+            Collider::Box(BoxCollider { size: Vec2::ONE }),
+        ));
+
+        for _ in 0..60 {
+            world.step();
+        }
+
+        world.body_mut(ground).pos.y = -5.;
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        let body = world.body(falling);
+
+        assert!((body.pos.y + 4.).abs() < 0.01, "{:?}", body.pos);
+    }
+
+    #[test]
+    fn ball_rolls_down_segment() {
+        let mut world = World::default();
+
+        world.add_body(Body::fixed(
+            Vec2::ZERO,
+            Collider::Segment(SegmentCollider {
+                a: Vec2::new(-10., 5.),
+                b: Vec2::new(10., -5.),
+                one_sided: false,
+            }),
+        ));
+
+        let ball = world.add_body(Body::dynamic(
+            Vec2::new(-5., 4.),
+            Collider::Circle(CircleCollider { radius: 0.5 }),
+        ));
+
+        for _ in 0..60 {
+            world.step();
+        }
+
+        let body = world.body(ball);
+
+        assert!(body.pos.x > -5., "{:?}", body.pos);
+        assert!(body.pos.y > -body.pos.x / 2., "{:?}", body.pos);
+    }
+
+    #[test]
+    fn distance_constraint_holds_pendulum() {
+        let mut world = World::default();
+        let pivot = world.add_body(Body::fixed(
+            Vec2::ZERO,
+            Collider::Circle(CircleCollider { radius: 0.1 }),
+        ));
+        let bob = world.add_body(Body::dynamic(
+            Vec2::new(2., 0.),
+            Collider::Circle(CircleCollider { radius: 0.1 }),
+        ));
+
+        world.add_distance_constraint(DistanceConstraint {
+            body_a: pivot,
+            body_b: bob,
+            rest_length: 2.,
+            compliance: 0.,
+        });
+
+        for _ in 0..60 {
+            world.step();
+        }
+
+        let length = world.body(bob).pos.length();
+
+        assert!((length - 2.).abs() < 0.01, "{length}");
+    }
+}